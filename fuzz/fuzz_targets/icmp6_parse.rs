@@ -0,0 +1,33 @@
+#![no_main]
+
+use ftthd::icmp6::packet::{Packet, PacketHopByHop, PacketHopLimit};
+use ftthd::icmp6::Icmp6Parser;
+
+use libfuzzer_sys::fuzz_target;
+
+// input layout: flags, hop limit, hop-by-hop length, hop-by-hop bytes, ICMPv6 message
+fuzz_target!(|input: &[u8]| {
+    if input.len() < 3 {
+        return;
+    }
+    let flags = input[0];
+    let hop_limit = input[1];
+    let hop_by_hop_len = (input[2] as usize).min(input.len() - 3);
+    let hop_by_hop = &input[3..(3 + hop_by_hop_len)];
+    let data = &input[(3 + hop_by_hop_len)..];
+    if data.len() > 65536 {
+        return;
+    }
+
+    let mut packet = Packet::new();
+    packet.data[..data.len()].copy_from_slice(data);
+    packet.data_len = data.len();
+    if flags & 0x01 != 0 {
+        packet.hop_limit = Some(PacketHopLimit { hop_limit });
+    }
+    if flags & 0x02 != 0 {
+        packet.hop_by_hop = Some(PacketHopByHop { hop_by_hop: hop_by_hop.to_vec() });
+    }
+
+    let _ = Icmp6Parser::new_from_packet(packet).parse();
+});
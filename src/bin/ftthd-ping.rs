@@ -17,7 +17,9 @@ async fn main() {
 
         let expected_identifier = identifier;
         loop {
-            recv_socket.recv_parser(&mut parser).await.expect("Failed to receive packet");
+            if recv_socket.recv_parser(&mut parser).await.expect("Failed to receive packet").is_some() {
+                continue;
+            }
             let src_addr = parser.packet().target_addr;
             let ttl = parser.packet().hop_limit.unwrap().hop_limit;
             let parsed = parser.parse();
@@ -30,6 +32,26 @@ async fn main() {
                             }
                             println!("Received echo reply from {}: ttl={}, sequence={}, datalen={}", src_addr, ttl, sequence, data.len());
                         }
+                        ftthd::icmp6::Icmp6Packet::DestinationUnreachable(error) => {
+                            if let Some(sequence) = echo_sequence(&error.invoking_packet, expected_identifier) {
+                                println!("Destination unreachable from {}: code={:?}, sequence={}", src_addr, error.code, sequence);
+                            }
+                        }
+                        ftthd::icmp6::Icmp6Packet::PacketTooBig(error) => {
+                            if let Some(sequence) = echo_sequence(&error.invoking_packet, expected_identifier) {
+                                println!("Packet too big from {}: mtu={}, sequence={}", src_addr, error.mtu, sequence);
+                            }
+                        }
+                        ftthd::icmp6::Icmp6Packet::TimeExceeded(error) => {
+                            if let Some(sequence) = echo_sequence(&error.invoking_packet, expected_identifier) {
+                                println!("Time exceeded from {}: code={:?}, sequence={}", src_addr, error.code, sequence);
+                            }
+                        }
+                        ftthd::icmp6::Icmp6Packet::ParameterProblem(error) => {
+                            if let Some(sequence) = echo_sequence(&error.invoking_packet, expected_identifier) {
+                                println!("Parameter problem from {}: code={:?}, pointer={}, sequence={}", src_addr, error.code, error.pointer, sequence);
+                            }
+                        }
                         _ => {}
                     }
                 }
@@ -57,3 +79,16 @@ async fn main() {
         tokio::time::sleep(std::time::Duration::from_secs(1)).await;
     }
 }
+
+/// Sequence number of our Echo Request quoted in an error message
+fn echo_sequence(invoking_packet: &ftthd::icmp6::error_message::InvokingPacket, identifier: u16) -> Option<u16> {
+    let header = invoking_packet.header.as_ref()?;
+    let payload = &invoking_packet.payload;
+    if header.next_header != 58 || payload.len() < 8 || payload[0] != 128 {
+        return None;
+    }
+    if u16::from_be_bytes([payload[4], payload[5]]) != identifier {
+        return None;
+    }
+    Some(u16::from_be_bytes([payload[6], payload[7]]))
+}
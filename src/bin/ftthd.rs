@@ -2,17 +2,26 @@
 use ftthd::interface::{InterfaceId, InterfaceStateManager};
 use ftthd::group::MldSubscriptionManager;
 use ftthd::group::NdpMulticastManager;
+use ftthd::config::ProxyMode;
+use ftthd::icmp6::view::Icmp6PacketRef;
 
 use clap::{Parser, Subcommand};
+use parking_lot::RwLock;
 
+use std::collections::HashMap;
 use std::collections::HashSet;
 use std::net::Ipv6Addr;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 
 fn main() {
     env_logger::init();
     let args = Cli::parse();
+    if let Command::CheckConfig = args.subcmd {
+        std::process::exit(check_config(&args.config));
+    }
+
     let config_manager = ftthd::config::ConfigManager::new(&args.config);
     let rt = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
@@ -62,6 +71,22 @@ fn main() {
     });
 }
 
+/// Prints every problem in the configuration file; returns the process exit code
+fn check_config(path: &std::path::Path) -> i32 {
+    match ftthd::config::Config::check_file(path) {
+        Ok(_) => {
+            println!("{}: OK", path.display());
+            0
+        }
+        Err(diagnostics) => {
+            for diagnostic in diagnostics {
+                eprintln!("{}", diagnostic);
+            }
+            1
+        }
+    }
+}
+
 async fn start(config: ftthd::config::ConfigManager) {
     if !config.is_loaded() {
         log::warn!("Configuration not loaded, waiting til configured");
@@ -74,8 +99,12 @@ async fn start(config: ftthd::config::ConfigManager) {
         log::debug!("Configuration: {:?}", config_data);
     }
 
+    let proxy_mode = config.get().unwrap().global.proxy_mode;
+
     tokio::fs::write("/proc/sys/net/ipv6/conf/all/forwarding", "1").await.unwrap();
-    tokio::fs::write("/proc/sys/net/ipv6/conf/all/proxy_ndp", "1").await.unwrap();
+    if proxy_mode == ProxyMode::NdpProxy {
+        tokio::fs::write("/proc/sys/net/ipv6/conf/all/proxy_ndp", "1").await.unwrap();
+    }
 
     let if_manager = InterfaceStateManager::new().await;
 
@@ -97,20 +126,33 @@ async fn start(config: ftthd::config::ConfigManager) {
     let rtnl_route = rtnl.route();
     let rtnl_neighbor = rtnl.neighbor();
 
-    let downstream_if_ids = config.get().unwrap().interfaces.downstreams.iter().map(|name| {
-        if_manager.get_index_by_name(name).unwrap()
-    }).collect::<Vec<_>>();
+    let mut downstream_if_ids = Vec::new();
+    for name in config.get().unwrap().interfaces.downstreams.iter() {
+        if let Some(if_id) = if_manager.get_index_by_name(name) {
+            downstream_if_ids.push(if_id);
+        } else {
+            log::error!("Unknown downstream interface: {}", name);
+            return;
+        }
+    }
 
-    let upstream_if_id = if_manager.get_index_by_name(&config.get().unwrap().interfaces.upstream).unwrap();
+    let upstream_if_id = if let Some(if_id) = if_manager.get_index_by_name(&config.get().unwrap().interfaces.upstream) {
+        if_id
+    } else {
+        log::error!("Unknown upstream interface: {}", config.get().unwrap().interfaces.upstream);
+        return;
+    };
 
-    let upstream_global_addrs = rtnl.address().get_v6(upstream_if_id, ftthd::rtnl::addr::V6AddressRequestScope::Global).await.unwrap();
+    let mut upstream_global_addrs = rtnl.address().get_v6(upstream_if_id, ftthd::rtnl::addr::V6AddressRequestScope::Global).await.unwrap();
 
-    for addr in &upstream_global_addrs {
-        for if_id in downstream_if_ids.iter() {
-            let _ = rtnl_neighbor.proxy_delete(*if_id, std::net::IpAddr::V6(*addr)).await;
+    if proxy_mode == ProxyMode::NdpProxy {
+        for addr in &upstream_global_addrs {
+            for if_id in downstream_if_ids.iter() {
+                let _ = rtnl_neighbor.proxy_delete(*if_id, std::net::IpAddr::V6(*addr)).await;
 
-            if let Err(e) = rtnl_neighbor.proxy_add(*if_id, std::net::IpAddr::V6(*addr)).await {
-                log::error!("Failed to add proxy neighbor: {:?}", e);
+                if let Err(e) = rtnl_neighbor.proxy_add(*if_id, std::net::IpAddr::V6(*addr)).await {
+                    log::error!("Failed to add proxy neighbor: {:?}", e);
+                }
             }
         }
     }
@@ -128,135 +170,171 @@ async fn start(config: ftthd::config::ConfigManager) {
         let _ = rtnl_link.set_all_multicast_mode(if_id, true).await;
     }
 
-    let mut subscription_manager = MldSubscriptionManager::new(socket.clone(), config.get().unwrap().interfaces.clone());
+    let mut subscription_manager = MldSubscriptionManager::new(socket.clone(), config.get().unwrap().interfaces.clone(), config.get().unwrap().mld_querier);
+    subscription_manager.set_acls(&config.get().unwrap().mld_acl);
     let mut ndp_multicast_manager = NdpMulticastManager::new(socket.clone(), config.get().unwrap().interfaces.clone());
 
-    let mut parser = ftthd::icmp6::Icmp6Parser::new();
-    let mut writer = ftthd::icmp6::Icmp6Writer::new();
-    loop {
-        socket.recv_parser(&mut parser).await.unwrap();
-        let packet = parser.parse();
-        let packet = if let Ok(packet) = packet {
-            packet
-        } else {
-            log::error!("Failed to parse packet: {:?}", packet);
-            continue;
-        };
-        let raw_packet = parser.packet();
-        match packet {
-            ftthd::icmp6::Icmp6Packet::RouterSolicitation(mut rs) => {
-                let info = raw_packet.info.unwrap();
-                let in_if = info.if_index;
-                let dst = info.addr;
-                let if_name = if_manager.get(in_if).unwrap().if_name;
-
-                let config = config.get().unwrap();
-
-                if !config.interfaces.downstreams.contains(&if_name) {
-                    log::debug!("Received Router Solicitation from non-downstream interface: {}", if_name);
-                    continue;
-                }
+    let dhcp6_server_config = config.get().unwrap().dhcp6_server;
+    let server_options = Arc::new(RwLock::new(ftthd::dhcp6::server::ServerOptions {
+        dns_servers: dhcp6_server_config.dns_servers.clone(),
+        domain_list: dhcp6_server_config.domain_list.clone(),
+    }));
+
+    // options not configured statically are learned from upstream
+    let learned_server_options = if dhcp6_server_config.enabled && dhcp6_server_config.dns_servers.is_empty() && dhcp6_server_config.domain_list.is_empty() {
+        Some(server_options.clone())
+    } else {
+        None
+    };
+
+    // PD mode always originates RAs for the carved prefixes
+    let ra_server = if proxy_mode == ProxyMode::Dhcpv6Pd || config.get().unwrap().ra_server.enabled {
+        let mut ra_config = config.get().unwrap().ra_server;
+        if dhcp6_server_config.enabled {
+            ra_config.other = true;
+        }
+        let ra_server = Arc::new(ftthd::ra::RaServer::new(socket.clone(), if_manager.clone(), &downstream_if_ids, ra_config));
+        let ra_runner = ra_server.clone();
+        tokio::spawn(async move {
+            if let Err(e) = ra_runner.run().await {
+                log::error!("Router Advertisement server stopped: {:?}", e);
+            }
+        });
+        Some(ra_server)
+    } else {
+        None
+    };
+
+    let mld_querier = if config.get().unwrap().mld_querier.enabled {
+        let mld_querier = Arc::new(ftthd::querier::MldQuerier::new(socket.clone(), if_manager.clone(), &downstream_if_ids, config.get().unwrap().mld_querier));
+        let querier_runner = mld_querier.clone();
+        tokio::spawn(async move {
+            if let Err(e) = querier_runner.run().await {
+                log::error!("MLD querier stopped: {:?}", e);
+            }
+        });
+        Some(mld_querier)
+    } else {
+        None
+    };
+
+    let delegated_prefixes: DelegatedPrefixes = Arc::new(RwLock::new(HashMap::new()));
+    if let (ProxyMode::Dhcpv6Pd, Some(ra_server)) = (&proxy_mode, &ra_server) {
+        // the kernel ignores RAs on forwarding interfaces unless accept_ra is 2
+        let accept_ra_path = format!("/proc/sys/net/ipv6/conf/{}/accept_ra", config.get().unwrap().interfaces.upstream);
+        if let Err(e) = tokio::fs::write(&accept_ra_path, "2").await {
+            log::warn!("Failed to set accept_ra on upstream interface: {:?}", e);
+        }
 
-                let out_if = &config.interfaces.upstream;
-                let out_if_index = if_manager.get_index_by_name(out_if).unwrap();
+        tokio::spawn(run_pd_client(config.clone(), if_manager.clone(), delegated_prefixes.clone(), learned_server_options.clone(), ra_server.clone()));
+    }
 
-                let source = if_manager.get_link_local_addr(out_if_index);
+    let dhcp6_relay_config = config.get().unwrap().dhcp6_relay;
+    if dhcp6_relay_config.enabled {
+        tokio::spawn(run_dhcp6_relay(upstream_if_id, downstream_if_ids.clone(), dhcp6_relay_config.server_address));
+    }
 
-                let source = if let Some(source) = source {
-                    source
-                } else {
-                    log::warn!("Failed to get link-local address for upstream interface: {}", out_if);
-                    continue;
-                };
+    if dhcp6_server_config.enabled && dhcp6_relay_config.enabled {
+        log::warn!("DHCPv6 relay is enabled, not starting the stateless DHCPv6 server");
+    } else if dhcp6_server_config.enabled {
+        for if_id in downstream_if_ids.iter() {
+            tokio::spawn(run_dhcp6_server(*if_id, server_options.clone()));
+        }
 
-                rs.options = rs.options.iter().filter(|opt| {
-                    if opt.option_type == 1 {
-                        false
-                    } else {
-                        true
-                    }
-                }).cloned().collect();
+        if proxy_mode == ProxyMode::NdpProxy {
+            if let Some(server_options) = learned_server_options {
+                tokio::spawn(run_information_request(config.clone(), if_manager.clone(), server_options));
+            }
+        }
+    }
 
-                if let Ok(Some(link_layer_address)) = rtnl_link.get_link_layer_address(out_if_index).await {
-                    rs.options.push(ftthd::icmp6::ndp::NdpOption {
-                        option_type: 1,
-                        option_data: link_layer_address,
-                    });
-                }
+    let mut config_changes = config.subscribe();
+    let mut applied_config = config.get().unwrap();
 
-                let info = ftthd::icmp6::packet::PacketInfo {
-                    if_index: out_if_index,
-                    addr: source,
-                };
-                writer.set_destination(dst);
-                writer.set_packet_info(Some(info));
-                writer.set_hop_limit(Some(255));
-                writer.set_hop_by_hop(None);
-                if let Err(e) = writer.set_packet(ftthd::icmp6::Icmp6Packet::RouterSolicitation(rs.clone())) {
-                    log::error!("Failed to set Router Solicitation: {:?}, rs: {:?}", e, &rs);
+    let mut parser = ftthd::icmp6::Icmp6Parser::new();
+    let mut writer = ftthd::icmp6::Icmp6Writer::new();
+    let mut stats_signal = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::user_defined1()).unwrap();
+    loop {
+        let mld_timer = subscription_manager.next_timer().unwrap_or_else(|| tokio::time::Instant::now() + std::time::Duration::from_secs(60));
+        tokio::select! {
+            res = socket.recv_parser(&mut parser) => {
+                if let Some(msg) = res.unwrap() {
+                    subscription_manager.receive_upcall(&msg);
                     continue;
                 }
-
-                if let Err(e) = socket.send_writer(&writer).await {
-                    log::error!("Failed to send Router Solicitation: {:?}", e);
-                }
             }
 
-            ftthd::icmp6::Icmp6Packet::RouterAdvertisement(mut ra) => {
-                let info = raw_packet.info.unwrap();
-                let in_if = info.if_index;
-                let dst = info.addr;
-                let if_name = if_manager.get(in_if).unwrap().if_name;
+            _ = tokio::time::sleep_until(mld_timer) => {
+                subscription_manager.handle_timers();
+                let upstream = config.get().unwrap().interfaces.upstream;
+                send_mld_reports(&socket, &mut writer, &if_manager, &upstream, &mut subscription_manager).await;
+                continue;
+            }
 
-                let config = config.get().unwrap();
+            _ = stats_signal.recv() => {
+                log_forwarding_stats(&if_manager, &subscription_manager).await;
+                continue;
+            }
 
-                if config.interfaces.upstream != if_name {
-                    log::debug!("Received Router Advertisement from non-upstream interface: {}", if_name);
+            _ = config_changes.recv() => {
+                let new_config = config.get().unwrap();
+                if new_config == applied_config {
                     continue;
                 }
 
-                let out_ifs = config.interfaces.downstreams.iter().map(|name| {
-                    if_manager.get_index_by_name(name).unwrap()
-                }).collect::<Vec<_>>();
-
-                ra.options = ra.options.iter().filter(|opt| {
-                    if opt.option_type == 1 {
-                        false
-                    } else {
-                        true
-                    }
-                }).cloned().collect::<Vec<_>>();
-
-                for out_if_index in out_ifs {
-                    let source = if_manager.get_link_local_addr(out_if_index);
+                if new_config.global.proxy_mode != applied_config.global.proxy_mode {
+                    log::warn!("Changing proxy_mode requires a restart");
+                }
+                if new_config.dhcp6 != applied_config.dhcp6 || new_config.dhcp6_server != applied_config.dhcp6_server || new_config.dhcp6_relay != applied_config.dhcp6_relay {
+                    log::warn!("Changes to DHCPv6 settings require a restart");
+                }
 
-                    let source = if let Some(source) = source {
-                        source
+                if new_config.interfaces != applied_config.interfaces {
+                    let applied = apply_interface_config(&applied_config.interfaces, &new_config.interfaces, &if_manager, &rtnl, proxy_mode.clone(), &upstream_global_addrs, &mut subscription_manager, &mut ndp_multicast_manager).await;
+                    if let Some((downstreams, global_addrs)) = applied {
+                        downstream_if_ids = downstreams;
+                        upstream_global_addrs = global_addrs;
+                        log::info!("Applied interface configuration: {:?}", new_config.interfaces);
                     } else {
-                        log::warn!("Failed to get link-local address for downstream interface: {}", if_manager.get(out_if_index).unwrap().if_name);
-                        continue;
-                    };
-
-                    let info = ftthd::icmp6::packet::PacketInfo {
-                        if_index: out_if_index,
-                        addr: source,
-                    };
-                    writer.set_destination(dst);
-                    writer.set_packet_info(Some(info));
-                    writer.set_hop_limit(Some(255));
-                    writer.set_hop_by_hop(None);
-                    if let Err(e) = writer.set_packet(ftthd::icmp6::Icmp6Packet::RouterAdvertisement(ra.clone())) {
-                        log::error!("Failed to set Router Advertisement: {:?}, ra: {:?}", e, &ra);
+                        log::error!("Keeping previous interface configuration");
                         continue;
                     }
+                }
 
-                    if let Err(e) = socket.send_writer(&writer).await {
-                        log::error!("Failed to send Router Advertisement: {:?}, writer: {:?}", e, &writer);
+                if let Some(ra_server) = &ra_server {
+                    let mut ra_config = new_config.ra_server.clone();
+                    if dhcp6_server_config.enabled {
+                        ra_config.other = true;
                     }
+                    ra_server.set_config(ra_config);
+                    ra_server.set_interfaces(&downstream_if_ids);
+                }
+
+                if new_config.mld_querier.enabled != applied_config.mld_querier.enabled {
+                    log::warn!("Enabling or disabling the MLD querier requires a restart");
+                }
+                if let Some(mld_querier) = &mld_querier {
+                    mld_querier.set_config(new_config.mld_querier.clone());
+                    mld_querier.set_interfaces(&downstream_if_ids);
                 }
+                subscription_manager.set_config(new_config.mld_querier.clone());
+                if new_config.mld_acl != applied_config.mld_acl || new_config.interfaces != applied_config.interfaces {
+                    subscription_manager.set_acls(&new_config.mld_acl);
+                }
+
+                applied_config = new_config;
+                continue;
             }
+        }
+
+        // NS and NA are handled on borrowed views to avoid allocating per packet
+        let raw_packet = parser.packet();
+        match parser.parse_ref() {
+            Ok(Icmp6PacketRef::NeighborSolicitation(ns)) => {
+                if proxy_mode != ProxyMode::NdpProxy {
+                    continue;
+                }
 
-            ftthd::icmp6::Icmp6Packet::NeighborSolicitation(mut ns) => {
                 let info = raw_packet.info.unwrap();
                 let in_if = info.if_index;
                 let dst = info.addr;
@@ -270,7 +348,7 @@ async fn start(config: ftthd::config::ConfigManager) {
                     continue;
                 }
 
-                let tgt_addr = ns.target_address;
+                let tgt_addr = ns.target_address();
 
                 if tgt_addr.is_unicast_link_local() {
                     log::debug!("Received Neighbor Solicitation for link-local address: {}", tgt_addr);
@@ -290,23 +368,8 @@ async fn start(config: ftthd::config::ConfigManager) {
                     if_manager.get_index_by_name(name).unwrap()
                 }).collect::<Vec<_>>();
 
-                ns.options = ns.options.iter().filter(|opt| {
-                    if opt.option_type == 1 {
-                        false
-                    } else {
-                        true
-                    }
-                }).cloned().collect::<Vec<_>>();
-
                 for out_if_index in out_ifs {
-                    let mut ns = ns.clone();
-
-                    if let Ok(Some(link_layer_address)) = rtnl_link.get_link_layer_address(out_if_index).await {
-                        ns.options.push(ftthd::icmp6::ndp::NdpOption {
-                            option_type: 1,
-                            option_data: link_layer_address,
-                        });
-                    }
+                    let link_layer_address = rtnl_link.get_link_layer_address(out_if_index).await;
 
                     let source = if_manager.get_link_local_addr(out_if_index);
 
@@ -325,7 +388,14 @@ async fn start(config: ftthd::config::ConfigManager) {
                     writer.set_packet_info(Some(info));
                     writer.set_hop_limit(Some(255));
                     writer.set_hop_by_hop(None);
-                    if let Err(e) = writer.set_packet(ftthd::icmp6::Icmp6Packet::NeighborSolicitation(ns.clone())) {
+                    let result = writer.set_raw_packet(ns.as_bytes()).and_then(|_| {
+                        if let Ok(Some(link_layer_address)) = link_layer_address {
+                            writer.replace_ndp_option(&ftthd::icmp6::ndp::NdpOption::SourceLinkLayer(link_layer_address))
+                        } else {
+                            writer.remove_ndp_options(ftthd::icmp6::ndp::ND_OPT_SOURCE_LINKADDR)
+                        }
+                    });
+                    if let Err(e) = result {
                         log::error!("Failed to set Neighbor Solicitation: {:?}, ns: {:?}", e, &ns);
                         continue;
                     }
@@ -334,9 +404,14 @@ async fn start(config: ftthd::config::ConfigManager) {
                         log::error!("Failed to send Neighbor Solicitation: {:?}, writer: {:?}", e, &writer);
                     }
                 }
+                continue;
             }
 
-            ftthd::icmp6::Icmp6Packet::NeighborAdvertisement(na) => {
+            Ok(Icmp6PacketRef::NeighborAdvertisement(na)) => {
+                if proxy_mode != ProxyMode::NdpProxy {
+                    continue;
+                }
+
                 let info = raw_packet.info.unwrap();
                 let in_if = info.if_index;
                 let if_name = if_manager.get(in_if).unwrap().if_name;
@@ -349,7 +424,7 @@ async fn start(config: ftthd::config::ConfigManager) {
                     continue;
                 }
 
-                let tgt_addr = na.target_address;
+                let tgt_addr = na.target_address();
                 if tgt_addr.is_unicast_link_local() {
                     log::debug!("Received Neighbor Advertisement for link-local address: {}", tgt_addr);
                     continue;
@@ -378,73 +453,180 @@ async fn start(config: ftthd::config::ConfigManager) {
                         log::error!("Failed to add proxy neighbor: {:?}", e);
                     }
                 }
+                continue;
             }
 
-            ftthd::icmp6::Icmp6Packet::Redirect(r) => {
-                log::info!("Redirect: {:?}", r);
-            }
+            _ => {}
+        }
 
-            ftthd::icmp6::Icmp6Packet::MulticastListenerQuery(mlq) => {
-                let in_if = raw_packet.info.unwrap().if_index;
+        let packet = parser.parse();
+        let packet = if let Ok(packet) = packet {
+            packet
+        } else {
+            log::error!("Failed to parse packet: {:?}", packet);
+            continue;
+        };
+        let raw_packet = parser.packet();
+        match packet {
+            ftthd::icmp6::Icmp6Packet::RouterSolicitation(mut rs) => {
+                let info = raw_packet.info.unwrap();
+                let in_if = info.if_index;
+                let dst = info.addr;
                 let if_name = if_manager.get(in_if).unwrap().if_name;
 
                 let config = config.get().unwrap();
 
-                if config.interfaces.upstream != if_name {
-                    log::debug!("Received Multicast Listener Query from non-configured interface: {}", if_name);
+                if !config.interfaces.downstreams.contains(&if_name) {
+                    log::debug!("Received Router Solicitation from non-downstream interface: {}", if_name);
                     continue;
                 }
 
-                let group_addr = mlq.group_address;
-
-                if group_addr < "ff03::".parse::<Ipv6Addr>().unwrap() {
-                    log::debug!("Received Multicast Listener Query for link-local or node-local group: {}", group_addr);
+                if let Some(ra_server) = &ra_server {
+                    ra_server.solicit(in_if);
                     continue;
                 }
 
-                subscription_manager.remove_old_subscriptions(300);
-                let groups = subscription_manager.get_groups();
-                if !groups.contains(&group_addr) {
-                    log::debug!("Received Multicast Listener Query for non-subscribed group: {}", group_addr);
-                    continue;
-                }
+                let out_if = &config.interfaces.upstream;
+                let out_if_index = if_manager.get_index_by_name(out_if).unwrap();
+
+                let source = if_manager.get_link_local_addr(out_if_index);
 
-                let source_addresses = subscription_manager.get_source_addresses(group_addr).iter().map(|addr| *addr).collect::<Vec<_>>();
-                let record_type = if !source_addresses.is_empty() {
-                    1 // MODE_IS_INCLUDE
+                let source = if let Some(source) = source {
+                    source
                 } else {
-                    2 // MODE_IS_EXCLUDE
+                    log::warn!("Failed to get link-local address for upstream interface: {}", out_if);
+                    continue;
                 };
 
-                let report_record = ftthd::icmp6::mld::MulticastReportRecord {
-                    multicast_address: group_addr,
-                    record_type,
-                    source_addresses,
-                };
+                rs.options = rs.options.iter().filter(|opt| {
+                    !matches!(opt, ftthd::icmp6::ndp::NdpOption::SourceLinkLayer(_))
+                }).cloned().collect();
+
+                if let Ok(Some(link_layer_address)) = rtnl_link.get_link_layer_address(out_if_index).await {
+                    rs.options.push(ftthd::icmp6::ndp::NdpOption::SourceLinkLayer(link_layer_address));
+                }
 
-                let report = ftthd::icmp6::mld::V2MulticastListenerReport {
-                    records: vec![report_record],
+                let info = ftthd::icmp6::packet::PacketInfo {
+                    if_index: out_if_index,
+                    addr: source,
                 };
+                writer.set_destination(dst);
+                writer.set_packet_info(Some(info));
+                writer.set_hop_limit(Some(255));
+                writer.set_hop_by_hop(None);
+                if let Err(e) = writer.set_packet(ftthd::icmp6::Icmp6Packet::RouterSolicitation(rs.clone())) {
+                    log::error!("Failed to set Router Solicitation: {:?}, rs: {:?}", e, &rs);
+                    continue;
+                }
 
-                let src = if_manager.get_link_local_addr(in_if).unwrap();
+                if let Err(e) = socket.send_writer(&writer).await {
+                    log::error!("Failed to send Router Solicitation: {:?}", e);
+                }
+            }
 
-                writer.set_destination("ff02::16".parse().unwrap());
-                writer.set_hop_limit(Some(1));
-                writer.set_packet_info(Some(ftthd::icmp6::packet::PacketInfo {
-                    if_index: in_if,
-                    addr: src,
-                }));
+            ftthd::icmp6::Icmp6Packet::RouterAdvertisement(mut ra) => {
+                let info = raw_packet.info.unwrap();
+                let in_if = info.if_index;
+                let dst = info.addr;
+                let if_name = if_manager.get(in_if).unwrap().if_name;
 
-                if let Err(e) = writer.set_packet(ftthd::icmp6::Icmp6Packet::V2MulticastListenerReport(report)) {
-                    log::error!("Failed to set Multicast Listener Report: {:?}", e);
+                let config = config.get().unwrap();
+
+                if config.interfaces.upstream != if_name {
+                    log::debug!("Received Router Advertisement from non-upstream interface: {}", if_name);
                     continue;
                 }
 
-                if let Err(e) = socket.send_writer(&writer).await {
-                    log::error!("Failed to send Multicast Listener Report: {:?}", e);
+                if proxy_mode != ProxyMode::NdpProxy || ra_server.is_some() {
+                    continue;
+                }
+
+                let out_ifs = config.interfaces.downstreams.iter().map(|name| {
+                    if_manager.get_index_by_name(name).unwrap()
+                }).collect::<Vec<_>>();
+
+                ra.options = ra.options.iter().filter(|opt| {
+                    !matches!(opt, ftthd::icmp6::ndp::NdpOption::SourceLinkLayer(_))
+                }).cloned().collect::<Vec<_>>();
+
+                if dhcp6_server_config.enabled {
+                    ra.other_configuration = true;
+                }
+
+                for out_if_index in out_ifs {
+                    let source = if_manager.get_link_local_addr(out_if_index);
+
+                    let source = if let Some(source) = source {
+                        source
+                    } else {
+                        log::warn!("Failed to get link-local address for downstream interface: {}", if_manager.get(out_if_index).unwrap().if_name);
+                        continue;
+                    };
+
+                    let mut ra = ra.clone();
+                    let out_if_name = if_manager.get(out_if_index).unwrap().if_name;
+                    if let Some(rule) = config.ra_rewrite.get(&out_if_name) {
+                        ftthd::ra::rewrite_router_advertisement(&mut ra, rule);
+                    }
+
+                    let info = ftthd::icmp6::packet::PacketInfo {
+                        if_index: out_if_index,
+                        addr: source,
+                    };
+                    writer.set_destination(dst);
+                    writer.set_packet_info(Some(info));
+                    writer.set_hop_limit(Some(255));
+                    writer.set_hop_by_hop(None);
+                    if let Err(e) = writer.set_packet(ftthd::icmp6::Icmp6Packet::RouterAdvertisement(ra.clone())) {
+                        log::error!("Failed to set Router Advertisement: {:?}, ra: {:?}", e, &ra);
+                        continue;
+                    }
+
+                    if let Err(e) = socket.send_writer(&writer).await {
+                        log::error!("Failed to send Router Advertisement: {:?}, writer: {:?}", e, &writer);
+                    }
+                }
+            }
+
+            ftthd::icmp6::Icmp6Packet::Redirect(r) => {
+                log::info!("Redirect: {:?}", r);
+            }
+
+            ftthd::icmp6::Icmp6Packet::MulticastListenerQuery(mlq) => {
+                let in_if = raw_packet.info.unwrap().if_index;
+                let if_name = if_manager.get(in_if).unwrap().if_name;
+
+                let config = config.get().unwrap();
+
+                if config.interfaces.upstream != if_name {
+                    if let (Some(mld_querier), true) = (&mld_querier, config.interfaces.downstreams.contains(&if_name)) {
+                        mld_querier.receive_query(in_if, raw_packet.target_addr, &mlq);
+
+                        // non-queriers follow the querier's specific queries (RFC 3810 section 7.6.3)
+                        if !mld_querier.is_querier(in_if) && !mlq.supress_router_processing && !mlq.group_address.is_unspecified() {
+                            subscription_manager.lower_timers(in_if, mlq.group_address, &mlq.source_addresses);
+                        }
+                        continue;
+                    }
+                    log::debug!("Received Multicast Listener Query from non-configured interface: {}", if_name);
+                    continue;
                 }
 
-                log::info!("Sent Multicast Listener Report for group: {}", group_addr);
+                let is_v1 = matches!(parser.parse_ref(), Ok(Icmp6PacketRef::MulticastListenerQuery(query)) if !query.is_v2());
+                let group_addr = mlq.group_address;
+
+                if !group_addr.is_unspecified() && group_addr < "ff03::".parse::<Ipv6Addr>().unwrap() {
+                    log::debug!("Received Multicast Listener Query for link-local or node-local group: {}", group_addr);
+                    continue;
+                }
+
+                // answered from the timer arm once the random response delay has passed
+                subscription_manager.receive_query(&mlq, is_v1);
+
+                if mld_querier.is_some() {
+                    // downstream listeners are queried by our own querier
+                    continue;
+                }
 
                 let mut mlq = mlq.clone();
                 mlq.source_addresses.clear();
@@ -478,11 +660,53 @@ async fn start(config: ftthd::config::ConfigManager) {
             }
 
             ftthd::icmp6::Icmp6Packet::V1MulticastListenerReport(mlr) => {
-                log::info!("Multicast Listener Report (v1): {:?}", mlr);
+                let in_if = raw_packet.info.unwrap().if_index;
+                let if_name = if_manager.get(in_if).unwrap().if_name;
+                let group = mlr.group_address;
+
+                if ftthd::group::is_solicited_node_address(&group) {
+                    log::info!("Received Multicast Listener Report (v1) for solicited node address: {}", group);
+
+                    ndp_multicast_manager.add_subscription(group, in_if);
+                    ndp_multicast_manager.remove_old_subscriptions(3600);
+                    continue;
+                }
+
+                let config = config.get().unwrap();
+                if !config.interfaces.downstreams.contains(&if_name) {
+                    log::debug!("Received Multicast Listener Report (v1) from non-downstream interface: {}", if_name);
+                    continue;
+                }
+
+                if group < "ff03::".parse::<Ipv6Addr>().unwrap() {
+                    log::debug!("Received Multicast Listener Report (v1) for link-local or node-local group: {}", group);
+                    continue;
+                }
+
+                let queries = subscription_manager.process_v1_report(in_if, group);
+                send_listener_queries(&mld_querier, &mut subscription_manager, in_if, queries);
+                send_mld_reports(&socket, &mut writer, &if_manager, &config.interfaces.upstream, &mut subscription_manager).await;
             }
 
             ftthd::icmp6::Icmp6Packet::V1MulticastListenerDone(mld) => {
-                log::info!("Multicast Listener Done (v1): {:?}", mld);
+                let in_if = raw_packet.info.unwrap().if_index;
+                let if_name = if_manager.get(in_if).unwrap().if_name;
+                let group = mld.group_address;
+
+                let config = config.get().unwrap();
+                if !config.interfaces.downstreams.contains(&if_name) {
+                    log::debug!("Received Multicast Listener Done from non-downstream interface: {}", if_name);
+                    continue;
+                }
+
+                if group < "ff03::".parse::<Ipv6Addr>().unwrap() {
+                    log::debug!("Received Multicast Listener Done for link-local or node-local group: {}", group);
+                    continue;
+                }
+
+                let queries = subscription_manager.process_v1_done(in_if, group);
+                send_listener_queries(&mld_querier, &mut subscription_manager, in_if, queries);
+                send_mld_reports(&socket, &mut writer, &if_manager, &config.interfaces.upstream, &mut subscription_manager).await;
             }
 
             ftthd::icmp6::Icmp6Packet::V2MulticastListenerReport(mlr) => {
@@ -500,9 +724,6 @@ async fn start(config: ftthd::config::ConfigManager) {
                     is_from_downstream = true;
                 }
 
-                subscription_manager.remove_old_subscriptions(300);
-
-                let mut records = Vec::new();
                 for record in mlr.records {
                     let group = record.multicast_address;
 
@@ -523,60 +744,558 @@ async fn start(config: ftthd::config::ConfigManager) {
                         continue;
                     }
 
-                    let source_addresses: HashSet<_> = record.source_addresses.iter().map(|addr| *addr).collect();
-                    subscription_manager.add_subscription(in_if, group, source_addresses);
+                    let queries = subscription_manager.process_record(in_if, &record);
+                    send_listener_queries(&mld_querier, &mut subscription_manager, in_if, queries);
+                }
 
-                    let source_addresses = subscription_manager.get_source_addresses(group).iter().map(|addr| *addr).collect::<Vec<_>>();
-                    let record_type = if !source_addresses.is_empty() {
-                        1 // MODE_IS_INCLUDE
-                    } else {
-                        2 // MODE_IS_EXCLUDE
-                    };
+                send_mld_reports(&socket, &mut writer, &if_manager, &config.interfaces.upstream, &mut subscription_manager).await;
+            }
 
-                    let report_record = ftthd::icmp6::mld::MulticastReportRecord {
-                        multicast_address: group,
-                        record_type,
-                        source_addresses,
-                    };
-                    records.push(report_record);
+            ftthd::icmp6::Icmp6Packet::PacketTooBig(ptb) => {
+                let config = config.get().unwrap();
+                let in_if = raw_packet.info.map(|info| info.if_index);
+                if in_if.is_some() && in_if == if_manager.get_index_by_name(&config.interfaces.upstream) {
+                    let destination = ptb.invoking_packet.header.as_ref().map(|header| header.destination);
+                    log::warn!("Packet Too Big from {} on upstream: mtu={}, destination={:?}", raw_packet.target_addr, ptb.mtu, destination);
+                } else {
+                    log::debug!("Packet Too Big from {}: mtu={}", raw_packet.target_addr, ptb.mtu);
                 }
+            }
+
+            _ => {
+                log::info!("Unknown packet: {:?}", packet);
+            }
+        }
+    }
+}
 
-                let out_if_name = &config.interfaces.upstream;
-                let out_if = if_manager.get_index_by_name(out_if_name).unwrap();
-                let src = if_manager.get_link_local_addr(out_if).unwrap();
+/// Hands the queries a report calls for to the querier if we are the querier on that interface
+fn send_listener_queries(mld_querier: &Option<Arc<ftthd::querier::MldQuerier>>, subscription_manager: &mut MldSubscriptionManager, in_if: InterfaceId, queries: Vec<ftthd::group::ListenerQuery>) {
+    let mld_querier = if let Some(mld_querier) = mld_querier {
+        mld_querier
+    } else {
+        return;
+    };
+    if !mld_querier.is_querier(in_if) {
+        return;
+    }
 
-                writer.set_destination("ff02::16".parse().unwrap());
-                writer.set_hop_limit(Some(1));
-                writer.set_packet_info(Some(ftthd::icmp6::packet::PacketInfo {
-                    if_index: out_if,
-                    addr: src,
-                }));
+    for query in queries {
+        subscription_manager.lower_timers(in_if, query.group_addr, &query.source_addrs);
+        if query.source_addrs.is_empty() {
+            mld_querier.query_group(in_if, query.group_addr);
+        } else {
+            mld_querier.query_sources(in_if, query.group_addr, query.source_addrs);
+        }
+    }
+}
 
-                if records.is_empty() {
-                    log::debug!("No groups to report");
-                    continue;
+/// Sends the state-change records and query answers that are due to the upstream
+async fn send_mld_reports(socket: &ftthd::icmp6::AsyncIcmp6Socket, writer: &mut ftthd::icmp6::Icmp6Writer, if_manager: &InterfaceStateManager, upstream: &str, subscription_manager: &mut MldSubscriptionManager) {
+    let records = subscription_manager.take_due_reports();
+    if records.is_empty() {
+        return;
+    }
+
+    let out_if = if let Some(if_id) = if_manager.get_index_by_name(upstream) {
+        if_id
+    } else {
+        log::error!("Unknown upstream interface: {}", upstream);
+        return;
+    };
+    let src = if let Some(addr) = if_manager.get_link_local_addr(out_if) {
+        addr
+    } else {
+        log::warn!("No link-local address on upstream interface: {}", upstream);
+        return;
+    };
+
+    writer.set_hop_limit(Some(1));
+    writer.set_packet_info(Some(ftthd::icmp6::packet::PacketInfo {
+        if_index: out_if,
+        addr: src,
+    }));
+
+    if subscription_manager.is_older_querier_present() {
+        // MLDv1 can only express joins and leaves (RFC 3810 section 8.2.2)
+        let groups = records.iter().map(|r| r.multicast_address).collect::<HashSet<_>>();
+        for group in groups {
+            let (destination, packet) = if subscription_manager.get_membership(group).is_some() {
+                (group, ftthd::icmp6::Icmp6Packet::V1MulticastListenerReport(ftthd::icmp6::mld::V1MulticastListenerReport {
+                    group_address: group,
+                }))
+            } else {
+                ("ff02::2".parse().unwrap(), ftthd::icmp6::Icmp6Packet::V1MulticastListenerDone(ftthd::icmp6::mld::V1MulticastListenerDone {
+                    group_address: group,
+                }))
+            };
+
+            writer.set_destination(destination);
+            if let Err(e) = writer.set_packet(packet) {
+                log::error!("Failed to set MLDv1 message: {:?}", e);
+                continue;
+            }
+            if let Err(e) = socket.send_writer(writer).await {
+                log::error!("Failed to send MLDv1 message: {:?}, writer: {:?}", e, writer);
+                continue;
+            }
+            log::info!("Sent MLDv1 message for group: {}", group);
+        }
+        return;
+    }
+
+    writer.set_destination("ff02::16".parse().unwrap());
+    let max_len = ftthd::icmp6::mld::max_report_len(if_manager.get_mtu(out_if));
+    for report in ftthd::icmp6::mld::V2MulticastListenerReport::split(records, max_len) {
+        let groups = report.records.iter().map(|r| r.multicast_address).collect::<Vec<_>>();
+
+        if let Err(e) = writer.set_packet(ftthd::icmp6::Icmp6Packet::V2MulticastListenerReport(report)) {
+            log::error!("Failed to set Multicast Listener Report: {:?}", e);
+            continue;
+        }
+
+        if let Err(e) = socket.send_writer(writer).await {
+            log::error!("Failed to send Multicast Listener Report: {:?}, writer: {:?}", e, writer);
+            continue;
+        }
+
+        log::info!("Sent Multicast Listener Report for groups: {:?}", groups);
+    }
+}
+
+/// Logs the kernel forwarding counters on SIGUSR1 so that stale subscriptions can be spotted
+async fn log_forwarding_stats(if_manager: &InterfaceStateManager, subscription_manager: &MldSubscriptionManager) {
+    let if_name = |if_index: InterfaceId| if_manager.get(if_index).map(|state| state.if_name).unwrap_or_else(|| format!("{:?}", if_index));
+
+    for (if_index, counters) in subscription_manager.get_interface_counters() {
+        log::info!("MIF {}: in {} packets / {} bytes, out {} packets / {} bytes", if_name(if_index), counters.in_packets, counters.in_bytes, counters.out_packets, counters.out_bytes);
+    }
+    for (group_addr, counters) in subscription_manager.get_forwarding_counters() {
+        for (if_index, counters) in counters {
+            log::info!("Group {} on {}: {} packets / {} bytes", group_addr, if_name(if_index), counters.packets, counters.bytes);
+        }
+    }
+
+    match ftthd::icmp6::mroute::read_mr_cache().await {
+        Ok(entries) => {
+            let unresolved = entries.iter().filter(|entry| entry.parent.is_none()).count();
+            log::info!("Kernel MFC: {} entries, {} unresolved", entries.len(), unresolved);
+        }
+        Err(e) => {
+            log::warn!("Failed to read {}: {:?}", ftthd::icmp6::mroute::MR_CACHE_PATH, e);
+        }
+    }
+}
+
+/// Reconciles interface-dependent kernel state after the interface configuration changed.
+/// Returns the new downstream interfaces and upstream global addresses, or `None` if the new configuration can't be applied.
+#[allow(clippy::too_many_arguments)]
+async fn apply_interface_config(old: &ftthd::config::InterfaceConfig, new: &ftthd::config::InterfaceConfig, if_manager: &InterfaceStateManager, rtnl: &ftthd::rtnl::RtnetlinkConnection, proxy_mode: ProxyMode, old_global_addrs: &[Ipv6Addr], subscription_manager: &mut MldSubscriptionManager, ndp_multicast_manager: &mut NdpMulticastManager) -> Option<(Vec<InterfaceId>, Vec<Ipv6Addr>)> {
+    let upstream_if_id = if let Some(if_id) = if_manager.get_index_by_name(&new.upstream) {
+        if_id
+    } else {
+        log::error!("Unknown upstream interface: {}", new.upstream);
+        return None;
+    };
+
+    let mut downstream_if_ids = Vec::new();
+    for name in &new.downstreams {
+        if let Some(if_id) = if_manager.get_index_by_name(name) {
+            downstream_if_ids.push(if_id);
+        } else {
+            log::error!("Unknown downstream interface: {}", name);
+            return None;
+        }
+    }
+
+    if downstream_if_ids.contains(&upstream_if_id) {
+        log::error!("Upstream interface is also a downstream interface");
+        return None;
+    }
+
+    let old_upstream_if_id = if_manager.get_index_by_name(&old.upstream);
+    let old_downstream_if_ids = old.downstreams.iter().filter_map(|name| if_manager.get_index_by_name(name)).collect::<Vec<_>>();
+    let upstream_changed = old_upstream_if_id != Some(upstream_if_id);
+
+    let mut rtnl_link = rtnl.link();
+    let old_if_ids = old.interfaces().iter().filter_map(|name| if_manager.get_index_by_name(name)).collect::<HashSet<_>>();
+    let new_if_ids = new.interfaces().iter().filter_map(|name| if_manager.get_index_by_name(name)).collect::<HashSet<_>>();
+    for if_id in old_if_ids.difference(&new_if_ids) {
+        let _ = rtnl_link.set_all_multicast_mode(*if_id, false).await;
+    }
+    for if_id in new_if_ids.difference(&old_if_ids) {
+        let _ = rtnl_link.set_all_multicast_mode(*if_id, true).await;
+    }
+
+    let global_addrs = if upstream_changed {
+        match rtnl.address().get_v6(upstream_if_id, ftthd::rtnl::addr::V6AddressRequestScope::Global).await {
+            Ok(addrs) => addrs,
+            Err(e) => {
+                log::error!("Failed to get upstream addresses: {:?}", e);
+                return None;
+            }
+        }
+    } else {
+        old_global_addrs.to_vec()
+    };
+
+    if proxy_mode == ProxyMode::NdpProxy {
+        let rtnl_neighbor = rtnl.neighbor();
+        for if_id in old_downstream_if_ids.iter() {
+            if !upstream_changed && downstream_if_ids.contains(if_id) {
+                continue;
+            }
+            for addr in old_global_addrs {
+                let _ = rtnl_neighbor.proxy_delete(*if_id, std::net::IpAddr::V6(*addr)).await;
+            }
+        }
+
+        for if_id in downstream_if_ids.iter() {
+            if !upstream_changed && old_downstream_if_ids.contains(if_id) {
+                continue;
+            }
+            for addr in &global_addrs {
+                let _ = rtnl_neighbor.proxy_delete(*if_id, std::net::IpAddr::V6(*addr)).await;
+
+                if let Err(e) = rtnl_neighbor.proxy_add(*if_id, std::net::IpAddr::V6(*addr)).await {
+                    log::error!("Failed to add proxy neighbor: {:?}", e);
                 }
+            }
+        }
+    }
 
-                let report = ftthd::icmp6::mld::V2MulticastListenerReport {
-                    records: records.clone(),
-                };
+    if let Err(e) = subscription_manager.set_interfaces(new.clone()) {
+        log::error!("Failed to update multicast interfaces: {:?}", e);
+    }
+    ndp_multicast_manager.set_interfaces(new.clone());
 
-                if let Err(e) = writer.set_packet(ftthd::icmp6::Icmp6Packet::V2MulticastListenerReport(report)) {
-                    log::error!("Failed to set Multicast Listener Report: {:?}", e);
-                    continue;
+    Some((downstream_if_ids, global_addrs))
+}
+
+/// /64 carved out of the delegated prefix for a downstream interface
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DownstreamPrefix {
+    prefix: Ipv6Addr,
+    preferred_lifetime: u32,
+    valid_lifetime: u32,
+}
+
+type DelegatedPrefixes = Arc<RwLock<HashMap<InterfaceId, DownstreamPrefix>>>;
+
+/// How long to wait for the Router Advertisement deprecating a lost prefix before removing it anyway
+const WITHDRAW_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Loads the client DUID from the state directory, generating and storing one on first use
+async fn load_client_duid(dhcp6_config: &ftthd::config::Dhcp6Config, rtnl_link: &mut ftthd::rtnl::link::LinkManager, upstream_if_id: InterfaceId) -> ftthd::dhcp6::Duid {
+    let store = ftthd::dhcp6::store::LeaseStore::new(&dhcp6_config.state_dir);
+
+    let stored_duid = match store.load_duid() {
+        Ok(duid) => duid,
+        Err(e) => {
+            log::error!("Failed to load stored DUID: {:?}", e);
+            None
+        }
+    };
+
+    let duid = if let Some(duid) = stored_duid {
+        duid
+    } else {
+        let duid = if dhcp6_config.duid_type == ftthd::config::DuidType::Uuid {
+            ftthd::dhcp6::Duid::new_random_uuid()
+        } else {
+            let link_layer_address = loop {
+                match rtnl_link.get_link_layer_address(upstream_if_id).await {
+                    Ok(Some(addr)) => break addr,
+                    Ok(None) => log::warn!("Upstream interface has no link-layer address: {:?}", upstream_if_id),
+                    Err(e) => log::error!("Failed to get link-layer address: {:?}", e),
                 }
+                tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+            };
+
+            if dhcp6_config.duid_type == ftthd::config::DuidType::Llt {
+                let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+                ftthd::dhcp6::Duid::new_llt(&link_layer_address, now)
+            } else {
+                ftthd::dhcp6::Duid::new_ll(&link_layer_address)
+            }
+        };
 
-                if let Err(e) = socket.send_writer(&writer).await {
-                    log::error!("Failed to send Multicast Listener Report: {:?}, writer: {:?}", e, &writer);
+        if let Err(e) = store.save_duid(&duid) {
+            log::error!("Failed to store DUID: {:?}", e);
+        }
+        duid
+    };
+    log::info!("Using DHCPv6 {:?}", duid);
+    duid
+}
+
+/// Loads the IAID from the state directory, generating and storing one on first use.
+/// The IAID must not follow the upstream interface index, which changes when the interface is re-created.
+fn load_client_iaid(store: &ftthd::dhcp6::store::LeaseStore, stored_lease: Option<&ftthd::dhcp6::client::Dhcp6Lease>) -> u32 {
+    let stored_iaid = match store.load_iaid() {
+        Ok(iaid) => iaid,
+        Err(e) => {
+            log::error!("Failed to load stored IAID: {:?}", e);
+            None
+        }
+    };
+
+    if let Some(iaid) = stored_iaid {
+        return iaid;
+    }
+
+    // a lease stored before the IAID was kept on its own carries the IAID it was bound to
+    let iaid = stored_lease.map(|lease| lease.iaid).unwrap_or_else(rand::random);
+    if let Err(e) = store.save_iaid(iaid) {
+        log::error!("Failed to store IAID: {:?}", e);
+    }
+    iaid
+}
+
+async fn run_pd_client(config: ftthd::config::ConfigManager, if_manager: InterfaceStateManager, delegated_prefixes: DelegatedPrefixes, learned_server_options: Option<Arc<RwLock<ftthd::dhcp6::server::ServerOptions>>>, ra_server: Arc<ftthd::ra::RaServer>) {
+    let rtnl = ftthd::rtnl::RtnetlinkConnection::new().await.unwrap();
+    let mut rtnl_link = rtnl.link();
+    let rtnl_addr = rtnl.address();
+
+    let upstream = config.get().unwrap().interfaces.upstream;
+    let upstream_if_id = if_manager.get_index_by_name(&upstream).unwrap();
+
+    let dhcp6_config = config.get().unwrap().dhcp6;
+    let store = ftthd::dhcp6::store::LeaseStore::new(&dhcp6_config.state_dir);
+    let duid = load_client_duid(&dhcp6_config, &mut rtnl_link, upstream_if_id).await;
+
+    let client_config = ftthd::dhcp6::client::Dhcp6ClientConfig {
+        request_address: dhcp6_config.request_address,
+        request_prefix: true,
+        prefix_length_hint: dhcp6_config.prefix_length_hint,
+    };
+
+    let stored_lease = match store.load_lease() {
+        Ok(lease) => lease,
+        Err(e) => {
+            log::error!("Failed to load stored DHCPv6 lease: {:?}", e);
+            None
+        }
+    };
+    let iaid = load_client_iaid(&store, stored_lease.as_ref());
+
+    let client = loop {
+        match ftthd::dhcp6::client::Dhcp6Client::new(upstream_if_id, duid.clone(), iaid, client_config.clone(), Some(store.clone())) {
+            Ok(client) => break Arc::new(client),
+            Err(e) => {
+                log::error!("Failed to create DHCPv6 client: {:?}", e);
+                tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+            }
+        }
+    };
+
+    let mut events = client.subscribe();
+    let client_runner = client.clone();
+    tokio::spawn(async move {
+        client_runner.run(stored_lease).await;
+    });
+
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+        };
+
+        match event {
+            ftthd::dhcp6::client::Dhcp6Event::Acquired(lease) | ftthd::dhcp6::client::Dhcp6Event::Renewed(lease) => {
+                log::info!("Delegated prefixes: {:?}", lease.prefixes);
+                if let Some(server_options) = &learned_server_options {
+                    *server_options.write() = ftthd::dhcp6::server::ServerOptions {
+                        dns_servers: lease.dns_servers.clone(),
+                        domain_list: lease.domain_list.clone(),
+                    };
+                }
+                for addr in &lease.addresses {
+                    if let Err(e) = rtnl_addr.add_v6(upstream_if_id, addr.addr, 128).await {
+                        log::error!("Failed to add address to upstream interface: {:?}", e);
+                    }
+                }
+                apply_pd_lease(&config, &if_manager, &rtnl_addr, &delegated_prefixes, &lease).await;
+                announce_pd_prefixes(&ra_server, &delegated_prefixes);
+            }
+
+            ftthd::dhcp6::client::Dhcp6Event::Lost(lease) => {
+                log::warn!("Delegated prefixes expired: {:?}", lease.prefixes);
+                for addr in &lease.addresses {
+                    let _ = rtnl_addr.delete_v6(upstream_if_id, addr.addr, 128).await;
+                }
+                withdraw_pd_lease(&delegated_prefixes);
+                announce_pd_prefixes(&ra_server, &delegated_prefixes);
+
+                // hosts only stop using the prefixes once they have seen them deprecated
+                let if_ids = delegated_prefixes.read().keys().cloned().collect::<Vec<_>>();
+                for if_id in if_ids {
+                    if tokio::time::timeout(WITHDRAW_TIMEOUT, ra_server.wait_advertised(if_id)).await.is_err() {
+                        log::warn!("Could not advertise deprecated prefixes on {:?}", if_id);
+                    }
                 }
 
-                log::info!("Sent Multicast Listener Report for groups: {:?}", records.iter().map(|r| r.multicast_address).collect::<Vec<_>>());
+                let prefixes = std::mem::take(&mut *delegated_prefixes.write());
+                for (if_id, prefix) in prefixes {
+                    ra_server.set_prefixes(if_id, Vec::new());
+                    let _ = rtnl_addr.delete_v6(if_id, router_address(prefix.prefix), 64).await;
+                }
             }
+        }
+    }
+}
 
-            _ => {
-                log::info!("Unknown packet: {:?}", packet);
+fn announce_pd_prefixes(ra_server: &ftthd::ra::RaServer, delegated_prefixes: &DelegatedPrefixes) {
+    let prefixes = delegated_prefixes.read().clone();
+    for (if_id, prefix) in prefixes {
+        ra_server.set_prefixes(if_id, vec![ftthd::config::RaPrefixConfig {
+            prefix: prefix.prefix,
+            prefix_len: 64,
+            on_link: true,
+            autonomous: true,
+            valid_lifetime: prefix.valid_lifetime,
+            preferred_lifetime: prefix.preferred_lifetime,
+        }]);
+    }
+}
+
+/// Deprecates every announced prefix so hosts stop using it
+fn withdraw_pd_lease(delegated_prefixes: &DelegatedPrefixes) {
+    for prefix in delegated_prefixes.write().values_mut() {
+        prefix.preferred_lifetime = 0;
+        prefix.valid_lifetime = 0;
+    }
+}
+
+async fn apply_pd_lease(config: &ftthd::config::ConfigManager, if_manager: &InterfaceStateManager, rtnl_addr: &ftthd::rtnl::addr::AddressManager, delegated_prefixes: &DelegatedPrefixes, lease: &ftthd::dhcp6::client::Dhcp6Lease) {
+    let prefix = &lease.prefixes[0];
+    let downstreams = config.get().unwrap().interfaces.downstreams;
+
+    for (i, name) in downstreams.iter().enumerate() {
+        let if_id = if let Some(if_id) = if_manager.get_index_by_name(name) {
+            if_id
+        } else {
+            log::warn!("Unknown downstream interface: {}", name);
+            continue;
+        };
+
+        let subnet = if let Some(subnet) = ftthd::dhcp6::client::subnet_prefix(prefix, i as u32) {
+            subnet
+        } else {
+            log::warn!("Delegated prefix {}/{} is too small for downstream interface: {}", prefix.prefix, prefix.prefix_len, name);
+            continue;
+        };
+
+        let downstream_prefix = DownstreamPrefix {
+            prefix: subnet,
+            preferred_lifetime: prefix.preferred_lifetime,
+            valid_lifetime: prefix.valid_lifetime,
+        };
+
+        let prev = delegated_prefixes.write().insert(if_id, downstream_prefix);
+        if let Some(prev) = prev {
+            if prev.prefix != subnet {
+                let _ = rtnl_addr.delete_v6(if_id, router_address(prev.prefix), 64).await;
             }
         }
+
+        if let Err(e) = rtnl_addr.add_v6(if_id, router_address(subnet), 64).await {
+            log::error!("Failed to add address to {}: {:?}", name, e);
+        }
+    }
+}
+
+fn router_address(prefix: Ipv6Addr) -> Ipv6Addr {
+    Ipv6Addr::from(u128::from_be_bytes(prefix.octets()) | 1)
+}
+
+async fn run_dhcp6_server(if_id: InterfaceId, server_options: Arc<RwLock<ftthd::dhcp6::server::ServerOptions>>) {
+    let rtnl = ftthd::rtnl::RtnetlinkConnection::new().await.unwrap();
+    let mut rtnl_link = rtnl.link();
+
+    let server = loop {
+        let duid = match rtnl_link.get_link_layer_address(if_id).await {
+            Ok(Some(addr)) => ftthd::dhcp6::Duid::new_ll(&addr),
+            Ok(None) => {
+                log::warn!("Downstream interface has no link-layer address: {:?}", if_id);
+                ftthd::dhcp6::Duid::new_random_uuid()
+            }
+            Err(e) => {
+                log::error!("Failed to get link-layer address: {:?}", e);
+                tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+                continue;
+            }
+        };
+
+        match ftthd::dhcp6::server::StatelessServer::new(if_id, duid, server_options.clone()) {
+            Ok(server) => break server,
+            Err(e) => {
+                log::error!("Failed to create DHCPv6 server: {:?}", e);
+                tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+            }
+        }
+    };
+
+    log::info!("Stateless DHCPv6 server running on {:?}", server.if_index());
+    server.run().await;
+}
+
+async fn run_dhcp6_relay(upstream_if_id: InterfaceId, downstream_if_ids: Vec<InterfaceId>, server_address: Ipv6Addr) {
+    let rtnl = ftthd::rtnl::RtnetlinkConnection::new().await.unwrap();
+
+    let relay = loop {
+        match ftthd::dhcp6::relay::Dhcp6Relay::new(upstream_if_id, &downstream_if_ids, server_address, &rtnl) {
+            Ok(relay) => break relay,
+            Err(e) => {
+                log::error!("Failed to create DHCPv6 relay: {:?}", e);
+                tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+            }
+        }
+    };
+
+    log::info!("DHCPv6 relay running towards {}", server_address);
+    relay.run().await;
+}
+
+/// Keeps the server options in sync with the upstream DHCPv6 server in NDP proxy mode
+async fn run_information_request(config: ftthd::config::ConfigManager, if_manager: InterfaceStateManager, server_options: Arc<RwLock<ftthd::dhcp6::server::ServerOptions>>) {
+    let rtnl = ftthd::rtnl::RtnetlinkConnection::new().await.unwrap();
+    let mut rtnl_link = rtnl.link();
+
+    let upstream_if_id = if_manager.get_index_by_name(&config.get().unwrap().interfaces.upstream).unwrap();
+    let dhcp6_config = config.get().unwrap().dhcp6;
+    let duid = load_client_duid(&dhcp6_config, &mut rtnl_link, upstream_if_id).await;
+
+    let client_config = ftthd::dhcp6::client::Dhcp6ClientConfig {
+        request_address: false,
+        request_prefix: false,
+        prefix_length_hint: None,
+    };
+
+    let client = loop {
+        match ftthd::dhcp6::client::Dhcp6Client::new(upstream_if_id, duid.clone(), 0, client_config.clone(), None) {
+            Ok(client) => break client,
+            Err(e) => {
+                log::error!("Failed to create DHCPv6 client: {:?}", e);
+                tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+            }
+        }
+    };
+
+    loop {
+        let refresh = match client.information_request().await {
+            Ok((options, refresh)) => {
+                log::info!("Learned DHCPv6 options from upstream: {:?}", options);
+                *server_options.write() = options;
+                refresh
+            }
+            Err(e) => {
+                log::warn!("DHCPv6 Information-Request failed: {:?}", e);
+                600
+            }
+        };
+        tokio::time::sleep(std::time::Duration::from_secs(refresh as u64)).await;
     }
 }
 
@@ -598,4 +1317,7 @@ pub struct Cli {
 pub enum Command {
     /// start the daemon
     Start,
+
+    /// validate the configuration file and exit
+    CheckConfig,
 }
@@ -5,8 +5,32 @@ use ftthd::interface::{self, InterfaceId};
 
 use std::str::FromStr;
 
+#[cfg(feature = "serde")]
+fn print_json(parser: &ftthd::icmp6::Icmp6Parser) -> std::io::Result<()> {
+    let packet = parser.packet();
+    let ifname = packet.info.map(|info| interface::index_to_name(info.if_index)).transpose()?;
+    let mut line = serde_json::json!({
+        "interface": ifname,
+        "source": packet.target_addr,
+        "info": packet.info,
+        "hop_limit": packet.hop_limit.map(|hop_limit| hop_limit.hop_limit),
+    });
+    match parser.parse() {
+        Ok(parsed) => line["packet"] = serde_json::to_value(&parsed).map_err(std::io::Error::other)?,
+        Err(e) => line["error"] = e.to_string().into(),
+    }
+    println!("{}", line);
+    Ok(())
+}
+
 fn main() -> std::io::Result<()> {
     env_logger::init();
+    let json = std::env::args().any(|arg| arg == "--json");
+    if json && cfg!(not(feature = "serde")) {
+        eprintln!("--json requires building with the serde feature");
+        std::process::exit(1);
+    }
+
     let socket = RawIcmp6Socket::new()?;
     socket.set_recv_hoplimit(true)?;
     socket.set_recv_hopopts(true)?;
@@ -17,7 +41,16 @@ fn main() -> std::io::Result<()> {
     socket.set_mrt_flag(true)?;
     let mut parser = ftthd::icmp6::Icmp6Parser::new();
     loop {
-        socket.recv_parser(&mut parser)?;
+        if let Some(msg) = socket.recv_parser(&mut parser)? {
+            println!("{:?}", msg);
+            continue;
+        }
+        #[cfg(feature = "serde")]
+        if json {
+            print_json(&parser)?;
+            continue;
+        }
+
         {
             let packet = parser.packet();
             let info = packet.info.as_ref().unwrap();
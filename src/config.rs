@@ -59,13 +59,350 @@ impl ConfigManager {
 pub struct Config {
     pub global: GlobalConfig,
     pub interfaces: InterfaceConfig,
+
+    #[serde(default)]
+    pub dhcp6: Dhcp6Config,
+
+    #[serde(default)]
+    pub dhcp6_server: Dhcp6ServerConfig,
+
+    #[serde(default)]
+    pub dhcp6_relay: Dhcp6RelayConfig,
+
+    #[serde(default)]
+    pub ra_server: RaServerConfig,
+
+    /// rewrite rules for relayed RAs, keyed by downstream interface name
+    #[serde(default)]
+    pub ra_rewrite: std::collections::HashMap<String, RaRewriteConfig>,
+
+    #[serde(default)]
+    pub mld_querier: MldQuerierConfig,
+
+    /// multicast access control, keyed by downstream interface name
+    #[serde(default)]
+    pub mld_acl: std::collections::HashMap<String, MldAclConfig>,
 }
 
 impl Config {
     pub fn from_file<P: AsRef<Path>>(file: P) -> Result<Self, std::io::Error> {
-        let content = std::fs::read_to_string(file)?;
-        let config: Config = toml::from_str(&content).map_err(|e| std::io::Error::other(e))?;
-        Ok(config)
+        Self::check_file(file).map_err(|diagnostics| {
+            let message = diagnostics.iter().map(|d| d.to_string()).collect::<Vec<_>>().join("\n");
+            std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+        })
+    }
+
+    /// Parses and validates a configuration file, reporting every problem found
+    pub fn check_file<P: AsRef<Path>>(file: P) -> Result<Self, Vec<ConfigDiagnostic>> {
+        let path = file.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            vec![ConfigDiagnostic {
+                path: path.to_path_buf(),
+                line: None,
+                message: e.to_string(),
+            }]
+        })?;
+        Self::check_str(path, &content)
+    }
+
+    /// Parses and validates configuration read from `path`, reporting every problem found
+    pub fn check_str<P: AsRef<Path>>(path: P, content: &str) -> Result<Self, Vec<ConfigDiagnostic>> {
+        let path = path.as_ref();
+        let mut checker = ConfigChecker::new(path, content)?;
+        let config: Config = toml::from_str(content).map_err(|e| {
+            let line = e.span().map(|span| checker.line(span.start));
+            vec![ConfigDiagnostic {
+                path: path.to_path_buf(),
+                line,
+                message: e.message().to_owned(),
+            }]
+        })?;
+
+        checker.check(&config);
+        if checker.diagnostics.is_empty() {
+            Ok(config)
+        } else {
+            Err(checker.diagnostics)
+        }
+    }
+}
+
+/// Problem found in a configuration file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigDiagnostic {
+    pub path: PathBuf,
+
+    /// 1-based line number, if known
+    pub line: Option<usize>,
+
+    pub message: String,
+}
+
+impl std::fmt::Display for ConfigDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "{}:{}: {}", self.path.display(), line, self.message)
+        } else {
+            write!(f, "{}: {}", self.path.display(), self.message)
+        }
+    }
+}
+
+impl std::error::Error for ConfigDiagnostic {}
+
+fn line_of(content: &str, offset: usize) -> usize {
+    content.as_bytes()[..offset.min(content.len())].iter().filter(|b| **b == b'\n').count() + 1
+}
+
+/// Span of an item; implicit tables such as `a` in `[a.b]` have none, so they take the span of their first child
+fn item_span(item: &toml_edit::Item) -> Option<std::ops::Range<usize>> {
+    item.span().or_else(|| {
+        item.as_table_like()?.iter()
+            .filter_map(|(_, child)| item_span(child))
+            .min_by_key(|span| span.start)
+    })
+}
+
+fn collect_unknown_keys(item: &toml_edit::Item, known: &toml::Value, key: &mut Vec<String>, unknown: &mut Vec<Vec<String>>) {
+    if let Some(table) = item.as_table_like() {
+        for (name, child) in table.iter() {
+            key.push(name.to_owned());
+            match known.get(name) {
+                Some(known) => collect_unknown_keys(child, known, key, unknown),
+                None => unknown.push(key.clone()),
+            }
+            key.pop();
+        }
+    } else if let Some(tables) = item.as_array_of_tables() {
+        for (i, table) in tables.iter().enumerate() {
+            if let Some(known) = known.get(i) {
+                key.push(i.to_string());
+                collect_unknown_keys(&toml_edit::Item::Table(table.clone()), known, key, unknown);
+                key.pop();
+            }
+        }
+    } else if let Some(values) = item.as_array() {
+        for (i, value) in values.iter().enumerate() {
+            if let Some(known) = known.get(i) {
+                key.push(i.to_string());
+                collect_unknown_keys(&toml_edit::Item::Value(value.clone()), known, key, unknown);
+                key.pop();
+            }
+        }
+    }
+}
+
+/// Semantic checks run after the file has been deserialized.
+/// Keys are located in the parsed document so diagnostics can point at a line.
+struct ConfigChecker<'a> {
+    path: &'a Path,
+    content: &'a str,
+    document: toml_edit::ImDocument<&'a str>,
+    diagnostics: Vec<ConfigDiagnostic>,
+}
+
+impl<'a> ConfigChecker<'a> {
+    fn new(path: &'a Path, content: &'a str) -> Result<Self, Vec<ConfigDiagnostic>> {
+        let document = toml_edit::ImDocument::parse(content).map_err(|e| {
+            let line = e.span().map(|span| line_of(content, span.start));
+            vec![ConfigDiagnostic {
+                path: path.to_path_buf(),
+                line,
+                message: e.message().to_owned(),
+            }]
+        })?;
+
+        Ok(Self {
+            path,
+            content,
+            document,
+            diagnostics: Vec::new(),
+        })
+    }
+
+    fn line(&self, offset: usize) -> usize {
+        line_of(self.content, offset)
+    }
+
+    /// Looks up a key path; numeric segments index into arrays.
+    fn item(&self, key: &[&str]) -> Option<&toml_edit::Item> {
+        let mut item = self.document.as_item();
+        for segment in key {
+            item = match item.get(*segment) {
+                Some(next) => next,
+                None => item.get(segment.parse::<usize>().ok()?)?,
+            };
+        }
+        Some(item)
+    }
+
+    fn is_set(&self, key: &[&str]) -> bool {
+        self.item(key).is_some()
+    }
+
+    /// Reports a problem at the innermost key of `key` present in the file
+    fn report(&mut self, key: &[&str], message: String) {
+        let line = (0..=key.len()).rev()
+            .find_map(|len| self.item(&key[..len]).and_then(item_span))
+            .map(|span| self.line(span.start));
+        self.diagnostics.push(ConfigDiagnostic {
+            path: self.path.to_path_buf(),
+            line,
+            message,
+        });
+    }
+
+    fn check(&mut self, config: &Config) {
+        self.check_unknown_keys(config);
+        self.check_interfaces(&config.interfaces);
+        self.check_proxy_mode(config);
+        self.check_ra_server(&config.ra_server);
+        self.check_mld_querier(&config.mld_querier);
+
+        for name in config.ra_rewrite.keys() {
+            if !config.interfaces.downstreams.contains(name) {
+                self.report(&["ra_rewrite", name], format!("ra_rewrite.{} is not a downstream interface", name));
+            }
+        }
+
+        for (name, acl) in &config.mld_acl {
+            if !config.interfaces.downstreams.contains(name) {
+                self.report(&["mld_acl", name], format!("mld_acl.{} is not a downstream interface", name));
+            }
+            self.check_mld_acl(name, acl);
+        }
+
+        if config.dhcp6_server.enabled && config.dhcp6_relay.enabled {
+            self.report(&["dhcp6_relay", "enabled"], "dhcp6_server and dhcp6_relay cannot both be enabled".to_owned());
+        }
+    }
+
+    /// Keys no option reads, found by comparing the file with the deserialized configuration serialized back
+    fn check_unknown_keys(&mut self, config: &Config) {
+        let known = if let Ok(known) = toml::Value::try_from(config) {
+            known
+        } else {
+            return;
+        };
+
+        let mut unknown = Vec::new();
+        collect_unknown_keys(self.document.as_item(), &known, &mut Vec::new(), &mut unknown);
+        for key in unknown {
+            let segments = key.iter().map(|segment| segment.as_str()).collect::<Vec<_>>();
+            self.report(&segments, format!("unknown key: {}", key.join(".")));
+        }
+    }
+
+    fn check_interfaces(&mut self, interfaces: &InterfaceConfig) {
+        if crate::interface::name_to_index(&interfaces.upstream).is_err() {
+            self.report(&["interfaces", "upstream"], format!("unknown interface: {}", interfaces.upstream));
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        for (i, name) in interfaces.downstreams.iter().enumerate() {
+            let index = i.to_string();
+            let key = ["interfaces", "downstreams", index.as_str()];
+            if name == &interfaces.upstream {
+                self.report(&key, format!("upstream interface {} is also listed as a downstream", name));
+            }
+            if !seen.insert(name) {
+                self.report(&key, format!("duplicate downstream interface: {}", name));
+                continue;
+            }
+            if crate::interface::name_to_index(name).is_err() {
+                self.report(&key, format!("unknown interface: {}", name));
+            }
+        }
+    }
+
+    fn check_proxy_mode(&mut self, config: &Config) {
+        match config.global.proxy_mode {
+            ProxyMode::NdpProxy => {
+                for key in ["request_address", "prefix_length_hint"] {
+                    if self.is_set(&["dhcp6", key]) {
+                        self.report(&["dhcp6", key], format!("dhcp6.{} is only used with proxy_mode = \"dhcpv6_pd\"", key));
+                    }
+                }
+            }
+
+            ProxyMode::Dhcpv6Pd => {
+                if !config.ra_rewrite.is_empty() {
+                    self.report(&["ra_rewrite"], "ra_rewrite has no effect with proxy_mode = \"dhcpv6_pd\" because upstream RAs are not relayed".to_owned());
+                }
+            }
+        }
+
+        if config.global.proxy_mode == ProxyMode::NdpProxy && config.ra_server.enabled && !config.ra_rewrite.is_empty() {
+            self.report(&["ra_rewrite"], "ra_rewrite has no effect when ra_server is enabled because upstream RAs are not relayed".to_owned());
+        }
+    }
+
+    /// Limits from RFC 4861 section 6.2.1
+    fn check_ra_server(&mut self, ra_server: &RaServerConfig) {
+        if !(4..=1800).contains(&ra_server.max_interval) {
+            self.report(&["ra_server", "max_interval"], "ra_server.max_interval must be between 4 and 1800 seconds".to_owned());
+        } else if !(3..=(ra_server.max_interval * 3 / 4)).contains(&ra_server.min_interval()) {
+            self.report(&["ra_server", "min_interval"], "ra_server.min_interval must be between 3 seconds and 0.75 * max_interval".to_owned());
+        }
+
+        if ra_server.router_lifetime != 0 && ((ra_server.router_lifetime as u32) < ra_server.max_interval || ra_server.router_lifetime > 9000) {
+            self.report(&["ra_server", "router_lifetime"], "ra_server.router_lifetime must be 0 or between max_interval and 9000 seconds".to_owned());
+        }
+
+        for (i, prefix) in ra_server.prefixes.iter().enumerate() {
+            let index = i.to_string();
+            if prefix.prefix_len > 128 {
+                self.report(&["ra_server", "prefixes", index.as_str(), "prefix_len"], format!("invalid prefix length: {}", prefix.prefix_len));
+            }
+            if prefix.preferred_lifetime > prefix.valid_lifetime {
+                self.report(&["ra_server", "prefixes", index.as_str(), "preferred_lifetime"], "preferred_lifetime must not exceed valid_lifetime".to_owned());
+            }
+        }
+
+        for (i, route) in ra_server.routes.iter().enumerate() {
+            let index = i.to_string();
+            if route.prefix_len > 128 {
+                self.report(&["ra_server", "routes", index.as_str(), "prefix_len"], format!("invalid prefix length: {}", route.prefix_len));
+            }
+        }
+    }
+
+    /// Limits from the MLDv2 header fields (RFC 3810 sections 5.1.3, 5.1.8 and 9)
+    fn check_mld_querier(&mut self, mld_querier: &MldQuerierConfig) {
+        if !(1..=7).contains(&mld_querier.robustness) {
+            self.report(&["mld_querier", "robustness"], "mld_querier.robustness must be between 1 and 7".to_owned());
+        }
+
+        if mld_querier.query_interval == 0 || mld_querier.query_interval > 31744 {
+            self.report(&["mld_querier", "query_interval"], "mld_querier.query_interval must be between 1 and 31744 seconds".to_owned());
+        } else if mld_querier.query_response_interval as u64 >= mld_querier.query_interval as u64 * 1000 {
+            self.report(&["mld_querier", "query_response_interval"], "mld_querier.query_response_interval must be less than query_interval".to_owned());
+        }
+
+        if mld_querier.query_response_interval > 8387584 {
+            self.report(&["mld_querier", "query_response_interval"], "mld_querier.query_response_interval must not exceed 8387584 milliseconds".to_owned());
+        }
+
+        if mld_querier.last_listener_query_interval == 0 || mld_querier.last_listener_query_interval > 8387584 {
+            self.report(&["mld_querier", "last_listener_query_interval"], "mld_querier.last_listener_query_interval must be between 1 and 8387584 milliseconds".to_owned());
+        }
+    }
+
+    fn check_mld_acl(&mut self, name: &str, acl: &MldAclConfig) {
+        let lists = [
+            ("allow_groups", &acl.allow_groups),
+            ("deny_groups", &acl.deny_groups),
+            ("allow_sources", &acl.allow_sources),
+            ("deny_sources", &acl.deny_sources),
+        ];
+        for (list, prefixes) in lists {
+            for (i, prefix) in prefixes.iter().enumerate() {
+                let index = i.to_string();
+                if prefix.prefix_len > 128 {
+                    self.report(&["mld_acl", name, list, index.as_str(), "prefix_len"], format!("invalid prefix length: {}", prefix.prefix_len));
+                }
+            }
+        }
     }
 }
 
@@ -107,3 +444,457 @@ pub struct GlobalConfig {
     #[serde(default)]
     pub proxy_mode: ProxyMode,
 }
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DuidType {
+    /// DUID-LL from the upstream MAC address
+    #[default]
+    Ll,
+
+    /// DUID-LLT from the upstream MAC address and the generation time
+    Llt,
+
+    /// DUID-UUID with a random UUID
+    Uuid,
+}
+
+fn default_state_dir() -> PathBuf {
+    PathBuf::from("/var/lib/ftthd")
+}
+
+/// DHCPv6 client on the upstream interface
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Dhcp6Config {
+    /// type of DUID generated on first start; the DUID is kept in `state_dir` afterwards
+    #[serde(default)]
+    pub duid_type: DuidType,
+
+    /// directory holding the DUID and the last lease
+    #[serde(default = "default_state_dir")]
+    pub state_dir: PathBuf,
+
+    /// also request a non-temporary address (IA_NA) for the upstream interface
+    #[serde(default)]
+    pub request_address: bool,
+
+    /// prefix length to hint in IA_PD requests, e.g. 56
+    #[serde(default)]
+    pub prefix_length_hint: Option<u8>,
+}
+
+impl Default for Dhcp6Config {
+    fn default() -> Self {
+        Self {
+            duid_type: DuidType::default(),
+            state_dir: default_state_dir(),
+            request_address: false,
+            prefix_length_hint: None,
+        }
+    }
+}
+
+/// Stateless DHCPv6 server on downstream interfaces
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct Dhcp6ServerConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// DNS servers to hand out; taken from upstream if empty
+    #[serde(default)]
+    pub dns_servers: Vec<std::net::Ipv6Addr>,
+
+    /// domain search list to hand out; taken from upstream if empty
+    #[serde(default)]
+    pub domain_list: Vec<String>,
+}
+
+fn default_relay_server_address() -> std::net::Ipv6Addr {
+    crate::dhcp6::ALL_DHCP_RELAY_AGENTS_AND_SERVERS
+}
+
+/// DHCPv6 relay agent from downstream interfaces to upstream
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Dhcp6RelayConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// where Relay-Forward messages are sent
+    #[serde(default = "default_relay_server_address")]
+    pub server_address: std::net::Ipv6Addr,
+}
+
+impl Default for Dhcp6RelayConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            server_address: default_relay_server_address(),
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_prefix_len() -> u8 {
+    64
+}
+
+fn default_max_rtr_adv_interval() -> u32 {
+    600
+}
+
+fn default_router_lifetime() -> u16 {
+    1800
+}
+
+fn default_hop_limit() -> u8 {
+    64
+}
+
+fn default_valid_lifetime() -> u32 {
+    2592000
+}
+
+fn default_preferred_lifetime() -> u32 {
+    604800
+}
+
+/// Router Advertisements originated on downstream interfaces (RFC 4861 section 6.2.1)
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct RaServerConfig {
+    /// always on in dhcpv6_pd mode
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// MaxRtrAdvInterval in seconds
+    #[serde(default = "default_max_rtr_adv_interval")]
+    pub max_interval: u32,
+
+    /// MinRtrAdvInterval in seconds; 0.33 * max_interval if unset, or 0.75 * max_interval when max_interval is below 9
+    #[serde(default)]
+    pub min_interval: Option<u32>,
+
+    #[serde(default = "default_router_lifetime")]
+    pub router_lifetime: u16,
+
+    #[serde(default = "default_hop_limit")]
+    pub hop_limit: u8,
+
+    /// milliseconds
+    #[serde(default)]
+    pub reachable_time: u32,
+
+    /// milliseconds
+    #[serde(default)]
+    pub retrans_timer: u32,
+
+    /// M flag
+    #[serde(default)]
+    pub managed: bool,
+
+    /// O flag
+    #[serde(default)]
+    pub other: bool,
+
+    #[serde(default)]
+    pub mtu: Option<u32>,
+
+    #[serde(default)]
+    pub prefixes: Vec<RaPrefixConfig>,
+
+    #[serde(default)]
+    pub rdnss: Vec<std::net::Ipv6Addr>,
+
+    #[serde(default)]
+    pub dnssl: Vec<String>,
+
+    /// lifetime of RDNSS/DNSSL in seconds; 3 * max_interval if unset
+    #[serde(default)]
+    pub dns_lifetime: Option<u32>,
+
+    #[serde(default)]
+    pub routes: Vec<RaRouteConfig>,
+}
+
+impl RaServerConfig {
+    pub fn min_interval(&self) -> u32 {
+        if let Some(min_interval) = self.min_interval {
+            min_interval
+        } else if self.max_interval >= 9 {
+            self.max_interval * 33 / 100
+        } else {
+            self.max_interval * 3 / 4
+        }
+    }
+
+    pub fn dns_lifetime(&self) -> u32 {
+        self.dns_lifetime.unwrap_or(self.max_interval * 3)
+    }
+}
+
+impl Default for RaServerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_interval: default_max_rtr_adv_interval(),
+            min_interval: None,
+            router_lifetime: default_router_lifetime(),
+            hop_limit: default_hop_limit(),
+            reachable_time: 0,
+            retrans_timer: 0,
+            managed: false,
+            other: false,
+            mtu: None,
+            prefixes: Vec::new(),
+            rdnss: Vec::new(),
+            dnssl: Vec::new(),
+            dns_lifetime: None,
+            routes: Vec::new(),
+        }
+    }
+}
+
+/// Prefix Information option
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct RaPrefixConfig {
+    pub prefix: std::net::Ipv6Addr,
+
+    #[serde(default = "default_prefix_len")]
+    pub prefix_len: u8,
+
+    /// L flag
+    #[serde(default = "default_true")]
+    pub on_link: bool,
+
+    /// A flag
+    #[serde(default = "default_true")]
+    pub autonomous: bool,
+
+    #[serde(default = "default_valid_lifetime")]
+    pub valid_lifetime: u32,
+
+    #[serde(default = "default_preferred_lifetime")]
+    pub preferred_lifetime: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RoutePreference {
+    High,
+
+    #[default]
+    Medium,
+
+    Low,
+}
+
+impl From<RoutePreference> for u8 {
+    /// two-bit Prf value (RFC 4191 section 2.1)
+    fn from(value: RoutePreference) -> Self {
+        match value {
+            RoutePreference::High => 0b01,
+            RoutePreference::Medium => 0b00,
+            RoutePreference::Low => 0b11,
+        }
+    }
+}
+
+fn default_route_lifetime() -> u32 {
+    default_router_lifetime() as u32
+}
+
+/// Route Information option (RFC 4191)
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct RaRouteConfig {
+    pub prefix: std::net::Ipv6Addr,
+    pub prefix_len: u8,
+
+    #[serde(default)]
+    pub preference: RoutePreference,
+
+    #[serde(default = "default_route_lifetime")]
+    pub lifetime: u32,
+}
+
+fn default_dns_lifetime() -> u32 {
+    default_max_rtr_adv_interval() * 3
+}
+
+/// Overrides applied to an upstream RA before it is relayed to a downstream
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct RaRewriteConfig {
+    #[serde(default)]
+    pub router_lifetime: Option<u16>,
+
+    #[serde(default)]
+    pub hop_limit: Option<u8>,
+
+    /// M flag
+    #[serde(default)]
+    pub managed: Option<bool>,
+
+    /// O flag
+    #[serde(default)]
+    pub other: Option<bool>,
+
+    /// upper bound for the MTU option; added if the upstream RA has none
+    #[serde(default)]
+    pub max_mtu: Option<u32>,
+
+    /// remove upstream RDNSS options
+    #[serde(default)]
+    pub drop_rdnss: bool,
+
+    /// remove upstream DNSSL options
+    #[serde(default)]
+    pub drop_dnssl: bool,
+
+    /// RDNSS to inject
+    #[serde(default)]
+    pub rdnss: Vec<std::net::Ipv6Addr>,
+
+    /// DNSSL to inject
+    #[serde(default)]
+    pub dnssl: Vec<String>,
+
+    /// lifetime of injected RDNSS/DNSSL in seconds
+    #[serde(default = "default_dns_lifetime")]
+    pub dns_lifetime: u32,
+
+    /// Route Information options to add
+    #[serde(default)]
+    pub routes: Vec<RaRouteConfig>,
+}
+
+fn default_robustness() -> u8 {
+    2
+}
+
+fn default_query_interval() -> u32 {
+    125
+}
+
+fn default_query_response_interval() -> u32 {
+    10000
+}
+
+fn default_last_listener_query_interval() -> u32 {
+    1000
+}
+
+/// MLDv2 querier on downstream interfaces (RFC 3810 section 9)
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MldQuerierConfig {
+    /// off by default: upstream queries are relayed downstream instead, as before the querier existed
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Robustness Variable
+    #[serde(default = "default_robustness")]
+    pub robustness: u8,
+
+    /// Query Interval in seconds
+    #[serde(default = "default_query_interval")]
+    pub query_interval: u32,
+
+    /// Query Response Interval in milliseconds
+    #[serde(default = "default_query_response_interval")]
+    pub query_response_interval: u32,
+
+    /// Last Listener Query Interval in milliseconds
+    #[serde(default = "default_last_listener_query_interval")]
+    pub last_listener_query_interval: u32,
+}
+
+impl MldQuerierConfig {
+    pub fn query_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.query_interval as u64)
+    }
+
+    pub fn query_response_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.query_response_interval as u64)
+    }
+
+    pub fn last_listener_query_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.last_listener_query_interval as u64)
+    }
+
+    /// Multicast Address Listening Interval (RFC 3810 section 9.4)
+    pub fn multicast_address_listening_interval(&self) -> std::time::Duration {
+        self.query_interval() * self.robustness as u32 + self.query_response_interval()
+    }
+
+    /// Last Listener Query Time, with the Last Listener Query Count equal to the Robustness Variable (RFC 3810 section 9.14)
+    pub fn last_listener_query_time(&self) -> std::time::Duration {
+        self.last_listener_query_interval() * self.robustness as u32
+    }
+}
+
+impl Default for MldQuerierConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            robustness: default_robustness(),
+            query_interval: default_query_interval(),
+            query_response_interval: default_query_response_interval(),
+            last_listener_query_interval: default_last_listener_query_interval(),
+        }
+    }
+}
+
+/// Address prefix matched by an ACL entry
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MldAclPrefix {
+    pub prefix: std::net::Ipv6Addr,
+    pub prefix_len: u8,
+}
+
+impl MldAclPrefix {
+    pub fn contains(&self, addr: &std::net::Ipv6Addr) -> bool {
+        let mask = if self.prefix_len == 0 { 0 } else { !0u128 << (128 - self.prefix_len.min(128) as u32) };
+        u128::from(*addr) & mask == u128::from(self.prefix) & mask
+    }
+}
+
+/// Groups and sources downstream listeners may subscribe to
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct MldAclConfig {
+    /// groups that may be joined; any group if empty
+    #[serde(default)]
+    pub allow_groups: Vec<MldAclPrefix>,
+
+    /// groups that may not be joined, even if allowed above
+    #[serde(default)]
+    pub deny_groups: Vec<MldAclPrefix>,
+
+    /// sources that may be requested; any source if empty
+    #[serde(default)]
+    pub allow_sources: Vec<MldAclPrefix>,
+
+    /// sources that may not be requested, even if allowed above
+    #[serde(default)]
+    pub deny_sources: Vec<MldAclPrefix>,
+
+    /// number of groups the interface may have joined at once
+    #[serde(default)]
+    pub max_groups: Option<usize>,
+}
+
+impl MldAclConfig {
+    pub fn permits_group(&self, group_addr: &std::net::Ipv6Addr) -> bool {
+        (self.allow_groups.is_empty() || self.allow_groups.iter().any(|prefix| prefix.contains(group_addr)))
+            && !self.deny_groups.iter().any(|prefix| prefix.contains(group_addr))
+    }
+
+    pub fn permits_source(&self, source_addr: &std::net::Ipv6Addr) -> bool {
+        (self.allow_sources.is_empty() || self.allow_sources.iter().any(|prefix| prefix.contains(source_addr)))
+            && !self.deny_sources.iter().any(|prefix| prefix.contains(source_addr))
+    }
+
+    /// Whether EXCLUDE-mode joins are allowed; they would let sources through the source lists cannot filter
+    pub fn permits_any_source(&self) -> bool {
+        self.allow_sources.is_empty() && self.deny_sources.is_empty()
+    }
+}
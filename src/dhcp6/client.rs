@@ -0,0 +1,654 @@
+use super::*;
+use crate::interface::InterfaceId;
+
+use std::net::Ipv6Addr;
+use std::time::Duration;
+
+use rand::Rng;
+use tokio::time::Instant;
+
+const INFINITY: u32 = 0xffffffff;
+
+/// Retransmission parameters (RFC 8415 section 15)
+#[derive(Debug, Clone, Copy)]
+pub struct Retransmission {
+    /// maximum random delay before the first transmission
+    pub max_delay: Duration,
+
+    /// initial retransmission time
+    pub irt: Duration,
+
+    /// maximum retransmission time, zero for no limit
+    pub mrt: Duration,
+
+    /// maximum retransmission count, zero for no limit
+    pub mrc: u32,
+
+    /// maximum retransmission duration, zero for no limit
+    pub mrd: Duration,
+}
+
+impl Retransmission {
+    pub const SOLICIT: Self = Self { max_delay: Duration::from_secs(1), irt: Duration::from_secs(1), mrt: Duration::from_secs(3600), mrc: 0, mrd: Duration::ZERO };
+    pub const REQUEST: Self = Self { max_delay: Duration::ZERO, irt: Duration::from_secs(1), mrt: Duration::from_secs(30), mrc: 10, mrd: Duration::ZERO };
+    pub const RENEW: Self = Self { max_delay: Duration::ZERO, irt: Duration::from_secs(10), mrt: Duration::from_secs(600), mrc: 0, mrd: Duration::ZERO };
+    pub const REBIND: Self = Self { max_delay: Duration::ZERO, irt: Duration::from_secs(10), mrt: Duration::from_secs(600), mrc: 0, mrd: Duration::ZERO };
+    pub const CONFIRM: Self = Self { max_delay: Duration::from_secs(1), irt: Duration::from_secs(1), mrt: Duration::from_secs(4), mrc: 0, mrd: Duration::from_secs(10) };
+    pub const RELEASE: Self = Self { max_delay: Duration::ZERO, irt: Duration::from_secs(1), mrt: Duration::ZERO, mrc: 4, mrd: Duration::ZERO };
+    pub const INFORMATION_REQUEST: Self = Self { max_delay: Duration::from_secs(1), irt: Duration::from_secs(1), mrt: Duration::from_secs(3600), mrc: 0, mrd: Duration::ZERO };
+
+    fn with_mrd(self, mrd: Duration) -> Self {
+        Self { mrd, ..self }
+    }
+
+    fn randomize(rt: Duration, positive: bool) -> Duration {
+        let rand: f64 = if positive {
+            rand::thread_rng().gen_range(0.0..0.1)
+        } else {
+            rand::thread_rng().gen_range(-0.1..0.1)
+        };
+        rt.mul_f64(1.0 + rand)
+    }
+
+    /// the first retransmission timeout; RAND is positive for Solicit
+    fn initial(&self, positive: bool) -> Duration {
+        Self::randomize(self.irt, positive)
+    }
+
+    fn next(&self, rt: Duration) -> Duration {
+        let rt = Self::randomize(rt * 2, false);
+        if !self.mrt.is_zero() && rt > self.mrt {
+            Self::randomize(self.mrt, false)
+        } else {
+            rt
+        }
+    }
+}
+
+/// Configuration acquired from a server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dhcp6Lease {
+    pub server_id: Duid,
+    pub iaid: u32,
+    pub t1: u32,
+    pub t2: u32,
+    pub addresses: Vec<IaAddr>,
+    pub prefixes: Vec<IaPrefix>,
+    pub dns_servers: Vec<Ipv6Addr>,
+    pub domain_list: Vec<String>,
+
+    /// UNIX time the lease was obtained or last extended
+    pub timestamp: u64,
+}
+
+impl Dhcp6Lease {
+    fn from_reply(reply: &Dhcp6Message, iaid: u32, config: &Dhcp6ClientConfig) -> Result<Self, std::io::Error> {
+        if let Some(status) = reply.status_code() {
+            if status.code != StatusCodeValue::Success {
+                log::warn!("DHCPv6 server returned {:?}: {}", status.code, status.message);
+                return Err(std::io::Error::other("DHCPv6 server returned an error status"));
+            }
+        }
+
+        let server_id = reply.server_id().cloned()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "DHCPv6 reply without server ID"))?;
+
+        let mut t1s = Vec::new();
+        let mut t2s = Vec::new();
+
+        let mut addresses = Vec::new();
+        if config.request_address {
+            let ia_na = reply.ia_na(iaid)
+                .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "DHCPv6 reply without IA_NA"))?;
+            if let Some(status) = ia_na.status_code() {
+                if status.code != StatusCodeValue::Success {
+                    log::warn!("DHCPv6 server returned {:?} for IA_NA: {}", status.code, status.message);
+                    return Err(std::io::Error::other("no address assigned"));
+                }
+            }
+            addresses = ia_na.addresses().into_iter()
+                .filter(|addr| addr.valid_lifetime != 0)
+                .collect();
+            if addresses.is_empty() {
+                return Err(std::io::Error::other("no address assigned"));
+            }
+            t1s.push(ia_na.t1);
+            t2s.push(ia_na.t2);
+        }
+
+        let mut prefixes = Vec::new();
+        if config.request_prefix {
+            let ia_pd = reply.ia_pd(iaid)
+                .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "DHCPv6 reply without IA_PD"))?;
+            if let Some(status) = ia_pd.status_code() {
+                if status.code != StatusCodeValue::Success {
+                    log::warn!("DHCPv6 server returned {:?} for IA_PD: {}", status.code, status.message);
+                    return Err(std::io::Error::other("no prefix delegated"));
+                }
+            }
+            prefixes = ia_pd.prefixes().into_iter()
+                .filter(|prefix| prefix.valid_lifetime != 0)
+                .collect();
+            if prefixes.is_empty() {
+                return Err(std::io::Error::other("no prefix delegated"));
+            }
+            t1s.push(ia_pd.t1);
+            t2s.push(ia_pd.t2);
+        }
+
+        let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+
+        Ok(Self {
+            server_id,
+            iaid,
+            t1: t1s.into_iter().filter(|t| *t != 0).min().unwrap_or(0),
+            t2: t2s.into_iter().filter(|t| *t != 0).min().unwrap_or(0),
+            addresses,
+            prefixes,
+            dns_servers: reply.dns_servers(),
+            domain_list: reply.domain_list(),
+            timestamp,
+        })
+    }
+
+    fn shortest_preferred_lifetime(&self) -> u32 {
+        self.addresses.iter().map(|addr| addr.preferred_lifetime)
+            .chain(self.prefixes.iter().map(|prefix| prefix.preferred_lifetime))
+            .min()
+            .unwrap_or(0)
+    }
+
+    fn shortest_valid_lifetime(&self) -> u32 {
+        self.addresses.iter().map(|addr| addr.valid_lifetime)
+            .chain(self.prefixes.iter().map(|prefix| prefix.valid_lifetime))
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Seconds after `timestamp` when the client starts renewing
+    pub fn renew_after(&self) -> u32 {
+        if self.t1 != 0 {
+            return self.t1;
+        }
+        let preferred = self.shortest_preferred_lifetime();
+        if preferred == INFINITY {
+            return INFINITY;
+        }
+        preferred / 2
+    }
+
+    /// Seconds after `timestamp` when the client starts rebinding
+    pub fn rebind_after(&self) -> u32 {
+        if self.t2 != 0 {
+            return self.t2;
+        }
+        let preferred = self.shortest_preferred_lifetime();
+        if preferred == INFINITY {
+            return INFINITY;
+        }
+        (preferred as u64 * 4 / 5) as u32
+    }
+
+    /// Seconds after `timestamp` when the lease is no longer usable
+    pub fn expires_after(&self) -> u32 {
+        self.shortest_valid_lifetime()
+    }
+
+    /// Whether the shortest-lived address or prefix of the lease has run out
+    pub fn is_expired(&self) -> bool {
+        self.seconds_left(self.expires_after()).is_zero()
+    }
+
+    fn seconds_left(&self, after: u32) -> Duration {
+        if after == INFINITY {
+            return Duration::from_secs(INFINITY as u64);
+        }
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+        let deadline = self.timestamp + after as u64;
+        Duration::from_secs(deadline.saturating_sub(now))
+    }
+
+    fn ia_options(&self, config: &Dhcp6ClientConfig) -> Vec<Dhcp6Option> {
+        let mut options = Vec::new();
+        if config.request_address {
+            options.push(Dhcp6Option::IaNa(IaNa {
+                iaid: self.iaid,
+                t1: 0,
+                t2: 0,
+                options: self.addresses.iter().cloned().map(Dhcp6Option::IaAddr).collect(),
+            }));
+        }
+        if config.request_prefix {
+            options.push(Dhcp6Option::IaPd(IaPd {
+                iaid: self.iaid,
+                t1: 0,
+                t2: 0,
+                options: self.prefixes.iter().cloned().map(Dhcp6Option::IaPrefix).collect(),
+            }));
+        }
+        options
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Dhcp6Event {
+    /// a new lease was obtained
+    Acquired(Dhcp6Lease),
+
+    /// an existing lease was extended by Renew or Rebind
+    Renewed(Dhcp6Lease),
+
+    /// the lease expired without being extended
+    Lost(Dhcp6Lease),
+}
+
+/// What the client asks the server for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dhcp6ClientConfig {
+    pub request_address: bool,
+    pub request_prefix: bool,
+    pub prefix_length_hint: Option<u8>,
+}
+
+/// Stateful DHCPv6 client for one interface (RFC 8415 section 18)
+#[derive(Debug)]
+pub struct Dhcp6Client<T = Dhcp6Socket> {
+    transport: T,
+    duid: Duid,
+    iaid: u32,
+    config: Dhcp6ClientConfig,
+    store: Option<store::LeaseStore>,
+    broadcast: tokio::sync::broadcast::Sender<Dhcp6Event>,
+}
+
+impl Dhcp6Client {
+    /// `store`, if given, receives every lease change so that it survives restarts.
+    /// `iaid` must stay the same across restarts for the server to recognize the IAs.
+    pub fn new(if_index: InterfaceId, duid: Duid, iaid: u32, config: Dhcp6ClientConfig, store: Option<store::LeaseStore>) -> Result<Self, std::io::Error> {
+        let socket = Dhcp6Socket::new(if_index, CLIENT_PORT)?;
+        Ok(Self::with_transport(socket, duid, iaid, config, store))
+    }
+
+    pub fn if_index(&self) -> InterfaceId {
+        self.transport.if_index()
+    }
+}
+
+impl<T: Dhcp6Transport> Dhcp6Client<T> {
+    pub fn with_transport(transport: T, duid: Duid, iaid: u32, config: Dhcp6ClientConfig, store: Option<store::LeaseStore>) -> Self {
+        Self {
+            transport,
+            duid,
+            iaid,
+            config,
+            store,
+            broadcast: tokio::sync::broadcast::channel(16).0,
+        }
+    }
+
+    pub fn iaid(&self) -> u32 {
+        self.iaid
+    }
+
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<Dhcp6Event> {
+        self.broadcast.subscribe()
+    }
+
+    fn notify(&self, event: Dhcp6Event) {
+        log::debug!("DHCPv6 client event: {:?}", event);
+        if let Some(store) = &self.store {
+            let res = match &event {
+                Dhcp6Event::Acquired(lease) | Dhcp6Event::Renewed(lease) => store.save_lease(lease),
+                Dhcp6Event::Lost(_) => store.clear_lease(),
+            };
+            if let Err(e) = res {
+                log::error!("Failed to store DHCPv6 lease: {:?}", e);
+            }
+        }
+        let _ = self.broadcast.send(event);
+    }
+
+    fn build_message(&self, msg_type: MessageType, server_id: Option<&Duid>, ia_options: Vec<Dhcp6Option>) -> Dhcp6Message {
+        let mut message = Dhcp6Message::new(msg_type, rand::random());
+        message.options.push(Dhcp6Option::ClientId(self.duid.clone()));
+        if let Some(server_id) = server_id {
+            message.options.push(Dhcp6Option::ServerId(server_id.clone()));
+        }
+        message.options.push(Dhcp6Option::ElapsedTime(0));
+        if msg_type != MessageType::Release && msg_type != MessageType::Confirm {
+            let mut requested = vec![OPTION_DNS_SERVERS, OPTION_DOMAIN_LIST];
+            if msg_type == MessageType::InformationRequest {
+                requested.push(OPTION_INFORMATION_REFRESH_TIME);
+            }
+            message.options.push(Dhcp6Option::OptionRequest(requested));
+        }
+        message.options.extend(ia_options);
+        message
+    }
+
+    fn solicit_ia_options(&self) -> Vec<Dhcp6Option> {
+        let mut options = Vec::new();
+        if self.config.request_address {
+            options.push(Dhcp6Option::IaNa(IaNa {
+                iaid: self.iaid,
+                t1: 0,
+                t2: 0,
+                options: Vec::new(),
+            }));
+        }
+        if self.config.request_prefix {
+            let hint = self.config.prefix_length_hint.map(|prefix_len| {
+                Dhcp6Option::IaPrefix(IaPrefix {
+                    preferred_lifetime: 0,
+                    valid_lifetime: 0,
+                    prefix_len,
+                    prefix: Ipv6Addr::UNSPECIFIED,
+                    options: Vec::new(),
+                })
+            });
+            options.push(Dhcp6Option::IaPd(IaPd {
+                iaid: self.iaid,
+                t1: 0,
+                t2: 0,
+                options: hint.into_iter().collect(),
+            }));
+        }
+        options
+    }
+
+    /// Sends `message` with retransmissions and waits for an acceptable answer of type `expected`.
+    /// Advertisements received during the first RT are collected and the most preferred one wins.
+    async fn exchange<F>(&self, mut message: Dhcp6Message, expected: MessageType, params: Retransmission, accept: F) -> Result<Dhcp6Message, std::io::Error>
+    where
+        F: Fn(&Dhcp6Message) -> bool,
+    {
+        if !params.max_delay.is_zero() {
+            let delay = rand::thread_rng().gen_range(Duration::ZERO..params.max_delay);
+            tokio::time::sleep(delay).await;
+        }
+
+        let started = Instant::now();
+        let mut rt = params.initial(message.msg_type == MessageType::Solicit);
+        let mut count = 0;
+        let mut buf = vec![0u8; 65536];
+
+        loop {
+            let elapsed = (started.elapsed().as_millis() / 10).min(0xffff) as u16;
+            for option in message.options.iter_mut() {
+                if let Dhcp6Option::ElapsedTime(value) = option {
+                    *value = elapsed;
+                }
+            }
+
+            self.transport.send_to(&message.serialize(), ALL_DHCP_RELAY_AGENTS_AND_SERVERS, SERVER_PORT).await?;
+            count += 1;
+
+            let mut deadline = Instant::now() + rt;
+            if !params.mrd.is_zero() {
+                deadline = deadline.min(started + params.mrd);
+            }
+
+            let collect = expected == MessageType::Advertise && count == 1;
+            let mut best: Option<Dhcp6Message> = None;
+            loop {
+                let (len, src) = match tokio::time::timeout_at(deadline, self.transport.recv_from(&mut buf)).await {
+                    Ok(res) => res?,
+                    Err(_) => break,
+                };
+
+                let reply = match Dhcp6Message::parse(&buf[..len]) {
+                    Ok(reply) => reply,
+                    Err(e) => {
+                        log::debug!("Failed to parse DHCPv6 message from {}: {}", src, e);
+                        continue;
+                    }
+                };
+
+                if reply.transaction_id != message.transaction_id || reply.msg_type != expected {
+                    continue;
+                }
+
+                if reply.client_id() != Some(&self.duid) || !accept(&reply) {
+                    continue;
+                }
+
+                if !collect || reply.preference() == 255 {
+                    return Ok(reply);
+                }
+
+                if best.as_ref().map(|best| best.preference() < reply.preference()).unwrap_or(true) {
+                    best = Some(reply);
+                }
+            }
+
+            if let Some(best) = best {
+                return Ok(best);
+            }
+
+            if params.mrc != 0 && count >= params.mrc {
+                return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "DHCPv6 retransmission count exceeded"));
+            }
+            if !params.mrd.is_zero() && started.elapsed() >= params.mrd {
+                return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "DHCPv6 retransmission duration exceeded"));
+            }
+
+            rt = params.next(rt);
+        }
+    }
+
+    /// Solicit, then request the configuration from the most preferred server
+    pub async fn solicit(&self) -> Result<Dhcp6Lease, std::io::Error> {
+        let solicit = self.build_message(MessageType::Solicit, None, self.solicit_ia_options());
+        let advertise = self.exchange(solicit, MessageType::Advertise, Retransmission::SOLICIT, |reply| {
+            Dhcp6Lease::from_reply(reply, self.iaid, &self.config).is_ok()
+        }).await?;
+        log::debug!("DHCPv6 Advertise: {:?}", advertise);
+
+        let offered = Dhcp6Lease::from_reply(&advertise, self.iaid, &self.config)?;
+
+        let request = self.build_message(MessageType::Request, Some(&offered.server_id), offered.ia_options(&self.config));
+        let reply = self.exchange(request, MessageType::Reply, Retransmission::REQUEST, |_| true).await?;
+        log::debug!("DHCPv6 Reply: {:?}", reply);
+
+        Dhcp6Lease::from_reply(&reply, self.iaid, &self.config)
+    }
+
+    /// Retransmission duration left until `deadline`; a zero MRD would mean no limit at all
+    fn time_left(deadline: Instant) -> Result<Duration, std::io::Error> {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "DHCPv6 lease timer already passed"));
+        }
+        Ok(left)
+    }
+
+    /// Extend the lease with the server that granted it, until T2
+    pub async fn renew(&self, lease: &Dhcp6Lease) -> Result<Dhcp6Lease, std::io::Error> {
+        self.renew_until(lease, Instant::now() + lease.seconds_left(lease.rebind_after())).await
+    }
+
+    async fn renew_until(&self, lease: &Dhcp6Lease, deadline: Instant) -> Result<Dhcp6Lease, std::io::Error> {
+        let params = Retransmission::RENEW.with_mrd(Self::time_left(deadline)?);
+        let renew = self.build_message(MessageType::Renew, Some(&lease.server_id), lease.ia_options(&self.config));
+        let reply = self.exchange(renew, MessageType::Reply, params, |_| true).await?;
+        log::debug!("DHCPv6 Reply: {:?}", reply);
+
+        self.reinstate_if_unbound(&reply, lease).await
+    }
+
+    /// Extend the lease with any server, until the lease expires
+    pub async fn rebind(&self, lease: &Dhcp6Lease) -> Result<Dhcp6Lease, std::io::Error> {
+        self.rebind_until(lease, Instant::now() + lease.seconds_left(lease.expires_after())).await
+    }
+
+    async fn rebind_until(&self, lease: &Dhcp6Lease, deadline: Instant) -> Result<Dhcp6Lease, std::io::Error> {
+        let params = Retransmission::REBIND.with_mrd(Self::time_left(deadline)?);
+        let rebind = self.build_message(MessageType::Rebind, None, lease.ia_options(&self.config));
+        let reply = self.exchange(rebind, MessageType::Reply, params, |_| true).await?;
+        log::debug!("DHCPv6 Reply: {:?}", reply);
+
+        self.reinstate_if_unbound(&reply, lease).await
+    }
+
+    /// A server that has no binding for our IAs is sent a Request to reinstate them (RFC 8415 section 18.2.10.1)
+    async fn reinstate_if_unbound(&self, reply: &Dhcp6Message, lease: &Dhcp6Lease) -> Result<Dhcp6Lease, std::io::Error> {
+        let ia_na_status = reply.ia_na(self.iaid).and_then(|ia_na| ia_na.status_code());
+        let ia_pd_status = reply.ia_pd(self.iaid).and_then(|ia_pd| ia_pd.status_code());
+        let no_binding = [ia_na_status, ia_pd_status].iter().flatten().any(|status| status.code == StatusCodeValue::NoBinding);
+        if !no_binding {
+            return Dhcp6Lease::from_reply(reply, self.iaid, &self.config);
+        }
+
+        log::info!("DHCPv6 server has no binding for the lease, requesting it again");
+        let server_id = reply.server_id().unwrap_or(&lease.server_id);
+        let request = self.build_message(MessageType::Request, Some(server_id), lease.ia_options(&self.config));
+        let reply = self.exchange(request, MessageType::Reply, Retransmission::REQUEST, |_| true).await?;
+        log::debug!("DHCPv6 Reply: {:?}", reply);
+
+        Dhcp6Lease::from_reply(&reply, self.iaid, &self.config)
+    }
+
+    /// Ask whether the addresses of the lease are still on-link (RFC 8415 section 18.2.3)
+    pub async fn confirm(&self, lease: &Dhcp6Lease) -> Result<bool, std::io::Error> {
+        let ia_na = Dhcp6Option::IaNa(IaNa {
+            iaid: self.iaid,
+            t1: 0,
+            t2: 0,
+            options: lease.addresses.iter().map(|addr| Dhcp6Option::IaAddr(IaAddr {
+                addr: addr.addr,
+                preferred_lifetime: 0,
+                valid_lifetime: 0,
+                options: Vec::new(),
+            })).collect(),
+        });
+        let confirm = self.build_message(MessageType::Confirm, None, vec![ia_na]);
+        let reply = self.exchange(confirm, MessageType::Reply, Retransmission::CONFIRM, |_| true).await?;
+        log::debug!("DHCPv6 Reply: {:?}", reply);
+
+        Ok(reply.status_code().map(|status| status.code == StatusCodeValue::Success).unwrap_or(false))
+    }
+
+    /// Stateless exchange for DNS options only (RFC 8415 section 18.2.6).
+    /// Returns the options and the number of seconds until they should be refreshed.
+    pub async fn information_request(&self) -> Result<(server::ServerOptions, u32), std::io::Error> {
+        let request = self.build_message(MessageType::InformationRequest, None, Vec::new());
+        let reply = self.exchange(request, MessageType::Reply, Retransmission::INFORMATION_REQUEST, |_| true).await?;
+        log::debug!("DHCPv6 Reply: {:?}", reply);
+
+        let options = server::ServerOptions {
+            dns_servers: reply.dns_servers(),
+            domain_list: reply.domain_list(),
+        };
+        // IRT_DEFAULT and IRT_MINIMUM (RFC 8415 section 21.23)
+        let refresh = reply.information_refresh_time().unwrap_or(86400).max(600);
+        Ok((options, refresh))
+    }
+
+    /// Check a lease stored by a previous run before using it again.
+    /// Prefixes may have moved, so they are rebound; addresses alone are confirmed.
+    async fn resume(&self, lease: Dhcp6Lease) -> Result<Dhcp6Lease, std::io::Error> {
+        if lease.is_expired() {
+            return Err(std::io::Error::other("stored lease has expired"));
+        }
+
+        if self.config.request_prefix || lease.addresses.is_empty() {
+            return self.rebind(&lease).await;
+        }
+
+        if self.confirm(&lease).await? {
+            Ok(lease)
+        } else {
+            Err(std::io::Error::other("stored addresses are not on-link"))
+        }
+    }
+
+    /// Give the lease back to the server
+    pub async fn release(&self, lease: &Dhcp6Lease) -> Result<(), std::io::Error> {
+        let release = self.build_message(MessageType::Release, Some(&lease.server_id), lease.ia_options(&self.config));
+        self.exchange(release, MessageType::Reply, Retransmission::RELEASE, |_| true).await?;
+        Ok(())
+    }
+
+    /// Keep the lease bound until `lease` can no longer be extended
+    async fn maintain(&self, mut lease: Dhcp6Lease) -> Dhcp6Lease {
+        loop {
+            // timers run on the monotonic clock from the moment the lease was (re)acquired
+            let now = Instant::now();
+            let renew_at = now + lease.seconds_left(lease.renew_after());
+            let rebind_at = now + lease.seconds_left(lease.rebind_after());
+            let expire_at = now + lease.seconds_left(lease.expires_after());
+
+            tokio::time::sleep_until(renew_at).await;
+
+            match self.renew_until(&lease, rebind_at).await {
+                Ok(renewed) => {
+                    lease = renewed;
+                    self.notify(Dhcp6Event::Renewed(lease.clone()));
+                    continue;
+                }
+                Err(e) => {
+                    log::warn!("DHCPv6 Renew failed: {:?}", e);
+                }
+            }
+
+            tokio::time::sleep_until(rebind_at).await;
+
+            match self.rebind_until(&lease, expire_at).await {
+                Ok(rebound) => {
+                    lease = rebound;
+                    self.notify(Dhcp6Event::Renewed(lease.clone()));
+                }
+                Err(e) => {
+                    log::warn!("DHCPv6 Rebind failed: {:?}", e);
+                    return lease;
+                }
+            }
+        }
+    }
+
+    /// Run the client state machine forever, starting from `initial` if a lease survived a restart
+    pub async fn run(&self, initial: Option<Dhcp6Lease>) {
+        if let Some(lease) = initial {
+            match self.resume(lease).await {
+                Ok(lease) => {
+                    self.notify(Dhcp6Event::Acquired(lease.clone()));
+                    let lost = self.maintain(lease).await;
+                    self.notify(Dhcp6Event::Lost(lost));
+                }
+                Err(e) => {
+                    log::info!("Not resuming stored DHCPv6 lease: {:?}", e);
+                }
+            }
+        }
+
+        loop {
+            let lease = match self.solicit().await {
+                Ok(lease) => lease,
+                Err(e) => {
+                    log::warn!("Failed to obtain DHCPv6 lease: {:?}", e);
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+                    continue;
+                }
+            };
+            self.notify(Dhcp6Event::Acquired(lease.clone()));
+
+            let lost = self.maintain(lease).await;
+            self.notify(Dhcp6Event::Lost(lost));
+        }
+    }
+}
+
+/// Returns the `index`-th /64 inside a delegated prefix, if it fits
+pub fn subnet_prefix(prefix: &IaPrefix, index: u32) -> Option<Ipv6Addr> {
+    if prefix.prefix_len > 64 {
+        return None;
+    }
+    let available_bits = 64 - prefix.prefix_len as u32;
+    if available_bits < 32 && index >= (1u32 << available_bits) {
+        return None;
+    }
+    let base = u128::from_be_bytes(prefix.prefix.octets());
+    let mask = if prefix.prefix_len == 0 { 0 } else { !0u128 << (128 - prefix.prefix_len as u32) };
+    let subnet = (base & mask) | ((index as u128) << 64);
+    Some(Ipv6Addr::from(subnet))
+}
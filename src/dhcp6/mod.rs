@@ -0,0 +1,307 @@
+pub mod options;
+pub mod socket;
+pub mod client;
+pub mod store;
+pub mod server;
+pub mod relay;
+
+pub use options::*;
+pub use socket::Dhcp6Socket;
+pub use socket::Dhcp6Transport;
+
+pub const ALL_DHCP_RELAY_AGENTS_AND_SERVERS: std::net::Ipv6Addr = std::net::Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 1, 2);
+pub const CLIENT_PORT: u16 = 546;
+pub const SERVER_PORT: u16 = 547;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dhcp6Error {
+    message: &'static str,
+}
+
+impl Dhcp6Error {
+    pub fn new(message: &'static str) -> Self {
+        Self {
+            message,
+        }
+    }
+}
+
+impl std::fmt::Display for Dhcp6Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Dhcp6Error {}
+
+impl From<Dhcp6Error> for std::io::Error {
+    fn from(e: Dhcp6Error) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageType {
+    Solicit,
+    Advertise,
+    Request,
+    Confirm,
+    Renew,
+    Rebind,
+    Reply,
+    Release,
+    Decline,
+    Reconfigure,
+    InformationRequest,
+    RelayForward,
+    RelayReply,
+    Unknown(u8),
+}
+
+impl From<u8> for MessageType {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => MessageType::Solicit,
+            2 => MessageType::Advertise,
+            3 => MessageType::Request,
+            4 => MessageType::Confirm,
+            5 => MessageType::Renew,
+            6 => MessageType::Rebind,
+            7 => MessageType::Reply,
+            8 => MessageType::Release,
+            9 => MessageType::Decline,
+            10 => MessageType::Reconfigure,
+            11 => MessageType::InformationRequest,
+            12 => MessageType::RelayForward,
+            13 => MessageType::RelayReply,
+            _ => MessageType::Unknown(value),
+        }
+    }
+}
+
+impl From<MessageType> for u8 {
+    fn from(value: MessageType) -> Self {
+        match value {
+            MessageType::Solicit => 1,
+            MessageType::Advertise => 2,
+            MessageType::Request => 3,
+            MessageType::Confirm => 4,
+            MessageType::Renew => 5,
+            MessageType::Rebind => 6,
+            MessageType::Reply => 7,
+            MessageType::Release => 8,
+            MessageType::Decline => 9,
+            MessageType::Reconfigure => 10,
+            MessageType::InformationRequest => 11,
+            MessageType::RelayForward => 12,
+            MessageType::RelayReply => 13,
+            MessageType::Unknown(value) => value,
+        }
+    }
+}
+
+/// Client/server message (RFC 8415 section 8)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dhcp6Message {
+    pub msg_type: MessageType,
+
+    /// 24-bit transaction ID
+    pub transaction_id: u32,
+
+    pub options: Vec<Dhcp6Option>,
+}
+
+impl Dhcp6Message {
+    pub fn new(msg_type: MessageType, transaction_id: u32) -> Self {
+        Self {
+            msg_type,
+            transaction_id: transaction_id & 0xffffff,
+            options: Vec::new(),
+        }
+    }
+
+    pub fn parse(buf: &[u8]) -> Result<Self, Dhcp6Error> {
+        if buf.len() < 4 {
+            return Err(Dhcp6Error::new("DHCPv6 message too short"));
+        }
+        let msg_type = MessageType::from(buf[0]);
+        let transaction_id = u32::from_be_bytes([0, buf[1], buf[2], buf[3]]);
+        let options = Dhcp6Option::parse_list(&buf[4..])?;
+        Ok(Self {
+            msg_type,
+            transaction_id,
+            options,
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.push(self.msg_type.into());
+        buf.extend_from_slice(&self.transaction_id.to_be_bytes()[1..]);
+        Dhcp6Option::serialize_list(&self.options, &mut buf);
+        buf
+    }
+
+    pub fn client_id(&self) -> Option<&Duid> {
+        self.options.iter().find_map(|option| {
+            if let Dhcp6Option::ClientId(duid) = option {
+                Some(duid)
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn server_id(&self) -> Option<&Duid> {
+        self.options.iter().find_map(|option| {
+            if let Dhcp6Option::ServerId(duid) = option {
+                Some(duid)
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn preference(&self) -> u8 {
+        self.options.iter().find_map(|option| {
+            if let Dhcp6Option::Preference(preference) = option {
+                Some(*preference)
+            } else {
+                None
+            }
+        }).unwrap_or(0)
+    }
+
+    pub fn status_code(&self) -> Option<&StatusCode> {
+        self.options.iter().find_map(|option| {
+            if let Dhcp6Option::StatusCode(status) = option {
+                Some(status)
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn has_option(&self, code: u16) -> bool {
+        self.options.iter().any(|option| option.code() == code)
+    }
+
+    pub fn ia_na(&self, iaid: u32) -> Option<&IaNa> {
+        self.options.iter().find_map(|option| {
+            match option {
+                Dhcp6Option::IaNa(ia_na) if ia_na.iaid == iaid => Some(ia_na),
+                _ => None,
+            }
+        })
+    }
+
+    pub fn dns_servers(&self) -> Vec<std::net::Ipv6Addr> {
+        self.options.iter().find_map(|option| {
+            if let Dhcp6Option::DnsServers(servers) = option {
+                Some(servers.clone())
+            } else {
+                None
+            }
+        }).unwrap_or_default()
+    }
+
+    pub fn domain_list(&self) -> Vec<String> {
+        self.options.iter().find_map(|option| {
+            if let Dhcp6Option::DomainList(domains) = option {
+                Some(domains.clone())
+            } else {
+                None
+            }
+        }).unwrap_or_default()
+    }
+
+    pub fn requested_options(&self) -> Vec<u16> {
+        self.options.iter().find_map(|option| {
+            if let Dhcp6Option::OptionRequest(codes) = option {
+                Some(codes.clone())
+            } else {
+                None
+            }
+        }).unwrap_or_default()
+    }
+
+    pub fn information_refresh_time(&self) -> Option<u32> {
+        self.options.iter().find_map(|option| {
+            if let Dhcp6Option::InformationRefreshTime(time) = option {
+                Some(*time)
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn ia_pd(&self, iaid: u32) -> Option<&IaPd> {
+        self.options.iter().find_map(|option| {
+            match option {
+                Dhcp6Option::IaPd(ia_pd) if ia_pd.iaid == iaid => Some(ia_pd),
+                _ => None,
+            }
+        })
+    }
+}
+
+/// Relay agent/server message (RFC 8415 section 9)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dhcp6RelayMessage {
+    /// Relay-Forward or Relay-Reply
+    pub msg_type: MessageType,
+
+    pub hop_count: u8,
+    pub link_address: std::net::Ipv6Addr,
+    pub peer_address: std::net::Ipv6Addr,
+    pub options: Vec<Dhcp6Option>,
+}
+
+impl Dhcp6RelayMessage {
+    pub fn parse(buf: &[u8]) -> Result<Self, Dhcp6Error> {
+        if buf.len() < 34 {
+            return Err(Dhcp6Error::new("DHCPv6 relay message too short"));
+        }
+        let msg_type = MessageType::from(buf[0]);
+        if msg_type != MessageType::RelayForward && msg_type != MessageType::RelayReply {
+            return Err(Dhcp6Error::new("not a DHCPv6 relay message"));
+        }
+        Ok(Self {
+            msg_type,
+            hop_count: buf[1],
+            link_address: read_addr(buf, 2)?,
+            peer_address: read_addr(buf, 18)?,
+            options: Dhcp6Option::parse_list(&buf[34..])?,
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.push(self.msg_type.into());
+        buf.push(self.hop_count);
+        buf.extend_from_slice(&self.link_address.octets());
+        buf.extend_from_slice(&self.peer_address.octets());
+        Dhcp6Option::serialize_list(&self.options, &mut buf);
+        buf
+    }
+
+    pub fn relay_message(&self) -> Option<&[u8]> {
+        self.options.iter().find_map(|option| {
+            if let Dhcp6Option::RelayMessage(message) = option {
+                Some(message.as_slice())
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn interface_id(&self) -> Option<&[u8]> {
+        self.options.iter().find_map(|option| {
+            if let Dhcp6Option::InterfaceId(id) = option {
+                Some(id.as_slice())
+            } else {
+                None
+            }
+        })
+    }
+}
@@ -0,0 +1,586 @@
+use super::Dhcp6Error;
+
+use std::net::Ipv6Addr;
+
+pub const OPTION_CLIENTID: u16 = 1;
+pub const OPTION_SERVERID: u16 = 2;
+pub const OPTION_IA_NA: u16 = 3;
+pub const OPTION_IAADDR: u16 = 5;
+pub const OPTION_ORO: u16 = 6;
+pub const OPTION_PREFERENCE: u16 = 7;
+pub const OPTION_ELAPSED_TIME: u16 = 8;
+pub const OPTION_RELAY_MSG: u16 = 9;
+pub const OPTION_STATUS_CODE: u16 = 13;
+pub const OPTION_RAPID_COMMIT: u16 = 14;
+pub const OPTION_INTERFACE_ID: u16 = 18;
+pub const OPTION_DNS_SERVERS: u16 = 23;
+pub const OPTION_DOMAIN_LIST: u16 = 24;
+pub const OPTION_INFORMATION_REFRESH_TIME: u16 = 32;
+pub const OPTION_IA_PD: u16 = 25;
+pub const OPTION_IAPREFIX: u16 = 26;
+
+pub(crate) fn read_u16(buf: &[u8], offset: usize) -> Result<u16, Dhcp6Error> {
+    if offset + 2 > buf.len() {
+        return Err(Dhcp6Error::new("DHCPv6 field out of bounds"));
+    }
+    Ok(u16::from_be_bytes([buf[offset], buf[offset + 1]]))
+}
+
+pub(crate) fn read_u32(buf: &[u8], offset: usize) -> Result<u32, Dhcp6Error> {
+    if offset + 4 > buf.len() {
+        return Err(Dhcp6Error::new("DHCPv6 field out of bounds"));
+    }
+    Ok(u32::from_be_bytes([buf[offset], buf[offset + 1], buf[offset + 2], buf[offset + 3]]))
+}
+
+pub(crate) fn read_addr(buf: &[u8], offset: usize) -> Result<Ipv6Addr, Dhcp6Error> {
+    if offset + 16 > buf.len() {
+        return Err(Dhcp6Error::new("DHCPv6 address out of bounds"));
+    }
+    let mut octets = [0u8; 16];
+    octets.copy_from_slice(&buf[offset..(offset + 16)]);
+    Ok(Ipv6Addr::from(octets))
+}
+
+/// Decodes a list of uncompressed domain names (RFC 1035 section 3.1)
+pub(crate) fn read_domain_list(buf: &[u8]) -> Result<Vec<String>, Dhcp6Error> {
+    let mut domains = Vec::new();
+    let mut labels: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < buf.len() {
+        let len = buf[i] as usize;
+        i += 1;
+        if len == 0 {
+            if !labels.is_empty() {
+                domains.push(labels.join("."));
+                labels.clear();
+            }
+            continue;
+        }
+        if len > 63 {
+            return Err(Dhcp6Error::new("domain name label too long"));
+        }
+        if i + len > buf.len() {
+            return Err(Dhcp6Error::new("domain name label out of bounds"));
+        }
+        labels.push(String::from_utf8_lossy(&buf[i..(i + len)]).into_owned());
+        i += len;
+    }
+    if !labels.is_empty() {
+        return Err(Dhcp6Error::new("domain name not terminated"));
+    }
+    Ok(domains)
+}
+
+pub(crate) fn write_domain_list(domains: &[String], buf: &mut Vec<u8>) {
+    for domain in domains {
+        for label in domain.split('.').filter(|label| !label.is_empty()) {
+            let label = &label.as_bytes()[..label.len().min(63)];
+            buf.push(label.len() as u8);
+            buf.extend_from_slice(label);
+        }
+        buf.push(0);
+    }
+}
+
+/// DHCP Unique Identifier, kept opaque on the wire.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Duid(pub Vec<u8>);
+
+impl Duid {
+    /// DUID-LLT (type 1) from an Ethernet hardware address and a UNIX time
+    pub fn new_llt(link_layer_address: &[u8], unix_time: u64) -> Self {
+        // DUID time is counted from 2000-01-01 00:00:00 UTC
+        let time = (unix_time.saturating_sub(946684800) & 0xffffffff) as u32;
+        let mut data = vec![0x00, 0x01, 0x00, 0x01];
+        data.extend_from_slice(&time.to_be_bytes());
+        data.extend_from_slice(link_layer_address);
+        Self(data)
+    }
+
+    /// DUID-LL (type 3) from an Ethernet hardware address
+    pub fn new_ll(link_layer_address: &[u8]) -> Self {
+        let mut data = vec![0x00, 0x03, 0x00, 0x01];
+        data.extend_from_slice(link_layer_address);
+        Self(data)
+    }
+
+    /// DUID-UUID (type 4, RFC 6355)
+    pub fn new_uuid(uuid: [u8; 16]) -> Self {
+        let mut data = vec![0x00, 0x04];
+        data.extend_from_slice(&uuid);
+        Self(data)
+    }
+
+    /// DUID-UUID with a random (version 4) UUID
+    pub fn new_random_uuid() -> Self {
+        let mut uuid: [u8; 16] = rand::random();
+        uuid[6] = (uuid[6] & 0x0f) | 0x40;
+        uuid[8] = (uuid[8] & 0x3f) | 0x80;
+        Self::new_uuid(uuid)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for Duid {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let value = self.0.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":");
+        write!(f, "{}", value)
+    }
+}
+
+impl std::fmt::Debug for Duid {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "DUID {}", self)
+    }
+}
+
+impl std::str::FromStr for Duid {
+    type Err = Dhcp6Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let data = s.split(':')
+            .map(|b| u8::from_str_radix(b, 16).map_err(|_| Dhcp6Error::new("invalid DUID string")))
+            .collect::<Result<Vec<_>, _>>()?;
+        if data.len() < 2 {
+            return Err(Dhcp6Error::new("DUID too short"));
+        }
+        Ok(Self(data))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusCodeValue {
+    Success,
+    UnspecFail,
+    NoAddrsAvail,
+    NoBinding,
+    NotOnLink,
+    UseMulticast,
+    NoPrefixAvail,
+    Unknown(u16),
+}
+
+impl From<u16> for StatusCodeValue {
+    fn from(value: u16) -> Self {
+        match value {
+            0 => StatusCodeValue::Success,
+            1 => StatusCodeValue::UnspecFail,
+            2 => StatusCodeValue::NoAddrsAvail,
+            3 => StatusCodeValue::NoBinding,
+            4 => StatusCodeValue::NotOnLink,
+            5 => StatusCodeValue::UseMulticast,
+            6 => StatusCodeValue::NoPrefixAvail,
+            _ => StatusCodeValue::Unknown(value),
+        }
+    }
+}
+
+impl From<StatusCodeValue> for u16 {
+    fn from(value: StatusCodeValue) -> Self {
+        match value {
+            StatusCodeValue::Success => 0,
+            StatusCodeValue::UnspecFail => 1,
+            StatusCodeValue::NoAddrsAvail => 2,
+            StatusCodeValue::NoBinding => 3,
+            StatusCodeValue::NotOnLink => 4,
+            StatusCodeValue::UseMulticast => 5,
+            StatusCodeValue::NoPrefixAvail => 6,
+            StatusCodeValue::Unknown(value) => value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusCode {
+    pub code: StatusCodeValue,
+    pub message: String,
+}
+
+fn find_status_code(options: &[Dhcp6Option]) -> Option<StatusCode> {
+    options.iter().find_map(|option| {
+        if let Dhcp6Option::StatusCode(status) = option {
+            Some(status.clone())
+        } else {
+            None
+        }
+    })
+}
+
+/// IA_NA option (RFC 8415 section 21.4)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IaNa {
+    pub iaid: u32,
+    pub t1: u32,
+    pub t2: u32,
+    pub options: Vec<Dhcp6Option>,
+}
+
+impl IaNa {
+    pub fn addresses(&self) -> Vec<IaAddr> {
+        self.options.iter().filter_map(|option| {
+            if let Dhcp6Option::IaAddr(addr) = option {
+                Some(addr.clone())
+            } else {
+                None
+            }
+        }).collect()
+    }
+
+    pub fn status_code(&self) -> Option<StatusCode> {
+        find_status_code(&self.options)
+    }
+}
+
+/// IA Address option (RFC 8415 section 21.6)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IaAddr {
+    pub addr: Ipv6Addr,
+    pub preferred_lifetime: u32,
+    pub valid_lifetime: u32,
+    pub options: Vec<Dhcp6Option>,
+}
+
+/// IA_PD option (RFC 8415 section 21.21)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IaPd {
+    pub iaid: u32,
+    pub t1: u32,
+    pub t2: u32,
+    pub options: Vec<Dhcp6Option>,
+}
+
+impl IaPd {
+    pub fn prefixes(&self) -> Vec<IaPrefix> {
+        self.options.iter().filter_map(|option| {
+            if let Dhcp6Option::IaPrefix(prefix) = option {
+                Some(prefix.clone())
+            } else {
+                None
+            }
+        }).collect()
+    }
+
+    pub fn status_code(&self) -> Option<StatusCode> {
+        find_status_code(&self.options)
+    }
+}
+
+/// IA Prefix option (RFC 8415 section 21.22)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IaPrefix {
+    pub preferred_lifetime: u32,
+    pub valid_lifetime: u32,
+    pub prefix_len: u8,
+    pub prefix: Ipv6Addr,
+    pub options: Vec<Dhcp6Option>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Dhcp6Option {
+    /// option 1
+    ClientId(Duid),
+
+    /// option 2
+    ServerId(Duid),
+
+    /// option 3
+    IaNa(IaNa),
+
+    /// option 5
+    IaAddr(IaAddr),
+
+    /// option 6
+    OptionRequest(Vec<u16>),
+
+    /// option 7
+    Preference(u8),
+
+    /// option 8
+    /// hundredths of a second
+    ElapsedTime(u16),
+
+    /// option 9
+    /// encapsulated client or relay message
+    RelayMessage(Vec<u8>),
+
+    /// option 13
+    StatusCode(StatusCode),
+
+    /// option 14
+    RapidCommit,
+
+    /// option 18
+    InterfaceId(Vec<u8>),
+
+    /// option 23
+    DnsServers(Vec<Ipv6Addr>),
+
+    /// option 24
+    DomainList(Vec<String>),
+
+    /// option 32
+    /// seconds
+    InformationRefreshTime(u32),
+
+    /// option 25
+    IaPd(IaPd),
+
+    /// option 26
+    IaPrefix(IaPrefix),
+
+    /// code, data
+    Unknown(u16, Vec<u8>),
+}
+
+impl Dhcp6Option {
+    pub fn code(&self) -> u16 {
+        match self {
+            Dhcp6Option::ClientId(_) => OPTION_CLIENTID,
+            Dhcp6Option::ServerId(_) => OPTION_SERVERID,
+            Dhcp6Option::IaNa(_) => OPTION_IA_NA,
+            Dhcp6Option::IaAddr(_) => OPTION_IAADDR,
+            Dhcp6Option::OptionRequest(_) => OPTION_ORO,
+            Dhcp6Option::Preference(_) => OPTION_PREFERENCE,
+            Dhcp6Option::ElapsedTime(_) => OPTION_ELAPSED_TIME,
+            Dhcp6Option::RelayMessage(_) => OPTION_RELAY_MSG,
+            Dhcp6Option::StatusCode(_) => OPTION_STATUS_CODE,
+            Dhcp6Option::RapidCommit => OPTION_RAPID_COMMIT,
+            Dhcp6Option::InterfaceId(_) => OPTION_INTERFACE_ID,
+            Dhcp6Option::DnsServers(_) => OPTION_DNS_SERVERS,
+            Dhcp6Option::DomainList(_) => OPTION_DOMAIN_LIST,
+            Dhcp6Option::InformationRefreshTime(_) => OPTION_INFORMATION_REFRESH_TIME,
+            Dhcp6Option::IaPd(_) => OPTION_IA_PD,
+            Dhcp6Option::IaPrefix(_) => OPTION_IAPREFIX,
+            Dhcp6Option::Unknown(code, _) => *code,
+        }
+    }
+
+    pub fn parse_list(buf: &[u8]) -> Result<Vec<Dhcp6Option>, Dhcp6Error> {
+        let mut options = Vec::new();
+        let mut i = 0;
+        while i < buf.len() {
+            let code = read_u16(buf, i)?;
+            let len = read_u16(buf, i + 2)? as usize;
+            let start = i + 4;
+            let end = start + len;
+            if end > buf.len() {
+                return Err(Dhcp6Error::new("DHCPv6 option too long"));
+            }
+            options.push(Self::parse(code, &buf[start..end])?);
+            i = end;
+        }
+        Ok(options)
+    }
+
+    fn parse(code: u16, data: &[u8]) -> Result<Dhcp6Option, Dhcp6Error> {
+        match code {
+            OPTION_CLIENTID => Ok(Dhcp6Option::ClientId(Duid(data.to_vec()))),
+
+            OPTION_SERVERID => Ok(Dhcp6Option::ServerId(Duid(data.to_vec()))),
+
+            OPTION_IA_NA => {
+                if data.len() < 12 {
+                    return Err(Dhcp6Error::new("DHCPv6 IA_NA option too short"));
+                }
+                Ok(Dhcp6Option::IaNa(IaNa {
+                    iaid: read_u32(data, 0)?,
+                    t1: read_u32(data, 4)?,
+                    t2: read_u32(data, 8)?,
+                    options: Self::parse_list(&data[12..])?,
+                }))
+            }
+
+            OPTION_IAADDR => {
+                if data.len() < 24 {
+                    return Err(Dhcp6Error::new("DHCPv6 IA Address option too short"));
+                }
+                Ok(Dhcp6Option::IaAddr(IaAddr {
+                    addr: read_addr(data, 0)?,
+                    preferred_lifetime: read_u32(data, 16)?,
+                    valid_lifetime: read_u32(data, 20)?,
+                    options: Self::parse_list(&data[24..])?,
+                }))
+            }
+
+            OPTION_ORO => {
+                if !data.len().is_multiple_of(2) {
+                    return Err(Dhcp6Error::new("DHCPv6 ORO option bad length"));
+                }
+                let codes = data.chunks(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+                Ok(Dhcp6Option::OptionRequest(codes))
+            }
+
+            OPTION_PREFERENCE => {
+                if data.len() != 1 {
+                    return Err(Dhcp6Error::new("DHCPv6 preference option bad length"));
+                }
+                Ok(Dhcp6Option::Preference(data[0]))
+            }
+
+            OPTION_ELAPSED_TIME => {
+                if data.len() != 2 {
+                    return Err(Dhcp6Error::new("DHCPv6 elapsed time option bad length"));
+                }
+                Ok(Dhcp6Option::ElapsedTime(read_u16(data, 0)?))
+            }
+
+            OPTION_RELAY_MSG => Ok(Dhcp6Option::RelayMessage(data.to_vec())),
+
+            OPTION_STATUS_CODE => {
+                let code = read_u16(data, 0)?;
+                let message = String::from_utf8_lossy(&data[2..]).into_owned();
+                Ok(Dhcp6Option::StatusCode(StatusCode {
+                    code: code.into(),
+                    message,
+                }))
+            }
+
+            OPTION_RAPID_COMMIT => Ok(Dhcp6Option::RapidCommit),
+
+            OPTION_INTERFACE_ID => Ok(Dhcp6Option::InterfaceId(data.to_vec())),
+
+            OPTION_DNS_SERVERS => {
+                if !data.len().is_multiple_of(16) {
+                    return Err(Dhcp6Error::new("DHCPv6 DNS servers option bad length"));
+                }
+                let mut servers = Vec::new();
+                for i in (0..data.len()).step_by(16) {
+                    servers.push(read_addr(data, i)?);
+                }
+                Ok(Dhcp6Option::DnsServers(servers))
+            }
+
+            OPTION_DOMAIN_LIST => Ok(Dhcp6Option::DomainList(read_domain_list(data)?)),
+
+            OPTION_INFORMATION_REFRESH_TIME => {
+                if data.len() != 4 {
+                    return Err(Dhcp6Error::new("DHCPv6 information refresh time option bad length"));
+                }
+                Ok(Dhcp6Option::InformationRefreshTime(read_u32(data, 0)?))
+            }
+
+            OPTION_IA_PD => {
+                if data.len() < 12 {
+                    return Err(Dhcp6Error::new("DHCPv6 IA_PD option too short"));
+                }
+                Ok(Dhcp6Option::IaPd(IaPd {
+                    iaid: read_u32(data, 0)?,
+                    t1: read_u32(data, 4)?,
+                    t2: read_u32(data, 8)?,
+                    options: Self::parse_list(&data[12..])?,
+                }))
+            }
+
+            OPTION_IAPREFIX => {
+                if data.len() < 25 {
+                    return Err(Dhcp6Error::new("DHCPv6 IA Prefix option too short"));
+                }
+                Ok(Dhcp6Option::IaPrefix(IaPrefix {
+                    preferred_lifetime: read_u32(data, 0)?,
+                    valid_lifetime: read_u32(data, 4)?,
+                    prefix_len: data[8],
+                    prefix: read_addr(data, 9)?,
+                    options: Self::parse_list(&data[25..])?,
+                }))
+            }
+
+            _ => Ok(Dhcp6Option::Unknown(code, data.to_vec())),
+        }
+    }
+
+    pub fn serialize_list(options: &[Dhcp6Option], buf: &mut Vec<u8>) {
+        for option in options {
+            option.serialize(buf);
+        }
+    }
+
+    pub fn serialize(&self, buf: &mut Vec<u8>) {
+        let mut data = Vec::new();
+        match self {
+            Dhcp6Option::ClientId(duid) | Dhcp6Option::ServerId(duid) => {
+                data.extend_from_slice(duid.as_bytes());
+            }
+
+            Dhcp6Option::RelayMessage(value) | Dhcp6Option::InterfaceId(value) => {
+                data.extend_from_slice(value);
+            }
+
+            Dhcp6Option::OptionRequest(codes) => {
+                for code in codes {
+                    data.extend_from_slice(&code.to_be_bytes());
+                }
+            }
+
+            Dhcp6Option::Preference(preference) => {
+                data.push(*preference);
+            }
+
+            Dhcp6Option::ElapsedTime(elapsed) => {
+                data.extend_from_slice(&elapsed.to_be_bytes());
+            }
+
+            Dhcp6Option::StatusCode(status) => {
+                data.extend_from_slice(&u16::from(status.code).to_be_bytes());
+                data.extend_from_slice(status.message.as_bytes());
+            }
+
+            Dhcp6Option::RapidCommit => {}
+
+            Dhcp6Option::DnsServers(servers) => {
+                for server in servers {
+                    data.extend_from_slice(&server.octets());
+                }
+            }
+
+            Dhcp6Option::DomainList(domains) => {
+                write_domain_list(domains, &mut data);
+            }
+
+            Dhcp6Option::InformationRefreshTime(time) => {
+                data.extend_from_slice(&time.to_be_bytes());
+            }
+
+            Dhcp6Option::IaNa(ia_na) => {
+                data.extend_from_slice(&ia_na.iaid.to_be_bytes());
+                data.extend_from_slice(&ia_na.t1.to_be_bytes());
+                data.extend_from_slice(&ia_na.t2.to_be_bytes());
+                Self::serialize_list(&ia_na.options, &mut data);
+            }
+
+            Dhcp6Option::IaAddr(addr) => {
+                data.extend_from_slice(&addr.addr.octets());
+                data.extend_from_slice(&addr.preferred_lifetime.to_be_bytes());
+                data.extend_from_slice(&addr.valid_lifetime.to_be_bytes());
+                Self::serialize_list(&addr.options, &mut data);
+            }
+
+            Dhcp6Option::IaPd(ia_pd) => {
+                data.extend_from_slice(&ia_pd.iaid.to_be_bytes());
+                data.extend_from_slice(&ia_pd.t1.to_be_bytes());
+                data.extend_from_slice(&ia_pd.t2.to_be_bytes());
+                Self::serialize_list(&ia_pd.options, &mut data);
+            }
+
+            Dhcp6Option::IaPrefix(prefix) => {
+                data.extend_from_slice(&prefix.preferred_lifetime.to_be_bytes());
+                data.extend_from_slice(&prefix.valid_lifetime.to_be_bytes());
+                data.push(prefix.prefix_len);
+                data.extend_from_slice(&prefix.prefix.octets());
+                Self::serialize_list(&prefix.options, &mut data);
+            }
+
+            Dhcp6Option::Unknown(_, value) => {
+                data.extend_from_slice(value);
+            }
+        }
+
+        buf.extend_from_slice(&self.code().to_be_bytes());
+        buf.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        buf.extend_from_slice(&data);
+    }
+}
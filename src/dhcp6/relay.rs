@@ -0,0 +1,258 @@
+use super::*;
+use crate::interface::InterfaceId;
+use crate::rtnl::addr::{AddressManager, V6AddressRequestScope};
+use crate::rtnl::route::RouteManager;
+
+use parking_lot::Mutex;
+
+use std::collections::HashMap;
+use std::net::Ipv6Addr;
+use std::time::Duration;
+use std::time::Instant;
+
+/// HOP_COUNT_LIMIT (RFC 8415 section 7.6)
+pub const HOP_COUNT_LIMIT: u8 = 8;
+
+/// Route installed for a prefix delegated to a downstream router
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DelegatedRoute {
+    if_index: InterfaceId,
+    gateway: Ipv6Addr,
+    expires: Instant,
+}
+
+/// DHCPv6 relay agent (RFC 8415 section 19) between downstream links and the upstream
+pub struct Dhcp6Relay {
+    upstream: Dhcp6Socket,
+    downstreams: HashMap<InterfaceId, Dhcp6Socket>,
+    server_address: Ipv6Addr,
+    rtnl_addr: AddressManager,
+    rtnl_route: RouteManager,
+    routes: Mutex<HashMap<(Ipv6Addr, u8), DelegatedRoute>>,
+}
+
+impl Dhcp6Relay {
+    pub fn new(upstream: InterfaceId, downstreams: &[InterfaceId], server_address: Ipv6Addr, rtnl: &crate::rtnl::RtnetlinkConnection) -> Result<Self, std::io::Error> {
+        let upstream = Dhcp6Socket::new(upstream, SERVER_PORT)?;
+
+        let mut sockets = HashMap::new();
+        for if_index in downstreams {
+            let socket = Dhcp6Socket::new(*if_index, SERVER_PORT)?;
+            socket.join_multicast(ALL_DHCP_RELAY_AGENTS_AND_SERVERS)?;
+            sockets.insert(*if_index, socket);
+        }
+
+        Ok(Self {
+            upstream,
+            downstreams: sockets,
+            server_address,
+            rtnl_addr: rtnl.address(),
+            rtnl_route: rtnl.route(),
+            routes: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn interface_id(if_index: InterfaceId) -> Vec<u8> {
+        if_index.inner_unchecked().to_be_bytes().to_vec()
+    }
+
+    fn downstream_by_interface_id(&self, id: &[u8]) -> Option<&Dhcp6Socket> {
+        let id: [u8; 4] = id.try_into().ok()?;
+        self.downstreams.get(&InterfaceId::new(u32::from_be_bytes(id)))
+    }
+
+    /// Wraps a message from a downstream client or relay into Relay-Forward (RFC 8415 section 19.1)
+    async fn forward(&self, socket: &Dhcp6Socket, buf: &[u8], src: Ipv6Addr) -> Result<(), std::io::Error> {
+        let hop_count = match MessageType::from(buf[0]) {
+            MessageType::RelayForward => {
+                let inner = Dhcp6RelayMessage::parse(buf)?;
+                if inner.hop_count >= HOP_COUNT_LIMIT {
+                    log::debug!("Dropping DHCPv6 Relay-Forward over hop count limit from {}", src);
+                    return Ok(());
+                }
+                inner.hop_count + 1
+            }
+
+            MessageType::Solicit | MessageType::Request | MessageType::Confirm | MessageType::Renew | MessageType::Rebind | MessageType::Release | MessageType::Decline | MessageType::InformationRequest => {
+                let message = Dhcp6Message::parse(buf)?;
+                if message.msg_type == MessageType::Release || message.msg_type == MessageType::Decline {
+                    self.release_routes(&message).await;
+                }
+                0
+            }
+
+            msg_type => {
+                log::debug!("Ignoring DHCPv6 {:?} from downstream {}", msg_type, src);
+                return Ok(());
+            }
+        };
+
+        // a global address identifies the link; the Interface-ID covers links without one
+        let link_address = if hop_count == 0 {
+            self.rtnl_addr.get_v6(socket.if_index(), V6AddressRequestScope::Global).await?
+                .first().copied().unwrap_or(Ipv6Addr::UNSPECIFIED)
+        } else {
+            Ipv6Addr::UNSPECIFIED
+        };
+
+        let relay_forward = Dhcp6RelayMessage {
+            msg_type: MessageType::RelayForward,
+            hop_count,
+            link_address,
+            peer_address: src,
+            options: vec![
+                Dhcp6Option::RelayMessage(buf.to_vec()),
+                Dhcp6Option::InterfaceId(Self::interface_id(socket.if_index())),
+            ],
+        };
+
+        self.upstream.send_to(&relay_forward.serialize(), self.server_address, SERVER_PORT).await
+    }
+
+    /// Unwraps Relay-Reply from the server towards the downstream peer (RFC 8415 section 19.2)
+    async fn reply(&self, buf: &[u8]) -> Result<(), std::io::Error> {
+        let relay_reply = Dhcp6RelayMessage::parse(buf)?;
+        if relay_reply.msg_type != MessageType::RelayReply {
+            return Ok(());
+        }
+
+        let socket = if let Some(socket) = relay_reply.interface_id().and_then(|id| self.downstream_by_interface_id(id)) {
+            socket
+        } else {
+            log::debug!("DHCPv6 Relay-Reply without a known Interface-ID");
+            return Ok(());
+        };
+
+        let inner = if let Some(inner) = relay_reply.relay_message() {
+            inner
+        } else {
+            return Err(Dhcp6Error::new("DHCPv6 Relay-Reply without Relay Message option").into());
+        };
+        if inner.is_empty() {
+            return Err(Dhcp6Error::new("DHCPv6 Relay Message option empty").into());
+        }
+
+        let port = if MessageType::from(inner[0]) == MessageType::RelayReply {
+            SERVER_PORT
+        } else {
+            if let Ok(message) = Dhcp6Message::parse(inner) {
+                if message.msg_type == MessageType::Reply {
+                    self.install_routes(socket.if_index(), relay_reply.peer_address, &message).await;
+                }
+            }
+            CLIENT_PORT
+        };
+
+        socket.send_to(inner, relay_reply.peer_address, port).await
+    }
+
+    /// Routes every delegated prefix in a Reply towards the client that requested it
+    async fn install_routes(&self, if_index: InterfaceId, gateway: Ipv6Addr, message: &Dhcp6Message) {
+        for option in &message.options {
+            let ia_pd = if let Dhcp6Option::IaPd(ia_pd) = option {
+                ia_pd
+            } else {
+                continue;
+            };
+
+            for prefix in ia_pd.prefixes() {
+                let key = (prefix.prefix, prefix.prefix_len);
+                if prefix.valid_lifetime == 0 {
+                    self.remove_route(key).await;
+                    continue;
+                }
+
+                let route = DelegatedRoute {
+                    if_index,
+                    gateway,
+                    expires: Instant::now() + Duration::from_secs(prefix.valid_lifetime as u64),
+                };
+                let prev = self.routes.lock().insert(key, route);
+                if let Some(prev) = prev {
+                    if prev.if_index == if_index && prev.gateway == gateway {
+                        continue;
+                    }
+                    let _ = self.rtnl_route.delete_v6(prev.if_index, prefix.prefix, prefix.prefix_len, Some(prev.gateway)).await;
+                }
+
+                log::info!("Routing delegated prefix {}/{} via {}", prefix.prefix, prefix.prefix_len, gateway);
+                if let Err(e) = self.rtnl_route.add_v6(if_index, prefix.prefix, prefix.prefix_len, Some(gateway)).await {
+                    log::error!("Failed to add route for delegated prefix: {:?}", e);
+                }
+            }
+        }
+    }
+
+    async fn release_routes(&self, message: &Dhcp6Message) {
+        for option in &message.options {
+            if let Dhcp6Option::IaPd(ia_pd) = option {
+                for prefix in ia_pd.prefixes() {
+                    self.remove_route((prefix.prefix, prefix.prefix_len)).await;
+                }
+            }
+        }
+    }
+
+    async fn remove_route(&self, key: (Ipv6Addr, u8)) {
+        let route = self.routes.lock().remove(&key);
+        if let Some(route) = route {
+            log::info!("Removing route for delegated prefix {}/{}", key.0, key.1);
+            let _ = self.rtnl_route.delete_v6(route.if_index, key.0, key.1, Some(route.gateway)).await;
+        }
+    }
+
+    async fn run_downstream(&self, socket: &Dhcp6Socket) {
+        let mut buf = vec![0u8; 65536];
+        loop {
+            let (len, src) = match socket.recv_from(&mut buf).await {
+                Ok(res) => res,
+                Err(e) => {
+                    log::error!("Failed to receive DHCPv6 message: {:?}", e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            };
+            if len == 0 {
+                continue;
+            }
+
+            if let Err(e) = self.forward(socket, &buf[..len], *src.ip()).await {
+                log::debug!("Failed to relay DHCPv6 message from {}: {}", src, e);
+            }
+        }
+    }
+
+    async fn run_upstream(&self) {
+        let mut buf = vec![0u8; 65536];
+        loop {
+            let (len, src) = match self.upstream.recv_from(&mut buf).await {
+                Ok(res) => res,
+                Err(e) => {
+                    log::error!("Failed to receive DHCPv6 message: {:?}", e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            };
+
+            if let Err(e) = self.reply(&buf[..len]).await {
+                log::debug!("Failed to relay DHCPv6 message from {}: {}", src, e);
+            }
+        }
+    }
+
+    async fn expire_routes(&self) {
+        loop {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            let now = Instant::now();
+            let expired = self.routes.lock().iter().filter(|(_, route)| route.expires <= now).map(|(key, _)| *key).collect::<Vec<_>>();
+            for key in expired {
+                self.remove_route(key).await;
+            }
+        }
+    }
+
+    pub async fn run(&self) {
+        let downstreams = futures::future::join_all(self.downstreams.values().map(|socket| self.run_downstream(socket)));
+        tokio::join!(downstreams, self.run_upstream(), self.expire_routes());
+    }
+}
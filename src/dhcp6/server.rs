@@ -0,0 +1,104 @@
+use super::*;
+use crate::interface::InterfaceId;
+
+use parking_lot::RwLock;
+
+use std::net::Ipv6Addr;
+use std::sync::Arc;
+
+/// Options handed out by the stateless server
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerOptions {
+    pub dns_servers: Vec<Ipv6Addr>,
+    pub domain_list: Vec<String>,
+}
+
+/// Stateless DHCPv6 server answering Information-Request (RFC 8415 section 18.3.6)
+#[derive(Debug)]
+pub struct StatelessServer {
+    socket: Dhcp6Socket,
+    duid: Duid,
+    options: Arc<RwLock<ServerOptions>>,
+}
+
+impl StatelessServer {
+    pub fn new(if_index: InterfaceId, duid: Duid, options: Arc<RwLock<ServerOptions>>) -> Result<Self, std::io::Error> {
+        let socket = Dhcp6Socket::new(if_index, SERVER_PORT)?;
+        socket.join_multicast(ALL_DHCP_RELAY_AGENTS_AND_SERVERS)?;
+        Ok(Self {
+            socket,
+            duid,
+            options,
+        })
+    }
+
+    pub fn if_index(&self) -> InterfaceId {
+        self.socket.if_index()
+    }
+
+    fn handle(&self, message: &Dhcp6Message) -> Option<Dhcp6Message> {
+        if message.msg_type != MessageType::InformationRequest {
+            log::debug!("Ignoring DHCPv6 {:?} on stateless server", message.msg_type);
+            return None;
+        }
+
+        // RFC 8415 section 16.12
+        if message.has_option(OPTION_IA_NA) || message.has_option(OPTION_IA_PD) {
+            return None;
+        }
+        if let Some(server_id) = message.server_id() {
+            if server_id != &self.duid {
+                return None;
+            }
+        }
+
+        let mut reply = Dhcp6Message::new(MessageType::Reply, message.transaction_id);
+        reply.options.push(Dhcp6Option::ServerId(self.duid.clone()));
+        if let Some(client_id) = message.client_id() {
+            reply.options.push(Dhcp6Option::ClientId(client_id.clone()));
+        }
+
+        let options = self.options.read().clone();
+        let requested = message.requested_options();
+        if requested.contains(&OPTION_DNS_SERVERS) && !options.dns_servers.is_empty() {
+            reply.options.push(Dhcp6Option::DnsServers(options.dns_servers));
+        }
+        if requested.contains(&OPTION_DOMAIN_LIST) && !options.domain_list.is_empty() {
+            reply.options.push(Dhcp6Option::DomainList(options.domain_list));
+        }
+
+        Some(reply)
+    }
+
+    pub async fn run(&self) {
+        let mut buf = vec![0u8; 65536];
+        loop {
+            let (len, src) = match self.socket.recv_from(&mut buf).await {
+                Ok(res) => res,
+                Err(e) => {
+                    log::error!("Failed to receive DHCPv6 message: {:?}", e);
+                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                    continue;
+                }
+            };
+
+            let message = match Dhcp6Message::parse(&buf[..len]) {
+                Ok(message) => message,
+                Err(e) => {
+                    log::debug!("Failed to parse DHCPv6 message from {}: {}", src, e);
+                    continue;
+                }
+            };
+
+            let reply = if let Some(reply) = self.handle(&message) {
+                reply
+            } else {
+                continue;
+            };
+
+            if let Err(e) = self.socket.send_to(&reply.serialize(), *src.ip(), src.port()).await {
+                log::error!("Failed to send DHCPv6 Reply: {:?}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use crate::interface::InterfaceId;
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use std::future::Future;
+use std::net::Ipv6Addr;
+use std::net::SocketAddrV6;
+
+/// Datagram transport a DHCPv6 client exchanges messages over
+pub trait Dhcp6Transport: Send + Sync {
+    fn send_to(&self, buf: &[u8], addr: Ipv6Addr, port: u16) -> impl Future<Output = Result<(), std::io::Error>> + Send;
+
+    fn recv_from(&self, buf: &mut [u8]) -> impl Future<Output = Result<(usize, SocketAddrV6), std::io::Error>> + Send;
+}
+
+/// UDP socket bound to a single interface for DHCPv6 traffic
+#[derive(Debug)]
+pub struct Dhcp6Socket {
+    socket: tokio::net::UdpSocket,
+    if_index: InterfaceId,
+}
+
+impl Dhcp6Socket {
+    pub fn new(if_index: InterfaceId, port: u16) -> Result<Self, std::io::Error> {
+        let if_name = crate::interface::index_to_name(if_index)?;
+
+        let socket = Socket::new(Domain::IPV6, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_only_v6(true)?;
+        socket.set_reuse_address(true)?;
+        socket.bind_device(Some(if_name.as_bytes()))?;
+        socket.set_multicast_if_v6(if_index.inner_unchecked())?;
+        socket.set_multicast_loop_v6(false)?;
+        socket.set_nonblocking(true)?;
+        socket.bind(&SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, port, 0, 0).into())?;
+
+        let socket = tokio::net::UdpSocket::from_std(socket.into())?;
+        Ok(Self { socket, if_index })
+    }
+
+    pub fn if_index(&self) -> InterfaceId {
+        self.if_index
+    }
+
+    pub fn join_multicast(&self, group: Ipv6Addr) -> Result<(), std::io::Error> {
+        self.socket.join_multicast_v6(&group, self.if_index.inner_unchecked())
+    }
+
+    pub async fn send_to(&self, buf: &[u8], addr: Ipv6Addr, port: u16) -> Result<(), std::io::Error> {
+        let scope_id = if addr.is_multicast() || addr.is_unicast_link_local() {
+            self.if_index.inner_unchecked()
+        } else {
+            0
+        };
+        let dst = SocketAddrV6::new(addr, port, 0, scope_id);
+        self.socket.send_to(buf, dst).await?;
+        Ok(())
+    }
+
+    pub async fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddrV6), std::io::Error> {
+        let (len, src) = self.socket.recv_from(buf).await?;
+        match src {
+            std::net::SocketAddr::V6(src) => Ok((len, src)),
+            std::net::SocketAddr::V4(_) => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "unexpected IPv4 peer")),
+        }
+    }
+}
+
+impl Dhcp6Transport for Dhcp6Socket {
+    fn send_to(&self, buf: &[u8], addr: Ipv6Addr, port: u16) -> impl Future<Output = Result<(), std::io::Error>> + Send {
+        Dhcp6Socket::send_to(self, buf, addr, port)
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> impl Future<Output = Result<(usize, SocketAddrV6), std::io::Error>> + Send {
+        Dhcp6Socket::recv_from(self, buf)
+    }
+}
@@ -0,0 +1,175 @@
+use super::*;
+use super::client::Dhcp6Lease;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use std::net::Ipv6Addr;
+use std::path::Path;
+use std::path::PathBuf;
+
+const DUID_FILE: &str = "duid";
+const IAID_FILE: &str = "iaid";
+const LEASE_FILE: &str = "dhcp6-lease.toml";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+struct StoredAddress {
+    addr: Ipv6Addr,
+    preferred_lifetime: u32,
+    valid_lifetime: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+struct StoredPrefix {
+    prefix: Ipv6Addr,
+    prefix_len: u8,
+    preferred_lifetime: u32,
+    valid_lifetime: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+struct StoredLease {
+    server_id: String,
+    iaid: u32,
+    t1: u32,
+    t2: u32,
+    timestamp: u64,
+
+    #[serde(default)]
+    addresses: Vec<StoredAddress>,
+
+    #[serde(default)]
+    prefixes: Vec<StoredPrefix>,
+
+    #[serde(default)]
+    dns_servers: Vec<Ipv6Addr>,
+
+    #[serde(default)]
+    domain_list: Vec<String>,
+}
+
+impl From<&Dhcp6Lease> for StoredLease {
+    fn from(lease: &Dhcp6Lease) -> Self {
+        Self {
+            server_id: lease.server_id.to_string(),
+            iaid: lease.iaid,
+            t1: lease.t1,
+            t2: lease.t2,
+            timestamp: lease.timestamp,
+            addresses: lease.addresses.iter().map(|addr| StoredAddress {
+                addr: addr.addr,
+                preferred_lifetime: addr.preferred_lifetime,
+                valid_lifetime: addr.valid_lifetime,
+            }).collect(),
+            prefixes: lease.prefixes.iter().map(|prefix| StoredPrefix {
+                prefix: prefix.prefix,
+                prefix_len: prefix.prefix_len,
+                preferred_lifetime: prefix.preferred_lifetime,
+                valid_lifetime: prefix.valid_lifetime,
+            }).collect(),
+            dns_servers: lease.dns_servers.clone(),
+            domain_list: lease.domain_list.clone(),
+        }
+    }
+}
+
+impl TryFrom<StoredLease> for Dhcp6Lease {
+    type Error = Dhcp6Error;
+
+    fn try_from(lease: StoredLease) -> Result<Self, Self::Error> {
+        Ok(Self {
+            server_id: lease.server_id.parse()?,
+            iaid: lease.iaid,
+            t1: lease.t1,
+            t2: lease.t2,
+            addresses: lease.addresses.into_iter().map(|addr| IaAddr {
+                addr: addr.addr,
+                preferred_lifetime: addr.preferred_lifetime,
+                valid_lifetime: addr.valid_lifetime,
+                options: Vec::new(),
+            }).collect(),
+            prefixes: lease.prefixes.into_iter().map(|prefix| IaPrefix {
+                preferred_lifetime: prefix.preferred_lifetime,
+                valid_lifetime: prefix.valid_lifetime,
+                prefix_len: prefix.prefix_len,
+                prefix: prefix.prefix,
+                options: Vec::new(),
+            }).collect(),
+            dns_servers: lease.dns_servers,
+            domain_list: lease.domain_list,
+            timestamp: lease.timestamp,
+        })
+    }
+}
+
+/// DUID, IAID and last lease kept in a state directory across restarts
+#[derive(Debug, Clone)]
+pub struct LeaseStore {
+    dir: PathBuf,
+}
+
+impl LeaseStore {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    fn write_atomic(&self, name: &str, content: &str) -> Result<(), std::io::Error> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(name);
+        let tmp_path = self.dir.join(format!(".{}.tmp", name));
+        std::fs::write(&tmp_path, content)?;
+        std::fs::rename(&tmp_path, &path)
+    }
+
+    pub fn load_duid(&self) -> Result<Option<Duid>, std::io::Error> {
+        let content = match std::fs::read_to_string(self.dir.join(DUID_FILE)) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let duid = content.trim().parse::<Duid>()?;
+        Ok(Some(duid))
+    }
+
+    pub fn save_duid(&self, duid: &Duid) -> Result<(), std::io::Error> {
+        self.write_atomic(DUID_FILE, &format!("{}\n", duid))
+    }
+
+    pub fn load_iaid(&self) -> Result<Option<u32>, std::io::Error> {
+        let content = match std::fs::read_to_string(self.dir.join(IAID_FILE)) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let iaid = content.trim().parse::<u32>().map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        Ok(Some(iaid))
+    }
+
+    pub fn save_iaid(&self, iaid: u32) -> Result<(), std::io::Error> {
+        self.write_atomic(IAID_FILE, &format!("{}\n", iaid))
+    }
+
+    pub fn load_lease(&self) -> Result<Option<Dhcp6Lease>, std::io::Error> {
+        let content = match std::fs::read_to_string(self.dir.join(LEASE_FILE)) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let lease: StoredLease = toml::from_str(&content).map_err(std::io::Error::other)?;
+        Ok(Some(lease.try_into()?))
+    }
+
+    pub fn save_lease(&self, lease: &Dhcp6Lease) -> Result<(), std::io::Error> {
+        let content = toml::to_string(&StoredLease::from(lease)).map_err(std::io::Error::other)?;
+        self.write_atomic(LEASE_FILE, &content)
+    }
+
+    pub fn clear_lease(&self) -> Result<(), std::io::Error> {
+        match std::fs::remove_file(self.dir.join(LEASE_FILE)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
@@ -1,31 +1,209 @@
 
-use crate::config::InterfaceConfig;
+use crate::config::{InterfaceConfig, MldAclConfig, MldQuerierConfig};
 use crate::interface::InterfaceId;
 use crate::icmp6::AsyncIcmp6Socket;
+use crate::icmp6::mld::*;
+use crate::icmp6::mroute::{MifCounters, MulticastRouter, SgCounters};
 use crate::icmp6::socket;
 
+use rand::Rng;
+use tokio::time::Instant;
+
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::net::Ipv6Addr;
+use std::time::Duration;
+
+/// Unsolicited Report Interval (RFC 3810 section 9.11)
+pub const UNSOLICITED_REPORT_INTERVAL: Duration = Duration::from_secs(1);
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterMode {
+    Include,
+    Exclude,
+}
+
+/// Listener state for a group on one downstream interface (RFC 3810 section 7.2)
 #[derive(Debug, Clone)]
 pub struct MldSubscription {
-    pub timestamp: u64,
     pub group_addr: Ipv6Addr,
+    pub filter_mode: FilterMode,
+
+    /// only running in EXCLUDE mode
+    pub group_timer: Instant,
+
+    /// source timers; `None` for sources excluded in EXCLUDE mode
+    pub sources: HashMap<Ipv6Addr, Option<Instant>>,
+
+    /// Older Version Host Present timer, set while MLDv1 listeners are around (RFC 3810 section 8.3.2)
+    pub older_host_present: Option<Instant>,
+}
+
+impl MldSubscription {
+    fn new(group_addr: Ipv6Addr, now: Instant) -> Self {
+        Self {
+            group_addr,
+            filter_mode: FilterMode::Include,
+            group_timer: now,
+            sources: HashMap::new(),
+            older_host_present: None,
+        }
+    }
+
+    /// Sources with a running timer: the include list, or the requested list in EXCLUDE mode
+    pub fn requested_sources(&self) -> HashSet<Ipv6Addr> {
+        self.sources.iter().filter(|(_, timer)| timer.is_some()).map(|(addr, _)| *addr).collect()
+    }
+
+    /// Sources whose traffic is not wanted in EXCLUDE mode
+    pub fn excluded_sources(&self) -> HashSet<Ipv6Addr> {
+        self.sources.iter().filter(|(_, timer)| timer.is_none()).map(|(addr, _)| *addr).collect()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.filter_mode == FilterMode::Include && self.sources.is_empty()
+    }
+
+    /// Whether traffic from a source has to be forwarded to this interface
+    pub fn forwards(&self, source_addr: &Ipv6Addr) -> bool {
+        match self.filter_mode {
+            FilterMode::Include => matches!(self.sources.get(source_addr), Some(Some(_))),
+            FilterMode::Exclude => !matches!(self.sources.get(source_addr), Some(None)),
+        }
+    }
+
+    fn refresh(&mut self, source_addrs: &HashSet<Ipv6Addr>, until: Instant) {
+        for source_addr in source_addrs {
+            self.sources.insert(*source_addr, Some(until));
+        }
+    }
+
+    /// Applies expired timers (RFC 3810 section 7.5); returns whether the state changed
+    fn expire(&mut self, now: Instant) -> bool {
+        let mut changed = false;
+        match self.filter_mode {
+            FilterMode::Include => {
+                let len = self.sources.len();
+                self.sources.retain(|_, timer| timer.map(|timer| timer > now).unwrap_or(false));
+                changed = len != self.sources.len();
+            }
+
+            FilterMode::Exclude => {
+                for timer in self.sources.values_mut() {
+                    if timer.map(|timer| timer <= now).unwrap_or(false) {
+                        *timer = None;
+                        changed = true;
+                    }
+                }
+
+                if self.group_timer <= now {
+                    self.sources.retain(|_, timer| timer.is_some());
+                    self.filter_mode = FilterMode::Include;
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+
+    fn next_timer(&self) -> Option<Instant> {
+        let group_timer = if self.filter_mode == FilterMode::Exclude {
+            Some(self.group_timer)
+        } else {
+            None
+        };
+        self.sources.values().flatten().cloned().chain(group_timer).min()
+    }
+}
+
+/// Membership the proxy reports upstream, merged from all downstream interfaces (RFC 4605 section 4.1)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MldMembership {
+    pub filter_mode: FilterMode,
     pub source_addrs: HashSet<Ipv6Addr>,
 }
 
+/// Group-Specific query (no sources) or Group-and-Source-Specific query the router must send
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListenerQuery {
+    pub group_addr: Ipv6Addr,
+    pub source_addrs: Vec<Ipv6Addr>,
+}
+
+/// State-change report still to be retransmitted upstream (RFC 3810 section 6.1)
+#[derive(Debug, Clone)]
+struct PendingReport {
+    filter_mode_change: bool,
+    allow: HashSet<Ipv6Addr>,
+    block: HashSet<Ipv6Addr>,
+    remaining: u8,
+    next: Instant,
+}
+
+/// Answer to a Group-Specific or Group-and-Source-Specific query from upstream waiting for its delay
+#[derive(Debug, Clone)]
+struct PendingResponse {
+    /// `None` to report the whole group
+    source_addrs: Option<HashSet<Ipv6Addr>>,
+    at: Instant,
+}
+
+/// MIF numbers in use; numbers of removed interfaces are handed out again so that reloads don't run out of MAXMIFS
+#[derive(Debug, Clone, Default)]
+pub struct MifAllocator {
+    used: BTreeSet<socket::mifi_t>,
+}
+
+impl MifAllocator {
+    /// Takes the lowest unused MIF number, or `None` if all MAXMIFS are taken
+    pub fn allocate(&mut self) -> Option<socket::mifi_t> {
+        let vifd = (0..socket::MAXMIFS as socket::mifi_t).find(|vifd| !self.used.contains(vifd))?;
+        self.used.insert(vifd);
+        Some(vifd)
+    }
+
+    pub fn free(&mut self, vifd: socket::mifi_t) {
+        self.used.remove(&vifd);
+    }
+}
+
 #[derive(Debug)]
-pub struct MldSubscriptionManager {
-    socket: AsyncIcmp6Socket,
+pub struct MldSubscriptionManager<R = AsyncIcmp6Socket> {
+    router: R,
+    config: MldQuerierConfig,
     subscriptions: HashMap<InterfaceId, HashMap<Ipv6Addr, MldSubscription>>,
+    memberships: HashMap<Ipv6Addr, MldMembership>,
+    pending_reports: HashMap<Ipv6Addr, PendingReport>,
+
+    /// Older Version Querier Present timer for the upstream (RFC 3810 section 8.2.1)
+    older_querier_present: Option<Instant>,
+
+    /// Robustness Variable, Query Interval and Query Response Interval learned from the upstream querier
+    upstream_robustness: u8,
+    upstream_query_interval: Duration,
+    upstream_query_response_interval: Duration,
+
+    /// Interface timer for the answer to a General Query (RFC 3810 section 6.2)
+    general_response: Option<Instant>,
+    pending_responses: HashMap<Ipv6Addr, PendingResponse>,
+
+    /// output MIFs of the MFC entries installed per group and source, `::` for the wildcard entry
+    mroutes: HashMap<Ipv6Addr, HashMap<Ipv6Addr, BTreeSet<socket::mifi_t>>>,
+
+    /// sources the kernel reported traffic from without a matching MFC entry, per group
+    upcall_sources: HashMap<Ipv6Addr, HashSet<Ipv6Addr>>,
+
+    /// access control lists of downstream interfaces
+    acls: HashMap<InterfaceId, MldAclConfig>,
+
     vifs: HashMap<InterfaceId, socket::mifi_t>,
-    last_vifd: socket::mifi_t,
+    mif_allocator: MifAllocator,
     parent_if_index: InterfaceId,
 }
 
 impl MldSubscriptionManager {
-    pub fn new(socket: AsyncIcmp6Socket, interface_config: crate::config::InterfaceConfig) -> Self {
+    pub fn new(socket: AsyncIcmp6Socket, interface_config: crate::config::InterfaceConfig, config: MldQuerierConfig) -> Self {
         let parent_if_index = crate::interface::name_to_index(&interface_config.upstream).unwrap();
         let downstreams = interface_config.downstreams.iter()
             .map(|name| crate::interface::name_to_index(name))
@@ -33,36 +211,64 @@ impl MldSubscriptionManager {
             .map(|id| id.unwrap())
             .collect::<Vec<_>>();
 
+        Self::with_router(socket, parent_if_index, downstreams, config).unwrap()
+    }
+}
+
+impl<R: MulticastRouter> MldSubscriptionManager<R> {
+    /// Registers MIFs for the upstream and downstream interfaces with the given multicast router
+    pub fn with_router(router: R, parent_if_index: InterfaceId, downstreams: impl IntoIterator<Item = InterfaceId>, config: MldQuerierConfig) -> Result<Self, std::io::Error> {
         let mut instance = Self {
-            socket,
+            router,
+            upstream_robustness: config.robustness,
+            upstream_query_interval: config.query_interval(),
+            upstream_query_response_interval: config.query_response_interval(),
+            general_response: None,
+            pending_responses: HashMap::new(),
+            config,
             subscriptions: HashMap::new(),
+            memberships: HashMap::new(),
+            pending_reports: HashMap::new(),
+            older_querier_present: None,
+            mroutes: HashMap::new(),
+            upcall_sources: HashMap::new(),
+            acls: HashMap::new(),
             vifs: HashMap::new(),
-            last_vifd: 0,
+            mif_allocator: MifAllocator::default(),
             parent_if_index,
         };
 
-        instance.add_if(parent_if_index).unwrap();
+        instance.add_if(parent_if_index)?;
         for if_index in downstreams {
-            instance.add_if(if_index).unwrap();
+            instance.add_if(if_index)?;
         }
 
-        instance
+        Ok(instance)
+    }
+
+    pub fn set_config(&mut self, config: MldQuerierConfig) {
+        self.config = config;
     }
 
     pub fn add_if(&mut self, if_index: InterfaceId) -> Result<(), std::io::Error> {
         if self.vifs.contains_key(&if_index) {
             return Ok(());
         }
-        self.last_vifd += 1;
+        let vifd = self.mif_allocator.allocate()
+            .ok_or_else(|| std::io::Error::other("no free MIF"))?;
 
-        self.socket.multicast_add_vif(self.last_vifd, if_index)?;
-        self.vifs.insert(if_index, self.last_vifd);
+        if let Err(e) = self.router.add_mif(vifd, if_index) {
+            self.mif_allocator.free(vifd);
+            return Err(e);
+        }
+        self.vifs.insert(if_index, vifd);
         Ok(())
     }
 
     pub fn remove_if(&mut self, if_index: InterfaceId) -> Result<(), std::io::Error> {
         if let Some(vifd) = self.vifs.get(&if_index) {
-            self.socket.multicast_del_vif(*vifd)?;
+            self.router.del_mif(*vifd)?;
+            self.mif_allocator.free(*vifd);
         }
         self.vifs.remove(&if_index);
         Ok(())
@@ -79,101 +285,789 @@ impl MldSubscriptionManager {
             .collect()
     }
 
-    pub fn add_subscription(&mut self, if_index: InterfaceId, group_addr: std::net::Ipv6Addr, source_addrs: HashSet<Ipv6Addr>) {
-        let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    /// Applies a Multicast Address Record heard on a downstream interface (RFC 3810 section 7.4).
+    /// Returns the queries the querier has to send in response.
+    pub fn process_record(&mut self, if_index: InterfaceId, record: &MulticastReportRecord) -> Vec<ListenerQuery> {
+        let record = if let Some(record) = self.apply_acl(if_index, record) {
+            record
+        } else {
+            return Vec::new();
+        };
+
+        let now = Instant::now();
+        let listening_interval = now + self.config.multicast_address_listening_interval();
+        let group_addr = record.multicast_address;
 
-        let source_addrs = {
-            let subscription = self.subscriptions
-                .entry(if_index)
-                .or_insert(HashMap::new())
-                .entry(group_addr)
-                .or_insert(MldSubscription {
-                    timestamp: 0,
+        let subscription = self.subscriptions
+            .entry(if_index)
+            .or_default()
+            .entry(group_addr)
+            .or_insert_with(|| MldSubscription::new(group_addr, now));
+
+        // MLDv1 listeners can't see source filters, so sources must not be blocked for them (RFC 3810 section 8.3.2)
+        let compatibility = subscription.older_host_present.map(|timer| timer > now).unwrap_or(false);
+        let b = if compatibility && record.record_type == CHANGE_TO_EXCLUDE_MODE {
+            HashSet::new()
+        } else {
+            record.source_addresses.iter().cloned().collect::<HashSet<_>>()
+        };
+
+        // A is the include list in INCLUDE mode, X and Y the requested and excluded lists in EXCLUDE mode
+        let a = subscription.requested_sources();
+        let y = subscription.excluded_sources();
+        let mut queries = Vec::new();
+        let mut query_sources = |source_addrs: HashSet<Ipv6Addr>| {
+            if !source_addrs.is_empty() {
+                queries.push(ListenerQuery {
                     group_addr,
-                    source_addrs: HashSet::new(),
+                    source_addrs: source_addrs.into_iter().collect(),
                 });
-            
-            if subscription.timestamp != 0 {
-                return;
             }
+        };
 
-            subscription.timestamp = timestamp;
+        match (subscription.filter_mode, record.record_type) {
+            (_, BLOCK_OLD_SOURCES) if compatibility => {}
 
-            for source_addr in source_addrs {
-                subscription.source_addrs.insert(source_addr);
+            (_, MODE_IS_INCLUDE | ALLOW_NEW_SOURCES) => {
+                subscription.refresh(&b, listening_interval);
             }
 
-            let source_addrs = subscription.source_addrs.clone();
+            (FilterMode::Include, CHANGE_TO_INCLUDE_MODE) => {
+                subscription.refresh(&b, listening_interval);
+                query_sources(&a - &b);
+            }
+
+            (FilterMode::Exclude, CHANGE_TO_INCLUDE_MODE) => {
+                subscription.refresh(&b, listening_interval);
+                query_sources(&a - &b);
+                queries.push(ListenerQuery {
+                    group_addr,
+                    source_addrs: Vec::new(),
+                });
+            }
+
+            (FilterMode::Include, MODE_IS_EXCLUDE | CHANGE_TO_EXCLUDE_MODE) => {
+                subscription.sources.retain(|addr, _| b.contains(addr));
+                for addr in &b - &a {
+                    subscription.sources.insert(addr, None);
+                }
+                subscription.filter_mode = FilterMode::Exclude;
+                subscription.group_timer = listening_interval;
+                if record.record_type == CHANGE_TO_EXCLUDE_MODE {
+                    query_sources(&a & &b);
+                }
+            }
+
+            (FilterMode::Exclude, MODE_IS_EXCLUDE) => {
+                subscription.sources.retain(|addr, _| b.contains(addr));
+                for addr in &b {
+                    subscription.sources.entry(*addr).or_insert(Some(listening_interval));
+                }
+                subscription.group_timer = listening_interval;
+            }
+
+            (FilterMode::Exclude, CHANGE_TO_EXCLUDE_MODE) => {
+                let group_timer = subscription.group_timer;
+                subscription.sources.retain(|addr, _| b.contains(addr));
+                for addr in &b {
+                    subscription.sources.entry(*addr).or_insert(Some(group_timer));
+                }
+                subscription.group_timer = listening_interval;
+                query_sources(&b - &y);
+            }
+
+            (FilterMode::Include, BLOCK_OLD_SOURCES) => {
+                query_sources(&a & &b);
+            }
+
+            (FilterMode::Exclude, BLOCK_OLD_SOURCES) => {
+                let group_timer = subscription.group_timer;
+                for addr in &b {
+                    subscription.sources.entry(*addr).or_insert(Some(group_timer));
+                }
+                query_sources(&b - &y);
+            }
+
+            (_, record_type) => {
+                log::debug!("Ignoring Multicast Address Record of unknown type {} for {}", record_type, group_addr);
+            }
+        }
+
+        if subscription.is_empty() {
+            self.remove_subscription(if_index, group_addr);
+        }
+        self.update_group(group_addr);
+        queries
+    }
+
+    /// An MLDv1 Report counts as IS_EX({}) and starts the Older Version Host Present timer
+    pub fn process_v1_report(&mut self, if_index: InterfaceId, group_addr: Ipv6Addr) -> Vec<ListenerQuery> {
+        let queries = self.process_record(if_index, &MulticastReportRecord {
+            record_type: MODE_IS_EXCLUDE,
+            multicast_address: group_addr,
+            source_addresses: Vec::new(),
+        });
+
+        // Older Version Host Present Timeout equals the Multicast Address Listening Interval (RFC 3810 section 9.13)
+        let timeout = Instant::now() + self.config.multicast_address_listening_interval();
+        if let Some(subscription) = self.subscriptions.get_mut(&if_index).and_then(|subscriptions| subscriptions.get_mut(&group_addr)) {
+            subscription.older_host_present = Some(timeout);
+        }
+        queries
+    }
+
+    /// An MLDv1 Done counts as TO_IN({})
+    pub fn process_v1_done(&mut self, if_index: InterfaceId, group_addr: Ipv6Addr) -> Vec<ListenerQuery> {
+        self.process_record(if_index, &MulticastReportRecord {
+            record_type: CHANGE_TO_INCLUDE_MODE,
+            multicast_address: group_addr,
+            source_addresses: Vec::new(),
+        })
+    }
+
+    /// Applies the access control lists of downstream interfaces and drops the subscriptions they no longer permit
+    pub fn set_acls(&mut self, acls: &HashMap<String, MldAclConfig>) {
+        self.set_interface_acls(acls.iter()
+            .filter_map(|(name, acl)| Some((crate::interface::name_to_index(name).ok()?, acl.clone())))
+            .collect());
+    }
+
+    /// Same as `set_acls` with the interfaces already resolved
+    pub fn set_interface_acls(&mut self, acls: HashMap<InterfaceId, MldAclConfig>) {
+        self.acls = acls;
+
+        let mut changed = HashSet::new();
+        for (if_index, subscriptions) in self.subscriptions.iter_mut() {
+            let acl = if let Some(acl) = self.acls.get(if_index) {
+                acl
+            } else {
+                continue;
+            };
+
+            subscriptions.retain(|group_addr, subscription| {
+                if !acl.permits_group(group_addr) || (subscription.filter_mode == FilterMode::Exclude && !acl.permits_any_source()) {
+                    log::info!("Dropping subscription to {} on {:?} denied by mld_acl", group_addr, if_index);
+                    changed.insert(*group_addr);
+                    return false;
+                }
 
-            source_addrs
+                let len = subscription.sources.len();
+                subscription.sources.retain(|source_addr, _| acl.permits_source(source_addr));
+                if subscription.sources.len() != len {
+                    log::info!("Dropping sources of {} on {:?} denied by mld_acl", group_addr, if_index);
+                    changed.insert(*group_addr);
+                }
+                !subscription.is_empty()
+            });
+        }
+        self.subscriptions.retain(|_, subscriptions| !subscriptions.is_empty());
+
+        for group_addr in changed {
+            self.update_group(group_addr);
+        }
+    }
+
+    /// Returns the part of a record the interface's access control list permits, or `None` if it is denied.
+    /// Leaving a group or blocking sources is always permitted.
+    fn apply_acl(&self, if_index: InterfaceId, record: &MulticastReportRecord) -> Option<MulticastReportRecord> {
+        let acl = if let Some(acl) = self.acls.get(&if_index) {
+            acl
+        } else {
+            return Some(record.clone());
+        };
+
+        let group_addr = record.multicast_address;
+        match record.record_type {
+            BLOCK_OLD_SOURCES => return Some(record.clone()),
+            MODE_IS_INCLUDE | CHANGE_TO_INCLUDE_MODE if record.source_addresses.is_empty() => return Some(record.clone()),
+            _ => {}
+        }
+
+        if !acl.permits_group(&group_addr) {
+            log::info!("Denied subscription to {} on {:?} by mld_acl", group_addr, if_index);
+            return None;
+        }
+        if matches!(record.record_type, MODE_IS_EXCLUDE | CHANGE_TO_EXCLUDE_MODE) && !acl.permits_any_source() {
+            log::info!("Denied any-source subscription to {} on {:?} by mld_acl", group_addr, if_index);
+            return None;
+        }
+
+        let subscriptions = self.subscriptions.get(&if_index);
+        let joined = subscriptions.map(|subscriptions| subscriptions.len()).unwrap_or(0);
+        let exists = subscriptions.map(|subscriptions| subscriptions.contains_key(&group_addr)).unwrap_or(false);
+        if let Some(max_groups) = acl.max_groups {
+            if !exists && joined >= max_groups {
+                log::info!("Denied subscription to {} on {:?}: mld_acl allows at most {} groups", group_addr, if_index, max_groups);
+                return None;
+            }
+        }
+
+        let (source_addresses, denied): (Vec<_>, Vec<_>) = record.source_addresses.iter()
+            .cloned()
+            .partition(|source_addr| acl.permits_source(source_addr));
+        if !denied.is_empty() {
+            log::info!("Denied sources {:?} of {} on {:?} by mld_acl", denied, group_addr, if_index);
+        }
+
+        Some(MulticastReportRecord {
+            source_addresses,
+            ..record.clone()
+        })
+    }
+
+    /// Schedules the answer to a query heard on the upstream at a random point within its Maximum Response Delay (RFC 3810 section 6.2)
+    pub fn receive_query(&mut self, query: &MulticastListenerQuery, is_v1: bool) {
+        let now = Instant::now();
+        let max_response_delay = if is_v1 {
+            // MLDv1 carries the delay in milliseconds without an exponent
+            Duration::from_millis(query.maximum_response_delay as u64)
+        } else {
+            Duration::from_millis(decode_max_response_code(query.maximum_response_delay) as u64)
+        };
+
+        // RFC 3810 section 9.1 to 9.3: hosts use the querier's values
+        self.upstream_query_response_interval = max_response_delay;
+        if is_v1 {
+            self.set_older_querier_present();
+        } else {
+            if query.qrv != 0 {
+                self.upstream_robustness = query.qrv;
+            }
+            if query.qqic != 0 {
+                self.upstream_query_interval = Duration::from_secs(decode_qqic(query.qqic) as u64);
+            }
+        }
+
+        let at = now + rand::thread_rng().gen_range(Duration::ZERO..=max_response_delay);
+        if self.general_response.map(|general_response| general_response <= at).unwrap_or(false) {
+            return;
+        }
+
+        let group_addr = query.group_address;
+        if group_addr.is_unspecified() {
+            self.general_response = Some(at);
+            return;
+        }
+        if !self.memberships.contains_key(&group_addr) {
+            return;
+        }
+
+        let source_addrs = if query.source_addresses.is_empty() {
+            None
+        } else {
+            Some(query.source_addresses.iter().cloned().collect::<HashSet<_>>())
+        };
+        if let Some(pending) = self.pending_responses.get_mut(&group_addr) {
+            pending.at = pending.at.min(at);
+            pending.source_addrs = match (pending.source_addrs.take(), source_addrs) {
+                (Some(mut pending_addrs), Some(source_addrs)) => {
+                    pending_addrs.extend(source_addrs);
+                    Some(pending_addrs)
+                }
+                _ => None,
+            };
+        } else {
+            self.pending_responses.insert(group_addr, PendingResponse {
+                source_addrs,
+                at,
+            });
+        }
+    }
+
+    /// Switches the upstream to MLDv1 after hearing an MLDv1 query there (RFC 3810 section 8.2.1)
+    fn set_older_querier_present(&mut self) {
+        if !self.is_older_querier_present() {
+            log::info!("MLDv1 querier on the upstream, sending MLDv1 reports");
+        }
+
+        // Older Version Querier Present Timeout (RFC 3810 section 9.12)
+        let timeout = self.upstream_query_interval * self.upstream_robustness as u32 + self.upstream_query_response_interval;
+        self.older_querier_present = Some(Instant::now() + timeout);
+    }
+
+    /// Whether reports to the upstream have to be MLDv1 messages
+    pub fn is_older_querier_present(&self) -> bool {
+        self.older_querier_present.map(|timer| timer > Instant::now()).unwrap_or(false)
+    }
+
+    fn remove_subscription(&mut self, if_index: InterfaceId, group_addr: Ipv6Addr) {
+        if let Some(subscriptions) = self.subscriptions.get_mut(&if_index) {
+            subscriptions.remove(&group_addr);
+            if subscriptions.is_empty() {
+                self.subscriptions.remove(&if_index);
+            }
+        }
+    }
+
+    /// Lowers timers to the Last Listener Query Time after a query with the S flag clear (RFC 3810 section 7.6.3)
+    pub fn lower_timers(&mut self, if_index: InterfaceId, group_addr: Ipv6Addr, source_addrs: &[Ipv6Addr]) {
+        let deadline = Instant::now() + self.config.last_listener_query_time();
+        let subscription = if let Some(subscription) = self.subscriptions.get_mut(&if_index).and_then(|subscriptions| subscriptions.get_mut(&group_addr)) {
+            subscription
+        } else {
+            return;
         };
 
-        let parent = self.get_vifd(self.parent_if_index).unwrap();
-        let output = self.get_subscribed_interfaces(group_addr).iter()
-            .filter_map(|if_index| self.get_vifd(*if_index))
-            .collect::<Vec<_>>();
-        
         if source_addrs.is_empty() {
-            let src = Ipv6Addr::UNSPECIFIED;
-            if let Err(e) = self.socket.multicast_add_mroute(parent, output, group_addr, src) {
-                log::error!("failed to add mroute: {}", e);
+            if subscription.filter_mode == FilterMode::Exclude && subscription.group_timer > deadline {
+                subscription.group_timer = deadline;
             }
             return;
         }
-        
-        for src in source_addrs {
-            if let Err(e) = self.socket.multicast_add_mroute(parent, output.clone(), group_addr, src) {
+
+        for source_addr in source_addrs {
+            if let Some(Some(timer)) = subscription.sources.get_mut(source_addr) {
+                if *timer > deadline {
+                    *timer = deadline;
+                }
+            }
+        }
+    }
+
+    /// Applies expired source and group timers
+    pub fn handle_timers(&mut self) {
+        let now = Instant::now();
+        let mut changed = HashSet::new();
+        for subscriptions in self.subscriptions.values_mut() {
+            for (group_addr, subscription) in subscriptions.iter_mut() {
+                if subscription.expire(now) {
+                    changed.insert(*group_addr);
+                }
+            }
+            subscriptions.retain(|_, subscription| !subscription.is_empty());
+        }
+        self.subscriptions.retain(|_, subscriptions| !subscriptions.is_empty());
+
+        for group_addr in changed {
+            self.update_group(group_addr);
+        }
+    }
+
+    /// When `handle_timers` or `take_due_reports` next has work to do
+    pub fn next_timer(&self) -> Option<Instant> {
+        let subscriptions = self.subscriptions.values()
+            .flat_map(|subscriptions| subscriptions.values())
+            .filter_map(|subscription| subscription.next_timer());
+        let reports = self.pending_reports.values().map(|pending| pending.next);
+        let responses = self.pending_responses.values().map(|pending| pending.at).chain(self.general_response);
+        subscriptions.chain(reports).chain(responses).min()
+    }
+
+    /// Merges the per-interface state of a group (RFC 4605 section 4.1)
+    fn merge(&self, group_addr: Ipv6Addr) -> Option<MldMembership> {
+        let subscriptions = self.subscriptions.values()
+            .filter_map(|subscriptions| subscriptions.get(&group_addr))
+            .collect::<Vec<_>>();
+        if subscriptions.is_empty() {
+            return None;
+        }
+
+        let mut included = HashSet::new();
+        let mut excluded: Option<HashSet<Ipv6Addr>> = None;
+        for subscription in subscriptions {
+            match subscription.filter_mode {
+                FilterMode::Include => included.extend(subscription.requested_sources()),
+                FilterMode::Exclude => {
+                    let sources = subscription.excluded_sources();
+                    excluded = Some(match excluded {
+                        Some(excluded) => &excluded & &sources,
+                        None => sources,
+                    });
+                }
+            }
+        }
+
+        Some(match excluded {
+            Some(excluded) => MldMembership {
+                filter_mode: FilterMode::Exclude,
+                source_addrs: &excluded - &included,
+            },
+            None => MldMembership {
+                filter_mode: FilterMode::Include,
+                source_addrs: included,
+            },
+        })
+    }
+
+    /// Recomputes the upstream membership and forwarding for a group after its state changed
+    fn update_group(&mut self, group_addr: Ipv6Addr) {
+        let membership = self.merge(group_addr);
+        let old = self.memberships.get(&group_addr).cloned();
+        if membership != old {
+            self.schedule_report(group_addr, old, membership.clone());
+            if let Some(membership) = membership {
+                self.memberships.insert(group_addr, membership);
+            } else {
+                self.memberships.remove(&group_addr);
+            }
+        }
+        self.sync_mroutes(group_addr);
+    }
+
+    /// Queues state-change records for the upstream (RFC 3810 section 6.1)
+    fn schedule_report(&mut self, group_addr: Ipv6Addr, old: Option<MldMembership>, new: Option<MldMembership>) {
+        let (old_mode, old_sources) = old.map(|m| (m.filter_mode, m.source_addrs)).unwrap_or((FilterMode::Include, HashSet::new()));
+        let (new_mode, new_sources) = new.map(|m| (m.filter_mode, m.source_addrs)).unwrap_or((FilterMode::Include, HashSet::new()));
+
+        let now = Instant::now();
+        let pending = self.pending_reports.entry(group_addr).or_insert(PendingReport {
+            filter_mode_change: false,
+            allow: HashSet::new(),
+            block: HashSet::new(),
+            remaining: 0,
+            next: now,
+        });
+
+        if old_mode != new_mode {
+            pending.filter_mode_change = true;
+            pending.allow.clear();
+            pending.block.clear();
+        } else if !pending.filter_mode_change {
+            let (allow, block) = if new_mode == FilterMode::Include {
+                (&new_sources - &old_sources, &old_sources - &new_sources)
+            } else {
+                (&old_sources - &new_sources, &new_sources - &old_sources)
+            };
+            for addr in allow {
+                pending.block.remove(&addr);
+                pending.allow.insert(addr);
+            }
+            for addr in block {
+                pending.allow.remove(&addr);
+                pending.block.insert(addr);
+            }
+        }
+
+        pending.remaining = self.upstream_robustness.max(1);
+        pending.next = now;
+    }
+
+    /// Records due for transmission upstream: state-change (re)transmissions followed by answers to queries
+    pub fn take_due_reports(&mut self) -> Vec<MulticastReportRecord> {
+        let mut records = self.take_due_state_changes();
+        let now = Instant::now();
+
+        if self.general_response.map(|general_response| general_response <= now).unwrap_or(false) {
+            self.general_response = None;
+            let mut groups = self.memberships.keys().cloned().collect::<Vec<_>>();
+            groups.sort();
+            records.extend(groups.into_iter().filter_map(|group_addr| self.current_state_record(group_addr)));
+        }
+
+        let due = self.pending_responses.iter()
+            .filter(|(_, pending)| pending.at <= now)
+            .map(|(group_addr, _)| *group_addr)
+            .collect::<Vec<_>>();
+        for group_addr in due {
+            let pending = self.pending_responses.remove(&group_addr).unwrap();
+            let queried = if let Some(queried) = pending.source_addrs {
+                queried
+            } else {
+                records.extend(self.current_state_record(group_addr));
+                continue;
+            };
+
+            // answer to a Group-and-Source-Specific query: the queried sources we still want
+            let membership = if let Some(membership) = self.memberships.get(&group_addr) {
+                membership
+            } else {
+                continue;
+            };
+            let source_addrs = match membership.filter_mode {
+                FilterMode::Include => &queried & &membership.source_addrs,
+                FilterMode::Exclude => &queried - &membership.source_addrs,
+            };
+            if !source_addrs.is_empty() {
+                records.push(MulticastReportRecord {
+                    record_type: MODE_IS_INCLUDE,
+                    multicast_address: group_addr,
+                    source_addresses: source_addrs.into_iter().collect(),
+                });
+            }
+        }
+        records
+    }
+
+    fn take_due_state_changes(&mut self) -> Vec<MulticastReportRecord> {
+        let now = Instant::now();
+        let mut records = Vec::new();
+        for (group_addr, pending) in self.pending_reports.iter_mut() {
+            if pending.next > now {
+                continue;
+            }
+
+            if pending.filter_mode_change {
+                let (record_type, source_addrs) = match self.memberships.get(group_addr) {
+                    Some(membership) if membership.filter_mode == FilterMode::Exclude => (CHANGE_TO_EXCLUDE_MODE, membership.source_addrs.clone()),
+                    Some(membership) => (CHANGE_TO_INCLUDE_MODE, membership.source_addrs.clone()),
+                    None => (CHANGE_TO_INCLUDE_MODE, HashSet::new()),
+                };
+                records.push(MulticastReportRecord {
+                    record_type,
+                    multicast_address: *group_addr,
+                    source_addresses: source_addrs.into_iter().collect(),
+                });
+            } else {
+                for (record_type, source_addrs) in [(ALLOW_NEW_SOURCES, &pending.allow), (BLOCK_OLD_SOURCES, &pending.block)] {
+                    if !source_addrs.is_empty() {
+                        records.push(MulticastReportRecord {
+                            record_type,
+                            multicast_address: *group_addr,
+                            source_addresses: source_addrs.iter().cloned().collect(),
+                        });
+                    }
+                }
+            }
+
+            pending.remaining = pending.remaining.saturating_sub(1);
+            pending.next = now + rand::thread_rng().gen_range(Duration::ZERO..=UNSOLICITED_REPORT_INTERVAL);
+        }
+        self.pending_reports.retain(|_, pending| pending.remaining > 0);
+        records
+    }
+
+    /// Current-state record for answering a query from upstream, `None` if nobody listens
+    pub fn current_state_record(&self, group_addr: Ipv6Addr) -> Option<MulticastReportRecord> {
+        let membership = self.memberships.get(&group_addr)?;
+        let record_type = match membership.filter_mode {
+            FilterMode::Include => MODE_IS_INCLUDE,
+            FilterMode::Exclude => MODE_IS_EXCLUDE,
+        };
+        Some(MulticastReportRecord {
+            record_type,
+            multicast_address: group_addr,
+            source_addresses: membership.source_addrs.iter().cloned().collect(),
+        })
+    }
+
+    /// Re-registers MIFs and mroutes after the interface configuration changed
+    pub fn set_interfaces(&mut self, interface_config: InterfaceConfig) -> Result<(), std::io::Error> {
+        let parent_if_index = crate::interface::name_to_index(&interface_config.upstream)?;
+        let mut if_indexes = interface_config.downstreams.iter()
+            .filter_map(|name| crate::interface::name_to_index(name).ok())
+            .collect::<HashSet<_>>();
+        if_indexes.insert(parent_if_index);
+
+        if parent_if_index != self.parent_if_index {
+            // every mroute hangs off the old parent MIF, and the new upstream has not heard of our memberships
+            self.subscriptions.clear();
+            for group_addr in self.mroutes.keys().cloned().collect::<Vec<_>>() {
+                self.sync_mroutes(group_addr);
+            }
+            self.memberships.clear();
+            self.pending_reports.clear();
+            self.pending_responses.clear();
+            self.general_response = None;
+            self.older_querier_present = None;
+            self.upstream_robustness = self.config.robustness;
+            self.upstream_query_interval = self.config.query_interval();
+            self.upstream_query_response_interval = self.config.query_response_interval();
+            self.parent_if_index = parent_if_index;
+        }
+
+        let removed = self.vifs.keys().filter(|if_index| !if_indexes.contains(if_index)).cloned().collect::<Vec<_>>();
+        for if_index in removed {
+            self.subscriptions.remove(&if_index);
+            self.remove_if(if_index)?;
+        }
+        for if_index in if_indexes {
+            self.add_if(if_index)?;
+        }
+
+        let mut groups = self.get_groups();
+        groups.extend(self.mroutes.keys().cloned());
+        for group_addr in groups {
+            self.update_group(group_addr);
+        }
+        Ok(())
+    }
+
+    /// Replaces the kernel entries of a group whose output MIFs changed and removes stale ones
+    fn sync_mroutes(&mut self, group_addr: Ipv6Addr) {
+        let parent = if let Some(parent) = self.get_vifd(self.parent_if_index) {
+            parent
+        } else {
+            return;
+        };
+
+        let subscriptions = self.subscriptions.iter()
+            .filter_map(|(if_index, subscriptions)| Some((self.get_vifd(*if_index)?, subscriptions.get(&group_addr)?)));
+        let no_upcall_sources = HashSet::new();
+        let wanted = wanted_mroutes(subscriptions, self.upcall_sources.get(&group_addr).unwrap_or(&no_upcall_sources));
+        if wanted.is_empty() {
+            self.upcall_sources.remove(&group_addr);
+        }
+
+        let mut installed = self.mroutes.remove(&group_addr).unwrap_or_default();
+        let changes = mroute_changes(&installed, &wanted);
+        for src in changes.delete {
+            if let Err(e) = self.router.del_mroute(parent, group_addr, src) {
+                log::error!("failed to del mroute: {}", e);
+            }
+            installed.remove(&src);
+        }
+        for (src, output) in changes.add {
+            if let Err(e) = self.router.add_mroute(parent, output.iter().cloned().collect(), group_addr, src) {
                 log::error!("failed to add mroute: {}", e);
+                installed.remove(&src);
+                continue;
             }
+            installed.insert(src, output);
+        }
+        if !installed.is_empty() {
+            self.mroutes.insert(group_addr, installed);
         }
     }
 
-    pub fn remove_old_subscriptions(&mut self, timeout: u64) {
-        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
-        for if_index in self.subscriptions.keys().cloned().collect::<Vec<_>>() {
-            let subscriptions = self.subscriptions.get_mut(&if_index).unwrap();
-            for (group_addr, subscription) in subscriptions.clone().iter() {
-                if now - subscription.timestamp > timeout {
-                    subscriptions.remove(group_addr);
-                    let parent = self.vifs.get(&self.parent_if_index).cloned().unwrap();
-
-                    if subscription.source_addrs.is_empty() {
-                        let src = Ipv6Addr::UNSPECIFIED;
-                        if let Err(e) = self.socket.multicast_del_mroute(parent, *group_addr, src) {
-                            log::error!("failed to del mroute: {}", e);
-                        }
+    /// Handles an upcall from the kernel multicast routing code
+    pub fn receive_upcall(&mut self, msg: &socket::Mrt6Msg) {
+        match msg.msg_type {
+            socket::Mrt6MsgType::NoCache => self.receive_nocache(msg.mif, msg.source_addr, msg.group_addr),
+            socket::Mrt6MsgType::WrongMif => {
+                log::debug!("Multicast traffic from {} to {} on unexpected MIF {}", msg.source_addr, msg.group_addr, msg.mif);
+            }
+            socket::Mrt6MsgType::WholePkt => {
+                log::debug!("Ignoring PIM register packet from {} to {}", msg.source_addr, msg.group_addr);
+            }
+            socket::Mrt6MsgType::Unknown(msg_type) => {
+                log::debug!("Ignoring unknown MRT6 upcall: {}", msg_type);
+            }
+        }
+    }
+
+    /// Installs the (S,G) entry on demand for upstream traffic the kernel has no MFC entry for,
+    /// so that sources of ASM groups need not be known in advance
+    fn receive_nocache(&mut self, mif: socket::mifi_t, source_addr: Ipv6Addr, group_addr: Ipv6Addr) {
+        if self.get_vifd(self.parent_if_index) != Some(mif) {
+            log::debug!("Ignoring multicast traffic from {} to {} on downstream MIF {}", source_addr, group_addr, mif);
+            return;
+        }
+        if self.get_subscribed_interfaces(group_addr).is_empty() {
+            return;
+        }
+
+        if let Some(mroutes) = self.mroutes.get_mut(&group_addr) {
+            // the kernel lost an entry we installed; have it replaced
+            mroutes.remove(&source_addr);
+        }
+
+        log::debug!("Installing mroute for ({}, {}) on demand", source_addr, group_addr);
+        self.upcall_sources.entry(group_addr).or_default().insert(source_addr);
+        self.sync_mroutes(group_addr);
+    }
+
+    /// Traffic forwarded per group to each subscribed downstream interface, summed over the group's MFC entries.
+    /// Subscriptions nothing was forwarded for show up with zero counters.
+    pub fn get_forwarding_counters(&self) -> HashMap<Ipv6Addr, HashMap<InterfaceId, SgCounters>> {
+        let mut counters: HashMap<Ipv6Addr, HashMap<InterfaceId, SgCounters>> = HashMap::new();
+        for (if_index, subscriptions) in &self.subscriptions {
+            for group_addr in subscriptions.keys() {
+                counters.entry(*group_addr).or_default().insert(*if_index, SgCounters::default());
+            }
+        }
+
+        for (group_addr, mroutes) in &self.mroutes {
+            for (source_addr, output) in mroutes {
+                let sg_counters = match self.router.sg_counters(*group_addr, *source_addr) {
+                    Ok(sg_counters) => sg_counters,
+                    Err(e) => {
+                        log::debug!("failed to get counters of ({}, {}): {}", source_addr, group_addr, e);
                         continue;
                     }
+                };
 
-                    let _ = self.socket.multicast_del_mroute(parent, *group_addr, Ipv6Addr::UNSPECIFIED);
-
-                    for src in subscription.source_addrs.iter() {
-                        if let Err(e) = self.socket.multicast_del_mroute(parent, *group_addr, *src) {
-                            log::error!("failed to del mroute: {}", e);
-                        }
+                for (if_index, vifd) in &self.vifs {
+                    if output.contains(vifd) {
+                        *counters.entry(*group_addr).or_default().entry(*if_index).or_default() += sg_counters;
                     }
                 }
             }
         }
+        counters
+    }
 
-        self.subscriptions.retain(|_, subscriptions| !subscriptions.is_empty());
+    /// Traffic received and sent on each MIF
+    pub fn get_interface_counters(&self) -> HashMap<InterfaceId, MifCounters> {
+        self.vifs.iter().filter_map(|(if_index, vifd)| {
+            match self.router.mif_counters(*vifd) {
+                Ok(mif_counters) => Some((*if_index, mif_counters)),
+                Err(e) => {
+                    log::debug!("failed to get counters of MIF {}: {}", vifd, e);
+                    None
+                }
+            }
+        }).collect()
     }
 
+    /// Groups with listeners on at least one downstream interface
     pub fn get_groups(&self) -> HashSet<Ipv6Addr> {
         self.subscriptions.iter().flat_map(|(_, subscriptions)| subscriptions.keys().cloned()).collect()
     }
 
-    pub fn get_source_addresses(&self, group_addr: Ipv6Addr) -> HashSet<Ipv6Addr> {
-        self.subscriptions.iter().flat_map(|(_, subscriptions)| {
-            subscriptions.iter().filter_map(|(addr, subscription)| {
-                if *addr == group_addr {
-                    Some(subscription.source_addrs.clone())
-                } else {
-                    None
-                }
-            })
-        }).flatten().collect()
+    pub fn get_membership(&self, group_addr: Ipv6Addr) -> Option<&MldMembership> {
+        self.memberships.get(&group_addr)
+    }
+
+    /// Listener state for a group on one downstream interface
+    pub fn get_subscription(&self, if_index: InterfaceId, group_addr: Ipv6Addr) -> Option<&MldSubscription> {
+        self.subscriptions.get(&if_index)?.get(&group_addr)
+    }
+}
+
+/// Output MIFs for every (S,G) entry a group needs, given its subscriptions by MIF and the sources upcalls reported;
+/// EXCLUDE-mode interfaces take the wildcard entry, keyed by `::`
+pub fn wanted_mroutes<'a>(subscriptions: impl IntoIterator<Item = (socket::mifi_t, &'a MldSubscription)>, upcall_sources: &HashSet<Ipv6Addr>) -> HashMap<Ipv6Addr, BTreeSet<socket::mifi_t>> {
+    let subscriptions = subscriptions.into_iter().collect::<Vec<_>>();
+
+    let mut mroutes = HashMap::new();
+    let wildcard = subscriptions.iter()
+        .filter(|(_, subscription)| subscription.filter_mode == FilterMode::Exclude)
+        .map(|(vifd, _)| *vifd)
+        .collect::<BTreeSet<_>>();
+
+    if subscriptions.is_empty() {
+        return mroutes;
+    }
+
+    let mut source_addrs = subscriptions.iter()
+        .flat_map(|(_, subscription)| subscription.sources.keys().cloned())
+        .collect::<HashSet<_>>();
+    source_addrs.extend(upcall_sources.iter().cloned());
+    for source_addr in source_addrs {
+        let output = subscriptions.iter()
+            .filter(|(_, subscription)| subscription.forwards(&source_addr))
+            .map(|(vifd, _)| *vifd)
+            .collect::<BTreeSet<_>>();
+
+        // an entry without outputs still keeps an excluded source off the wildcard entry
+        if !output.is_empty() || !wildcard.is_empty() {
+            mroutes.insert(source_addr, output);
+        }
+    }
+
+    if !wildcard.is_empty() {
+        mroutes.insert(Ipv6Addr::UNSPECIFIED, wildcard);
     }
+    mroutes
+}
+
+/// MFC entries of a group to delete and to (re)install, by source
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MrouteChanges {
+    pub delete: Vec<Ipv6Addr>,
+    pub add: Vec<(Ipv6Addr, BTreeSet<socket::mifi_t>)>,
+}
+
+/// Entries to change so that the installed entries of a group become the wanted ones; unchanged entries are left alone
+pub fn mroute_changes(installed: &HashMap<Ipv6Addr, BTreeSet<socket::mifi_t>>, wanted: &HashMap<Ipv6Addr, BTreeSet<socket::mifi_t>>) -> MrouteChanges {
+    let mut delete = installed.keys().filter(|src| !wanted.contains_key(src)).cloned().collect::<Vec<_>>();
+    let mut add = wanted.iter()
+        .filter(|(src, output)| installed.get(src) != Some(output))
+        .map(|(src, output)| (*src, output.clone()))
+        .collect::<Vec<_>>();
+    delete.sort();
+    add.sort();
+    MrouteChanges { delete, add }
 }
 
 pub fn is_solicited_node_address(addr: &std::net::Ipv6Addr) -> bool {
@@ -221,6 +1115,37 @@ impl NdpMulticastManager {
         }
     }
 
+    /// Moves solicited-node group memberships to the new set of interfaces
+    pub fn set_interfaces(&mut self, interface_config: InterfaceConfig) {
+        let interface_ids = interface_config.interfaces().iter()
+            .filter_map(|name| crate::interface::name_to_index(name).ok())
+            .collect::<HashSet<_>>();
+        let removed = self.interface_ids.difference(&interface_ids).cloned().collect::<Vec<_>>();
+        let added = interface_ids.difference(&self.interface_ids).cloned().collect::<Vec<_>>();
+
+        for (solicited_node_addr, subscriptions) in self.subscriptions.iter_mut() {
+            subscriptions.retain(|if_index, _| interface_ids.contains(if_index));
+
+            for if_id in removed.iter() {
+                let _ = self.socket.leave_multicast(*solicited_node_addr, *if_id);
+            }
+
+            for if_id in interface_ids.iter() {
+                let wanted = subscriptions.keys().any(|other| other != if_id);
+                if wanted && added.contains(if_id) {
+                    if let Err(e) = self.socket.join_multicast(*solicited_node_addr, *if_id) {
+                        log::error!("failed to join multicast: {}", e);
+                    }
+                } else if !wanted && !added.contains(if_id) {
+                    let _ = self.socket.leave_multicast(*solicited_node_addr, *if_id);
+                }
+            }
+        }
+
+        self.subscriptions.retain(|_, subscriptions| !subscriptions.is_empty());
+        self.interface_ids = interface_ids;
+    }
+
     pub fn remove_old_subscriptions(&mut self, timeout: u64) {
         let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
         for solicited_node_addr in self.subscriptions.keys().cloned().collect::<Vec<_>>() {
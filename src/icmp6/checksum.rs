@@ -0,0 +1,30 @@
+
+use std::net::Ipv6Addr;
+
+/// next header value of ICMPv6
+const NEXT_HEADER_ICMPV6: u8 = 58;
+
+/// RFC 4443 section 2.3 checksum over the IPv6 pseudo-header and the message as given.
+/// Returns zero when `data` already carries a valid checksum.
+pub fn icmp6_checksum(source: Ipv6Addr, destination: Ipv6Addr, data: &[u8]) -> u16 {
+    let mut sum = 0u32;
+    let mut add = |bytes: &[u8]| {
+        for chunk in bytes.chunks(2) {
+            let word = if chunk.len() == 2 {
+                u16::from_be_bytes([chunk[0], chunk[1]])
+            } else {
+                u16::from_be_bytes([chunk[0], 0])
+            };
+            sum += word as u32;
+            sum = (sum & 0xffff) + (sum >> 16);
+        }
+    };
+
+    add(&source.octets());
+    add(&destination.octets());
+    add(&(data.len() as u32).to_be_bytes());
+    add(&[0, 0, 0, NEXT_HEADER_ICMPV6]);
+    add(data);
+
+    !(sum as u16)
+}
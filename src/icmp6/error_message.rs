@@ -0,0 +1,258 @@
+
+use std::net::Ipv6Addr;
+
+/// Destination Unreachable codes (RFC 4443 section 3.1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DestinationUnreachableCode {
+    /// code 0
+    NoRoute,
+
+    /// code 1
+    AdministrativelyProhibited,
+
+    /// code 2
+    BeyondScope,
+
+    /// code 3
+    AddressUnreachable,
+
+    /// code 4
+    PortUnreachable,
+
+    /// code 5
+    SourcePolicyFailed,
+
+    /// code 6
+    RejectRoute,
+
+    /// code 7 (RFC 6554)
+    SourceRoutingHeader,
+
+    Unknown(u8),
+}
+
+impl From<u8> for DestinationUnreachableCode {
+    fn from(code: u8) -> Self {
+        match code {
+            0 => Self::NoRoute,
+            1 => Self::AdministrativelyProhibited,
+            2 => Self::BeyondScope,
+            3 => Self::AddressUnreachable,
+            4 => Self::PortUnreachable,
+            5 => Self::SourcePolicyFailed,
+            6 => Self::RejectRoute,
+            7 => Self::SourceRoutingHeader,
+            _ => Self::Unknown(code),
+        }
+    }
+}
+
+impl From<DestinationUnreachableCode> for u8 {
+    fn from(code: DestinationUnreachableCode) -> Self {
+        match code {
+            DestinationUnreachableCode::NoRoute => 0,
+            DestinationUnreachableCode::AdministrativelyProhibited => 1,
+            DestinationUnreachableCode::BeyondScope => 2,
+            DestinationUnreachableCode::AddressUnreachable => 3,
+            DestinationUnreachableCode::PortUnreachable => 4,
+            DestinationUnreachableCode::SourcePolicyFailed => 5,
+            DestinationUnreachableCode::RejectRoute => 6,
+            DestinationUnreachableCode::SourceRoutingHeader => 7,
+            DestinationUnreachableCode::Unknown(code) => code,
+        }
+    }
+}
+
+/// Time Exceeded codes (RFC 4443 section 3.3)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TimeExceededCode {
+    /// code 0
+    HopLimitExceeded,
+
+    /// code 1
+    FragmentReassemblyTimeExceeded,
+
+    Unknown(u8),
+}
+
+impl From<u8> for TimeExceededCode {
+    fn from(code: u8) -> Self {
+        match code {
+            0 => Self::HopLimitExceeded,
+            1 => Self::FragmentReassemblyTimeExceeded,
+            _ => Self::Unknown(code),
+        }
+    }
+}
+
+impl From<TimeExceededCode> for u8 {
+    fn from(code: TimeExceededCode) -> Self {
+        match code {
+            TimeExceededCode::HopLimitExceeded => 0,
+            TimeExceededCode::FragmentReassemblyTimeExceeded => 1,
+            TimeExceededCode::Unknown(code) => code,
+        }
+    }
+}
+
+/// Parameter Problem codes (RFC 4443 section 3.4)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ParameterProblemCode {
+    /// code 0
+    ErroneousHeaderField,
+
+    /// code 1
+    UnrecognizedNextHeader,
+
+    /// code 2
+    UnrecognizedIpv6Option,
+
+    /// code 3 (RFC 7112)
+    IncompleteHeaderChain,
+
+    Unknown(u8),
+}
+
+impl From<u8> for ParameterProblemCode {
+    fn from(code: u8) -> Self {
+        match code {
+            0 => Self::ErroneousHeaderField,
+            1 => Self::UnrecognizedNextHeader,
+            2 => Self::UnrecognizedIpv6Option,
+            3 => Self::IncompleteHeaderChain,
+            _ => Self::Unknown(code),
+        }
+    }
+}
+
+impl From<ParameterProblemCode> for u8 {
+    fn from(code: ParameterProblemCode) -> Self {
+        match code {
+            ParameterProblemCode::ErroneousHeaderField => 0,
+            ParameterProblemCode::UnrecognizedNextHeader => 1,
+            ParameterProblemCode::UnrecognizedIpv6Option => 2,
+            ParameterProblemCode::IncompleteHeaderChain => 3,
+            ParameterProblemCode::Unknown(code) => code,
+        }
+    }
+}
+
+/// Fixed IPv6 header (RFC 8200 section 3)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ipv6Header {
+    pub traffic_class: u8,
+    pub flow_label: u32,
+    pub payload_length: u16,
+    pub next_header: u8,
+    pub hop_limit: u8,
+    pub source: Ipv6Addr,
+    pub destination: Ipv6Addr,
+}
+
+impl Ipv6Header {
+    pub const LENGTH: usize = 40;
+
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < Self::LENGTH || data[0] >> 4 != 6 {
+            return None;
+        }
+
+        let source: [u8; 16] = data[8..24].try_into().ok()?;
+        let destination: [u8; 16] = data[24..40].try_into().ok()?;
+        Some(Self {
+            traffic_class: (data[0] << 4) | (data[1] >> 4),
+            flow_label: u32::from_be_bytes([0, data[1] & 0x0f, data[2], data[3]]),
+            payload_length: u16::from_be_bytes([data[4], data[5]]),
+            next_header: data[6],
+            hop_limit: data[7],
+            source: Ipv6Addr::from(source),
+            destination: Ipv6Addr::from(destination),
+        })
+    }
+
+    pub fn serialize(&self) -> [u8; Self::LENGTH] {
+        let mut data = [0u8; Self::LENGTH];
+        let flow_label = self.flow_label.to_be_bytes();
+        data[0] = 0x60 | (self.traffic_class >> 4);
+        data[1] = (self.traffic_class << 4) | (flow_label[1] & 0x0f);
+        data[2] = flow_label[2];
+        data[3] = flow_label[3];
+        data[4..6].copy_from_slice(&self.payload_length.to_be_bytes());
+        data[6] = self.next_header;
+        data[7] = self.hop_limit;
+        data[8..24].copy_from_slice(&self.source.octets());
+        data[24..40].copy_from_slice(&self.destination.octets());
+        data
+    }
+}
+
+/// As much of the packet that caused an error message as the sender included
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InvokingPacket {
+    /// `None` if the quoted bytes don't start with a complete IPv6 header
+    pub header: Option<Ipv6Header>,
+
+    /// bytes following the header, or everything quoted if the header couldn't be parsed
+    pub payload: Vec<u8>,
+}
+
+impl InvokingPacket {
+    pub fn parse(data: &[u8]) -> Self {
+        match Ipv6Header::parse(data) {
+            Some(header) => Self {
+                header: Some(header),
+                payload: data[Ipv6Header::LENGTH..].to_vec(),
+            },
+            None => Self {
+                header: None,
+                payload: data.to_vec(),
+            },
+        }
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        if let Some(header) = &self.header {
+            data.extend_from_slice(&header.serialize());
+        }
+        data.extend_from_slice(&self.payload);
+        data
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DestinationUnreachable {
+    pub code: DestinationUnreachableCode,
+    pub invoking_packet: InvokingPacket,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PacketTooBig {
+    pub mtu: u32,
+    pub invoking_packet: InvokingPacket,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimeExceeded {
+    pub code: TimeExceededCode,
+    pub invoking_packet: InvokingPacket,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParameterProblem {
+    pub code: ParameterProblemCode,
+
+    /// offset of the offending octet within the invoking packet
+    pub pointer: u32,
+
+    pub invoking_packet: InvokingPacket,
+}
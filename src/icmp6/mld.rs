@@ -1,6 +1,7 @@
 
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MulticastReportRecord {
     pub record_type: u8,
     //pub aux_data_len: u8,
@@ -9,7 +10,8 @@ pub struct MulticastReportRecord {
     //pub aux_data: Vec<u8>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MulticastListenerQuery {
     pub maximum_response_delay: u16,
     pub group_address: std::net::Ipv6Addr,
@@ -19,17 +21,137 @@ pub struct MulticastListenerQuery {
     pub source_addresses: Vec<std::net::Ipv6Addr>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct V1MulticastListenerReport {
     pub group_address: std::net::Ipv6Addr,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct V1MulticastListenerDone {
     pub group_address: std::net::Ipv6Addr,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct V2MulticastListenerReport {
     pub records: Vec<MulticastReportRecord>,
 }
+
+/// Maximum Response Delay in milliseconds for a Maximum Response Code (RFC 3810 section 5.1.3)
+pub fn decode_max_response_code(code: u16) -> u32 {
+    if code < 0x8000 {
+        return code as u32;
+    }
+    let exp = (code >> 12) & 0x07;
+    let mant = code & 0x0fff;
+    ((mant as u32) | 0x1000) << (exp + 3)
+}
+
+/// Smallest Maximum Response Code covering `delay` milliseconds, saturating at the largest encodable delay
+pub fn encode_max_response_code(delay: u32) -> u16 {
+    if delay < 0x8000 {
+        return delay as u16;
+    }
+    for exp in 0..8 {
+        let mant = delay >> (exp + 3);
+        if mant <= 0x1fff {
+            return 0x8000 | ((exp as u16) << 12) | (mant as u16 & 0x0fff);
+        }
+    }
+    0xffff
+}
+
+/// Querier's Query Interval in seconds for a QQIC (RFC 3810 section 5.1.9)
+pub fn decode_qqic(code: u8) -> u32 {
+    if code < 0x80 {
+        return code as u32;
+    }
+    let exp = (code >> 4) & 0x07;
+    let mant = code & 0x0f;
+    ((mant as u32) | 0x10) << (exp + 3)
+}
+
+/// QQIC for `interval` seconds, saturating at the largest encodable interval
+pub fn encode_qqic(interval: u32) -> u8 {
+    if interval < 0x80 {
+        return interval as u8;
+    }
+    for exp in 0..8 {
+        let mant = interval >> (exp + 3);
+        if mant <= 0x1f {
+            return 0x80 | ((exp as u8) << 4) | (mant as u8 & 0x0f);
+        }
+    }
+    0xff
+}
+
+/// Multicast Address Record types (RFC 3810 section 5.2.12)
+pub const MODE_IS_INCLUDE: u8 = 1;
+pub const MODE_IS_EXCLUDE: u8 = 2;
+pub const CHANGE_TO_INCLUDE_MODE: u8 = 3;
+pub const CHANGE_TO_EXCLUDE_MODE: u8 = 4;
+pub const ALLOW_NEW_SOURCES: u8 = 5;
+pub const BLOCK_OLD_SOURCES: u8 = 6;
+
+/// Largest MLD message that fits the minimum IPv6 MTU after the IPv6 header and the Router Alert;
+/// used when the link MTU is unknown
+pub const MAX_REPORT_LEN: usize = 1280 - 40 - 8;
+
+/// Largest MLD message that fits the link MTU after the IPv6 header and the Router Alert
+pub fn max_report_len(mtu: Option<u32>) -> usize {
+    match mtu {
+        Some(mtu) => (mtu as usize).saturating_sub(40 + 8).max(MAX_REPORT_LEN),
+        None => MAX_REPORT_LEN,
+    }
+}
+
+impl V2MulticastListenerReport {
+    /// Packs records into as few reports of at most `max_len` bytes as possible (RFC 3810 section 5.2.15).
+    /// Records with too many sources are split, except MODE_IS_EXCLUDE and CHANGE_TO_EXCLUDE_MODE records which are truncated.
+    pub fn split(records: Vec<MulticastReportRecord>, max_len: usize) -> Vec<Self> {
+        let max_sources = max_len.saturating_sub(8 + 20) / 16;
+        let mut reports = Vec::new();
+        let mut current = Vec::new();
+        let mut len = 8;
+
+        for record in records {
+            let mut parts = Vec::new();
+            if record.source_addresses.len() <= max_sources {
+                parts.push(record);
+            } else if record.record_type == MODE_IS_EXCLUDE || record.record_type == CHANGE_TO_EXCLUDE_MODE {
+                let mut record = record;
+                record.source_addresses.truncate(max_sources);
+                parts.push(record);
+            } else {
+                for source_addresses in record.source_addresses.chunks(max_sources.max(1)) {
+                    parts.push(MulticastReportRecord {
+                        record_type: record.record_type,
+                        multicast_address: record.multicast_address,
+                        source_addresses: source_addresses.to_vec(),
+                    });
+                }
+            }
+
+            for part in parts {
+                let part_len = 20 + part.source_addresses.len() * 16;
+                if len + part_len > max_len && !current.is_empty() {
+                    reports.push(Self {
+                        records: std::mem::take(&mut current),
+                    });
+                    len = 8;
+                }
+                len += part_len;
+                current.push(part);
+            }
+        }
+
+        if !current.is_empty() {
+            reports.push(Self {
+                records: current,
+            });
+        }
+        reports
+    }
+}
@@ -3,10 +3,15 @@ pub mod socket;
 pub mod packet;
 pub mod mld;
 pub mod ndp;
+pub mod checksum;
+pub mod error_message;
+pub mod view;
+pub mod mroute;
 
 pub use socket::RawIcmp6Socket;
 pub use socket::AsyncIcmp6Socket;
 
+use error_message::*;
 use mld::*;
 use ndp::*;
 
@@ -33,18 +38,21 @@ impl std::error::Error for Icmp6Error {}
 
 pub struct Icmp6Parser {
     pub(crate) packet: packet::Packet,
+    verify_checksum: bool,
 }
 
 impl Icmp6Parser {
     pub fn new() -> Self {
         Self {
             packet: packet::Packet::new(),
+            verify_checksum: false,
         }
     }
 
     pub fn new_from_packet(packet: packet::Packet) -> Self {
         Self {
             packet,
+            verify_checksum: false,
         }
     }
 
@@ -52,86 +60,125 @@ impl Icmp6Parser {
         &self.packet
     }
 
-    fn parse_ndp_options(&self, buf: &[u8]) -> Vec<NdpOption> {
-        let mut options = Vec::new();
-        let mut i = 0;
-        while i + 8 < buf.len() {
-            let option_type = buf[i];
-            let option_length = buf[i + 1];
-            let total_length = option_length as usize * 8;
-            if i + total_length > buf.len() {
-                break;
-            }
-            let option_data = buf[(i + 2)..(i + total_length)].to_vec();
-            options.push(NdpOption {
-                option_type,
-                option_data,
-            });
-            i += total_length;
-        }
-        options
+    /// Rejects packets with a bad checksum. Needs the destination address in the packet info,
+    /// which is not the case for kernel-verified packets from a raw socket.
+    pub fn set_verify_checksum(&mut self, verify: bool) {
+        self.verify_checksum = verify;
     }
 
-    /// TODO: index validation
-    pub fn parse(&self) -> Result<Icmp6Packet, Icmp6Error> {
+    /// Validates the hop-by-hop options, length and checksum.
+    /// Returns the parsed hop-by-hop options header, if any.
+    fn check_header(&self) -> Result<Option<packet::HopByHopOptions>, Icmp6Error> {
         let packet = &self.packet;
-        let ttl = packet.hop_limit.map(|hop_limit| hop_limit.hop_limit).unwrap_or(255);
+        let hop_by_hop = packet.hop_by_hop.as_ref().map(|hop_by_hop| hop_by_hop.options()).transpose()?;
 
-        let mut router_alert_mld = false;
-        if let Some(hop_by_hop) = &packet.hop_by_hop {
-            let data = &hop_by_hop.hop_by_hop;
-            if data.len() < 2 {
-                return Err(Icmp6Error::new("HBH: <bad length>"));
+        let data = packet.data();
+        if data.len() < 4 {
+            return Err(Icmp6Error::new("ICMPv6 packet too short"));
+        }
+        if self.verify_checksum {
+            let destination = if let Some(info) = &packet.info {
+                info.addr
             } else {
-                let _next_hdr = data[0];
-                let data = &data[2..];
-
-                let mut i = 0;
-                while i < data.len() {
-                    let hdr = data[i];
-                    if hdr == 0 {
-                        break;
-                    }
-                    let len = data[i + 1];
-                    let data = &data[(i + 2)..(i + 2 + len as usize)];
-                    match hdr {
-                        0x05 => {
-                            let value = u16::from_be_bytes([data[0], data[1]]);
-                            if value == 0 {
-                                router_alert_mld = true;
-                            }
-                        }
-                        0x01 => {}
-                        _ => {}
-                    }
-                    i += 2 + len as usize;
-                }
+                return Err(Icmp6Error::new("ICMPv6 checksum: no destination address"));
+            };
+            if checksum::icmp6_checksum(packet.target_addr, destination, data) != 0 {
+                return Err(Icmp6Error::new("ICMPv6 checksum mismatch"));
             }
         }
+        Ok(hop_by_hop)
+    }
 
-        let data = packet.data();
-        if data.len() < 4 {
-            return Err(Icmp6Error::new("ICMPv6 packet too short"));
+    /// MLD messages must be link-local with hop limit 1 and carry a Router Alert for MLD (RFC 3810 section 5)
+    fn check_mld(&self, hop_by_hop: &Option<packet::HopByHopOptions>) -> Result<(), Icmp6Error> {
+        let ttl = self.packet.hop_limit.map(|hop_limit| hop_limit.hop_limit).unwrap_or(255);
+        if ttl != 1 {
+            return Err(Icmp6Error::new("MLD packet with hop limit other than 1"));
+        }
+        let hop_by_hop = if let Some(hop_by_hop) = hop_by_hop {
+            hop_by_hop
+        } else {
+            return Err(Icmp6Error::new("MLD packet without hop-by-hop options"));
+        };
+        match hop_by_hop.router_alert() {
+            Some(packet::ROUTER_ALERT_MLD) => Ok(()),
+            Some(_) => Err(Icmp6Error::new("MLD packet with non-MLD router alert")),
+            None => Err(Icmp6Error::new("MLD packet without router alert")),
+        }
+    }
+
+    /// Borrowed view of the received packet, for handling NDP and MLD without allocating
+    pub fn parse_ref(&self) -> Result<view::Icmp6PacketRef<'_>, Icmp6Error> {
+        let hop_by_hop = self.check_header()?;
+        let data = self.packet.data();
+
+        match data[0] {
+            130 => {
+                self.check_mld(&hop_by_hop)?;
+                Ok(view::Icmp6PacketRef::MulticastListenerQuery(view::MulticastListenerQueryRef::new(data)?))
+            }
+            133 => Ok(view::Icmp6PacketRef::RouterSolicitation(view::RouterSolicitationRef::new(data)?)),
+            134 => Ok(view::Icmp6PacketRef::RouterAdvertisement(view::RouterAdvertisementRef::new(data)?)),
+            135 => Ok(view::Icmp6PacketRef::NeighborSolicitation(view::NeighborSolicitationRef::new(data)?)),
+            136 => Ok(view::Icmp6PacketRef::NeighborAdvertisement(view::NeighborAdvertisementRef::new(data)?)),
+            137 => Ok(view::Icmp6PacketRef::Redirect(view::RedirectRef::new(data)?)),
+            143 => {
+                self.check_mld(&hop_by_hop)?;
+                Ok(view::Icmp6PacketRef::V2MulticastListenerReport(view::V2MulticastListenerReportRef::new(data)?))
+            }
+            icmp6_type => Ok(view::Icmp6PacketRef::Other { icmp6_type, code: data[1], data }),
         }
+    }
+
+    /// Decodes the received packet. Malformed input is reported as `Icmp6Error`, never by panicking.
+    pub fn parse(&self) -> Result<Icmp6Packet, Icmp6Error> {
+        let hop_by_hop = self.check_header()?;
+        let data = self.packet.data();
         let icmp6_type = data[0];
         let icmp6_code = data[1];
 
         match icmp6_type {
             1 => {
-                Ok(Icmp6Packet::DestinationUnreachable(icmp6_code))
+                if data.len() < 8 {
+                    return Err(Icmp6Error::new("ICMPv6 packet too short"));
+                }
+                Ok(Icmp6Packet::DestinationUnreachable(DestinationUnreachable {
+                    code: icmp6_code.into(),
+                    invoking_packet: InvokingPacket::parse(&data[8..]),
+                }))
             }
 
             2 => {
+                if data.len() < 8 {
+                    return Err(Icmp6Error::new("ICMPv6 packet too short"));
+                }
                 let mtu = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
-                Ok(Icmp6Packet::PacketTooBig(mtu))
+                Ok(Icmp6Packet::PacketTooBig(PacketTooBig {
+                    mtu,
+                    invoking_packet: InvokingPacket::parse(&data[8..]),
+                }))
             }
 
             3 => {
-                Ok(Icmp6Packet::TimeExceeded(icmp6_code))
+                if data.len() < 8 {
+                    return Err(Icmp6Error::new("ICMPv6 packet too short"));
+                }
+                Ok(Icmp6Packet::TimeExceeded(TimeExceeded {
+                    code: icmp6_code.into(),
+                    invoking_packet: InvokingPacket::parse(&data[8..]),
+                }))
             }
 
             4 => {
-                Ok(Icmp6Packet::ParameterProblem(icmp6_code))
+                if data.len() < 8 {
+                    return Err(Icmp6Error::new("ICMPv6 packet too short"));
+                }
+                let pointer = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+                Ok(Icmp6Packet::ParameterProblem(ParameterProblem {
+                    code: icmp6_code.into(),
+                    pointer,
+                    invoking_packet: InvokingPacket::parse(&data[8..]),
+                }))
             }
 
             128 => {
@@ -156,75 +203,14 @@ impl Icmp6Parser {
 
             130 => {
                 // multicast listener query
-                if ttl != 1 {
-                    return Err(Icmp6Error::new("MLD packet TTL != 1"));
-                }
-                if !router_alert_mld {
-                    return Err(Icmp6Error::new("MLD packet with router alert"));
-                }
-                if data.len() < 24 {
-                    return Err(Icmp6Error::new("MLD packet too short"));
-                }
-                let maximum_response_delay = u16::from_be_bytes([data[4], data[5]]);
-                let group_address = std::net::Ipv6Addr::new(
-                    u16::from_be_bytes([data[8], data[9]]),
-                    u16::from_be_bytes([data[10], data[11]]),
-                    u16::from_be_bytes([data[12], data[13]]),
-                    u16::from_be_bytes([data[14], data[15]]),
-                    u16::from_be_bytes([data[16], data[17]]),
-                    u16::from_be_bytes([data[18], data[19]]),
-                    u16::from_be_bytes([data[20], data[21]]),
-                    u16::from_be_bytes([data[22], data[23]]),
-                );
-                let mut source_addresses = Vec::new();
-                if data.len() < 28 {
-                    Ok(Icmp6Packet::MulticastListenerQuery(MulticastListenerQuery {
-                        maximum_response_delay,
-                        group_address,
-                        supress_router_processing: false,
-                        qrv: 0,
-                        qqic: 0,
-                        source_addresses: Vec::new(),
-                    }))
-                } else {
-                    let supress_router_processing = data[24] & 0x80 != 0;
-                    let qrv = data[24] & 0x07;
-                    let qqic = data[25];
-                    let mut i = 28;
-                    while i + 15 < data.len() {
-                        let source_address = std::net::Ipv6Addr::new(
-                            u16::from_be_bytes([data[i], data[i + 1]]),
-                            u16::from_be_bytes([data[i + 2], data[i + 3]]),
-                            u16::from_be_bytes([data[i + 4], data[i + 5]]),
-                            u16::from_be_bytes([data[i + 6], data[i + 7]]),
-                            u16::from_be_bytes([data[i + 8], data[i + 9]]),
-                            u16::from_be_bytes([data[i + 10], data[i + 11]]),
-                            u16::from_be_bytes([data[i + 12], data[i + 13]]),
-                            u16::from_be_bytes([data[i + 14], data[i + 15]]),
-                        );
-                        source_addresses.push(source_address);
-                        i += 16;
-                    }
-
-                    Ok(Icmp6Packet::MulticastListenerQuery(MulticastListenerQuery {
-                        maximum_response_delay,
-                        group_address,
-                        supress_router_processing,
-                        qrv,
-                        qqic,
-                        source_addresses,
-                    }))
-                }
+                self.check_mld(&hop_by_hop)?;
+                let query = view::MulticastListenerQueryRef::new(data)?;
+                Ok(Icmp6Packet::MulticastListenerQuery(query.into_owned()))
             }
 
             131 => {
                 // v1 multicast listener report
-                if ttl != 1 {
-                    return Err(Icmp6Error::new("MLD packet TTL != 1"));
-                }
-                if !router_alert_mld {
-                    return Err(Icmp6Error::new("MLD packet with router alert"));
-                }
+                self.check_mld(&hop_by_hop)?;
                 if data.len() < 24 {
                     return Err(Icmp6Error::new("MLD packet too short"));
                 }
@@ -245,12 +231,7 @@ impl Icmp6Parser {
 
             132 => {
                 // v1 multicast listener done
-                if ttl != 1 {
-                    return Err(Icmp6Error::new("MLD packet TTL != 1"));
-                }
-                if !router_alert_mld {
-                    return Err(Icmp6Error::new("MLD packet with router alert"));
-                }
+                self.check_mld(&hop_by_hop)?;
                 if data.len() < 24 {
                     return Err(Icmp6Error::new("MLD packet too short"));
                 }
@@ -271,126 +252,32 @@ impl Icmp6Parser {
 
             133 => {
                 // router solicitation
-                if data.len() < 8 {
-                    return Err(Icmp6Error::new("NDP packet too short"));
-                }
-                let options = self.parse_ndp_options(&data[8..]);
-                Ok(Icmp6Packet::RouterSolicitation(RouterSolicitation {
-                    options,
-                }))
+                let solicitation = view::RouterSolicitationRef::new(data)?;
+                Ok(Icmp6Packet::RouterSolicitation(solicitation.into_owned()))
             }
 
             134 => {
                 // router advertisement
-                if data.len() < 16 {
-                    return Err(Icmp6Error::new("NDP packet too short"));
-                }
-                let hop_limit = data[4];
-                let managed_address_configuration = data[5] & 0x80 != 0;
-                let other_configuration = data[5] & 0x40 != 0;
-                let router_lifetime = u16::from_be_bytes([data[6], data[7]]);
-                let reachable_time = u32::from_be_bytes([data[8], data[9], data[10], data[11]]);
-                let retrans_timer = u32::from_be_bytes([data[12], data[13], data[14], data[15]]);
-                let options = self.parse_ndp_options(&data[16..]);
-                Ok(Icmp6Packet::RouterAdvertisement(RouterAdvertisement {
-                    hop_limit,
-                    managed_address_configuration,
-                    other_configuration,
-                    router_lifetime,
-                    reachable_time,
-                    retrans_timer,
-                    options,
-                }))
+                let advertisement = view::RouterAdvertisementRef::new(data)?;
+                Ok(Icmp6Packet::RouterAdvertisement(advertisement.into_owned()))
             }
 
             135 => {
                 // neighbor solicitation
-                if data.len() < 24 {
-                    return Err(Icmp6Error::new("NDP packet too short"));
-                }
-                let target_address = std::net::Ipv6Addr::new(
-                    u16::from_be_bytes([data[8], data[9]]),
-                    u16::from_be_bytes([data[10], data[11]]),
-                    u16::from_be_bytes([data[12], data[13]]),
-                    u16::from_be_bytes([data[14], data[15]]),
-                    u16::from_be_bytes([data[16], data[17]]),
-                    u16::from_be_bytes([data[18], data[19]]),
-                    u16::from_be_bytes([data[20], data[21]]),
-                    u16::from_be_bytes([data[22], data[23]]),
-                );
-                let options = self.parse_ndp_options(&data[24..]);
-                Ok(Icmp6Packet::NeighborSolicitation(NeighborSolicitation {
-                    target_address,
-                    options,
-                }))
+                let solicitation = view::NeighborSolicitationRef::new(data)?;
+                Ok(Icmp6Packet::NeighborSolicitation(solicitation.into_owned()))
             }
 
             136 => {
                 // neighbor advertisement
-                if data.len() < 24 {
-                    return Err(Icmp6Error::new("NDP packet too short"));
-                }
-
-                let flags = data[4];
-                let router = flags & 0x80 != 0;
-                let solicited = flags & 0x40 != 0;
-                let override_ = flags & 0x20 != 0;
-
-                let target_address = std::net::Ipv6Addr::new(
-                    u16::from_be_bytes([data[8], data[9]]),
-                    u16::from_be_bytes([data[10], data[11]]),
-                    u16::from_be_bytes([data[12], data[13]]),
-                    u16::from_be_bytes([data[14], data[15]]),
-                    u16::from_be_bytes([data[16], data[17]]),
-                    u16::from_be_bytes([data[18], data[19]]),
-                    u16::from_be_bytes([data[20], data[21]]),
-                    u16::from_be_bytes([data[22], data[23]]),
-                );
-
-                let options = self.parse_ndp_options(&data[24..]);
-                Ok(Icmp6Packet::NeighborAdvertisement(NeighborAdvertisement {
-                    router,
-                    solicited,
-                    override_,
-                    target_address,
-                    options,
-                }))
+                let advertisement = view::NeighborAdvertisementRef::new(data)?;
+                Ok(Icmp6Packet::NeighborAdvertisement(advertisement.into_owned()))
             }
 
             137 => {
                 // redirect
-                if data.len() < 40 {
-                    return Err(Icmp6Error::new("NDP packet too short"));
-                }
-
-                let target_address = std::net::Ipv6Addr::new(
-                    u16::from_be_bytes([data[8], data[9]]),
-                    u16::from_be_bytes([data[10], data[11]]),
-                    u16::from_be_bytes([data[12], data[13]]),
-                    u16::from_be_bytes([data[14], data[15]]),
-                    u16::from_be_bytes([data[16], data[17]]),
-                    u16::from_be_bytes([data[18], data[19]]),
-                    u16::from_be_bytes([data[20], data[21]]),
-                    u16::from_be_bytes([data[22], data[23]]),
-                );
-
-                let destination_address = std::net::Ipv6Addr::new(
-                    u16::from_be_bytes([data[24], data[25]]),
-                    u16::from_be_bytes([data[26], data[27]]),
-                    u16::from_be_bytes([data[28], data[29]]),
-                    u16::from_be_bytes([data[30], data[31]]),
-                    u16::from_be_bytes([data[32], data[33]]),
-                    u16::from_be_bytes([data[34], data[35]]),
-                    u16::from_be_bytes([data[36], data[37]]),
-                    u16::from_be_bytes([data[38], data[39]]),
-                );
-
-                let options = self.parse_ndp_options(&data[40..]);
-                Ok(Icmp6Packet::Redirect(Redirect {
-                    target_address,
-                    destination_address,
-                    options,
-                }))
+                let redirect = view::RedirectRef::new(data)?;
+                Ok(Icmp6Packet::Redirect(redirect.into_owned()))
             }
 
             138 => {
@@ -398,11 +285,25 @@ impl Icmp6Parser {
             }
 
             139 => {
-                Ok(Icmp6Packet::NodeInformationQuery)
+                if data.len() < 16 {
+                    return Err(Icmp6Error::new("Node Information packet too short"));
+                }
+                let qtype = u16::from_be_bytes([data[4], data[5]]);
+                let flags = u16::from_be_bytes([data[6], data[7]]);
+                let nonce = u64::from_be_bytes([data[8], data[9], data[10], data[11], data[12], data[13], data[14], data[15]]);
+                let data = data[16..].to_vec();
+                Ok(Icmp6Packet::NodeInformationQuery { code: icmp6_code, qtype, flags, nonce, data })
             }
 
             140 => {
-                Ok(Icmp6Packet::NodeInformationResponse)
+                if data.len() < 16 {
+                    return Err(Icmp6Error::new("Node Information packet too short"));
+                }
+                let qtype = u16::from_be_bytes([data[4], data[5]]);
+                let flags = u16::from_be_bytes([data[6], data[7]]);
+                let nonce = u64::from_be_bytes([data[8], data[9], data[10], data[11], data[12], data[13], data[14], data[15]]);
+                let data = data[16..].to_vec();
+                Ok(Icmp6Packet::NodeInformationResponse { code: icmp6_code, qtype, flags, nonce, data })
             }
 
             141 => {
@@ -415,71 +316,9 @@ impl Icmp6Parser {
 
             143 => {
                 // v2 multicast listener report
-                if ttl != 1 {
-                    return Err(Icmp6Error::new("MLD packet TTL != 1"));
-                }
-                if !router_alert_mld {
-                    return Err(Icmp6Error::new("MLD packet with router alert"));
-                }
-                if data.len() < 8 {
-                    return Err(Icmp6Error::new("MLDv2 packet too short"));
-                }
-
-                let numrecords = u16::from_be_bytes([data[6], data[7]]);
-                let mut records = Vec::new();
-
-                let mut offset = 8;
-                for _ in 0..numrecords {
-                    if offset + 20 > data.len() {
-                        return Err(Icmp6Error::new("MLDv2 packet too short"));
-                    }
-
-                    let record_type = data[offset];
-                    let aux_data_len = data[offset + 1];
-                    let numsources = u16::from_be_bytes([data[offset + 2], data[offset + 3]]);
-
-                    let end_offset = offset + 4 + 16 + 16 * numsources as usize + aux_data_len as usize;
-                    if end_offset > data.len() {
-                        return Err(Icmp6Error::new("MLDv2 packet too short"));
-                    }
-                    let multicast_address = std::net::Ipv6Addr::new(
-                        u16::from_be_bytes([data[offset + 4], data[offset + 5]]),
-                        u16::from_be_bytes([data[offset + 6], data[offset + 7]]),
-                        u16::from_be_bytes([data[offset + 8], data[offset + 9]]),
-                        u16::from_be_bytes([data[offset + 10], data[offset + 11]]),
-                        u16::from_be_bytes([data[offset + 12], data[offset + 13]]),
-                        u16::from_be_bytes([data[offset + 14], data[offset + 15]]),
-                        u16::from_be_bytes([data[offset + 16], data[offset + 17]]),
-                        u16::from_be_bytes([data[offset + 18], data[offset + 19]]),
-                    );
-
-                    let mut source_addresses = Vec::new();
-
-                    for j in 0..numsources {
-                        let source_address = std::net::Ipv6Addr::new(
-                            u16::from_be_bytes([data[offset + 4 + 16 + 16 * j as usize + 0], data[offset + 4 + 16 + 16 * j as usize + 1]]),
-                            u16::from_be_bytes([data[offset + 4 + 16 + 16 * j as usize + 2], data[offset + 4 + 16 + 16 * j as usize + 3]]),
-                            u16::from_be_bytes([data[offset + 4 + 16 + 16 * j as usize + 4], data[offset + 4 + 16 + 16 * j as usize + 5]]),
-                            u16::from_be_bytes([data[offset + 4 + 16 + 16 * j as usize + 6], data[offset + 4 + 16 + 16 * j as usize + 7]]),
-                            u16::from_be_bytes([data[offset + 4 + 16 + 16 * j as usize + 8], data[offset + 4 + 16 + 16 * j as usize + 9]]),
-                            u16::from_be_bytes([data[offset + 4 + 16 + 16 * j as usize + 10], data[offset + 4 + 16 + 16 * j as usize + 11]]),
-                            u16::from_be_bytes([data[offset + 4 + 16 + 16 * j as usize + 12], data[offset + 4 + 16 + 16 * j as usize + 13]]),
-                            u16::from_be_bytes([data[offset + 4 + 16 + 16 * j as usize + 14], data[offset + 4 + 16 + 16 * j as usize + 15]]),
-                        );
-                        source_addresses.push(source_address);
-                    }
-
-                    records.push(MulticastReportRecord {
-                        record_type,
-                        multicast_address,
-                        source_addresses,
-                    });
-
-                    offset = end_offset;
-                }
-                Ok(Icmp6Packet::V2MulticastListenerReport(V2MulticastListenerReport {
-                    records,
-                }))
+                self.check_mld(&hop_by_hop)?;
+                let report = view::V2MulticastListenerReportRef::new(data)?;
+                Ok(Icmp6Packet::V2MulticastListenerReport(report.into_owned()))
             }
 
             144 => {
@@ -507,11 +346,27 @@ impl Icmp6Parser {
             }
 
             160 => {
-                Ok(Icmp6Packet::ExtendedEchoRequest(icmp6_code))
+                if data.len() < 8 {
+                    return Err(Icmp6Error::new("ICMPv6 packet too short"));
+                }
+                let identifier = u16::from_be_bytes([data[4], data[5]]);
+                let sequence = data[6];
+                let local = data[7] & 0x01 != 0;
+                let data = data[8..].to_vec();
+                Ok(Icmp6Packet::ExtendedEchoRequest { identifier, sequence, local, data })
             }
 
             161 => {
-                Ok(Icmp6Packet::ExtendedEchoReply(icmp6_code))
+                if data.len() < 8 {
+                    return Err(Icmp6Error::new("ICMPv6 packet too short"));
+                }
+                let identifier = u16::from_be_bytes([data[4], data[5]]);
+                let sequence = data[6];
+                let state = data[7] >> 5;
+                let active = data[7] & 0x04 != 0;
+                let ipv4 = data[7] & 0x02 != 0;
+                let ipv6 = data[7] & 0x01 != 0;
+                Ok(Icmp6Packet::ExtendedEchoReply { code: icmp6_code, identifier, sequence, state, active, ipv4, ipv6 })
             }
 
             _ => {
@@ -522,24 +377,21 @@ impl Icmp6Parser {
 }
 
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Icmp6Packet {
     /// type 1
-    /// code
-    DestinationUnreachable(u8),
+    DestinationUnreachable(DestinationUnreachable),
 
     /// type 2
-    /// MTU
-    PacketTooBig(u32),
+    PacketTooBig(PacketTooBig),
 
     /// type 3
-    /// code
-    TimeExceeded(u8),
+    TimeExceeded(TimeExceeded),
 
     /// type 4
-    /// code
-    ParameterProblem(u8),
+    ParameterProblem(ParameterProblem),
 
     /// type 128
     EchoRequest { identifier: u16, sequence: u16, data: Vec<u8> },
@@ -575,11 +427,11 @@ pub enum Icmp6Packet {
     /// code
     RouterRenumbering(u8),
 
-    /// type 139
-    NodeInformationQuery,
+    /// type 139 (RFC 4620)
+    NodeInformationQuery { code: u8, qtype: u16, flags: u16, nonce: u64, data: Vec<u8> },
 
-    /// type 140
-    NodeInformationResponse,
+    /// type 140 (RFC 4620)
+    NodeInformationResponse { code: u8, qtype: u16, flags: u16, nonce: u64, data: Vec<u8> },
 
     /// type 141
     InverseNeighborDiscoverySolicitation,
@@ -610,28 +462,61 @@ pub enum Icmp6Packet {
     /// code
     DuplicateAddressConfirmation(u8),
 
-    /// type 160
-    /// code
-    ExtendedEchoRequest(u8),
+    /// type 160 (RFC 8335)
+    /// `data` is the ICMP extension structure identifying the probed interface
+    ExtendedEchoRequest { identifier: u16, sequence: u8, local: bool, data: Vec<u8> },
 
-    /// type 161
-    /// code
-    ExtendedEchoReply(u8),
+    /// type 161 (RFC 8335)
+    ExtendedEchoReply { code: u8, identifier: u16, sequence: u8, state: u8, active: bool, ipv4: bool, ipv6: bool },
 
     /// type, code
     Unknown(u8, u8),
 }
 
+/// IPv6 minimum MTU minus the IPv6 header
+pub const MAX_ERROR_MESSAGE_LEN: usize = 1280 - 40;
+
 #[derive(Debug)]
 pub struct Icmp6Writer {
     pub(crate) packet: packet::Packet,
+    compute_checksum: bool,
 }
 
 impl Icmp6Writer {
     pub fn new() -> Self {
         Self {
             packet: packet::Packet::new(),
+            compute_checksum: false,
+        }
+    }
+
+    pub fn packet(&self) -> &packet::Packet {
+        &self.packet
+    }
+
+    /// Fills in the checksum on `set_packet` instead of leaving it to the kernel.
+    /// The source address is taken from the packet info, so set it first.
+    pub fn set_compute_checksum(&mut self, compute: bool) {
+        self.compute_checksum = compute;
+    }
+
+    /// Recomputes the checksum of the current packet from the packet info and destination
+    pub fn update_checksum(&mut self) -> Result<(), Icmp6Error> {
+        let source = if let Some(info) = &self.packet.info {
+            info.addr
+        } else {
+            return Err(Icmp6Error::new("ICMPv6 checksum: no source address"));
+        };
+        let len = self.packet.data_len;
+        if len < 4 || len > self.packet.data.len() {
+            return Err(Icmp6Error::new("ICMPv6 packet too short"));
         }
+
+        self.packet.data[2] = 0;
+        self.packet.data[3] = 0;
+        let checksum = checksum::icmp6_checksum(source, self.packet.target_addr, &self.packet.data[..len]);
+        self.packet.data[2..4].copy_from_slice(&checksum.to_be_bytes());
+        Ok(())
     }
 
     pub fn set_destination(&mut self, addr: std::net::Ipv6Addr) {
@@ -642,8 +527,8 @@ impl Icmp6Writer {
         self.packet.hop_limit = hop_limit.map(|hop_limit| packet::PacketHopLimit { hop_limit });
     }
 
-    pub fn set_hop_by_hop(&mut self, hop_by_hop: Option<Vec<u8>>) {
-        self.packet.hop_by_hop = hop_by_hop.map(|hop_by_hop| packet::PacketHopByHop { hop_by_hop });
+    pub fn set_hop_by_hop(&mut self, hop_by_hop: Option<packet::HopByHopOptions>) {
+        self.packet.hop_by_hop = hop_by_hop.as_ref().map(packet::PacketHopByHop::from);
     }
 
     pub fn set_packet_info(&mut self, info: Option<packet::PacketInfo>) {
@@ -652,48 +537,67 @@ impl Icmp6Writer {
 
     pub fn setup_mld(&mut self) {
         self.set_hop_limit(Some(1));
-        let mut hop_by_hop = vec![0u8; 8];
+        self.set_hop_by_hop(Some(packet::HopByHopOptions::mld()));
+    }
 
-        // next header (MLD)
-        hop_by_hop[0] = 0x3a;
+    /// Error message with the invoking packet truncated to fit the minimum IPv6 MTU (RFC 4443 section 2.4)
+    fn write_error_message(&mut self, icmp6_type: u8, code: u8, word: [u8; 4], invoking_packet: &InvokingPacket) {
+        let mut data = vec![icmp6_type, code, 0, 0];
+        data.extend_from_slice(&word);
+        data.extend_from_slice(&invoking_packet.serialize());
+        data.truncate(MAX_ERROR_MESSAGE_LEN);
+        self.packet.data_len = data.len();
+        self.packet.data[..data.len()].copy_from_slice(&data);
+    }
 
-        // length
-        hop_by_hop[1] = 0x00;
+    /// Messages whose body is not decoded are written as a bare header with a zero reserved field
+    fn write_header_only(&mut self, icmp6_type: u8, code: u8) {
+        let data = [icmp6_type, code, 0, 0, 0, 0, 0, 0];
+        self.packet.data_len = data.len();
+        self.packet.data[..data.len()].copy_from_slice(&data);
+    }
 
-        // padding
-        hop_by_hop[2] = 0x01;
-        hop_by_hop[3] = 0x00;
+    fn write_node_information(&mut self, icmp6_type: u8, code: u8, qtype: u16, flags: u16, nonce: u64, payload: &[u8]) -> Result<(), Icmp6Error> {
+        if 16 + payload.len() > self.packet.data.len() {
+            return Err(Icmp6Error::new("Node Information packet too long"));
+        }
+        let mut data = vec![icmp6_type, code, 0, 0];
+        data.extend_from_slice(&qtype.to_be_bytes());
+        data.extend_from_slice(&flags.to_be_bytes());
+        data.extend_from_slice(&nonce.to_be_bytes());
+        data.extend_from_slice(payload);
+        self.packet.data_len = data.len();
+        self.packet.data[..data.len()].copy_from_slice(&data);
+        Ok(())
+    }
 
-        // router alert
-        hop_by_hop[4] = 0x05;
-        hop_by_hop[5] = 0x02;
-        hop_by_hop[6] = 0x00;
-        hop_by_hop[7] = 0x00;
+    /// Option with its type/length header and padding, or `None` if too long to send
+    fn serialize_ndp_option(option: &NdpOption) -> Option<Vec<u8>> {
+        let mut option_data = option.serialize();
+        let orig_len = option_data.len();
 
-        self.set_hop_by_hop(Some(hop_by_hop));
+        if orig_len > 1500 {
+            return None;
+        }
+        let rem = (2 + orig_len) % 8;
+        if rem != 0 {
+            option_data.resize(orig_len + 8 - rem, 0);
+        }
+
+        let option_length = ((option_data.len() + 2) / 8) as u8;
+        let mut data = vec![option.option_type(), option_length];
+        data.extend_from_slice(&option_data);
+        Some(data)
     }
 
     fn serialize_ndp_options(&self, options: &[NdpOption]) -> Vec<u8> {
         let mut data = Vec::new();
         for option in options {
-            let orig_len = option.option_data.len();
-
-            if orig_len > 1500 {
-                continue;
-            }
-            let rem = (2 + orig_len) % 8;
-            let option_data = if rem == 0 {
-                option.option_data.clone()
-            } else {
-                let mut option_data = Vec::new();
-                option_data.extend_from_slice(&option.option_data);
-                option_data.resize(orig_len + 8 - rem, 0);
+            let option_data = if let Some(option_data) = Self::serialize_ndp_option(option) {
                 option_data
+            } else {
+                continue;
             };
-
-            let option_length = ((option_data.len() + 2) / 8) as u8;
-            data.push(option.option_type);
-            data.push(option_length);
             data.extend_from_slice(&option_data);
             if data.len() > 1500 {
                 break;
@@ -702,7 +606,106 @@ impl Icmp6Writer {
         data
     }
 
+    /// Copies an already encoded message, e.g. `as_bytes()` of a received view, for patching before sending
+    pub fn set_raw_packet(&mut self, data: &[u8]) -> Result<(), Icmp6Error> {
+        if data.len() < 4 {
+            return Err(Icmp6Error::new("ICMPv6 packet too short"));
+        }
+        if data.len() > self.packet.data.len() {
+            return Err(Icmp6Error::new("ICMPv6 packet too long"));
+        }
+        self.packet.data_len = data.len();
+        self.packet.data[..data.len()].copy_from_slice(data);
+        if self.compute_checksum {
+            self.update_checksum()?;
+        }
+        Ok(())
+    }
+
+    /// Offset of the options area of the current NDP message
+    fn ndp_options_offset(&self) -> Result<usize, Icmp6Error> {
+        let offset = match self.packet.data[0] {
+            133 => 8,
+            134 => 16,
+            135 | 136 => 24,
+            137 => 40,
+            _ => return Err(Icmp6Error::new("not an NDP packet")),
+        };
+        if self.packet.data_len < offset || self.packet.data_len > self.packet.data.len() {
+            return Err(Icmp6Error::new("NDP packet too short"));
+        }
+        view::NdpOptionsRef::new(&self.packet.data[offset..self.packet.data_len])?;
+        Ok(offset)
+    }
+
+    /// Offsets and lengths of the options of a type in the current NDP message
+    fn find_ndp_options(&self, option_type: u8) -> Result<Vec<(usize, usize)>, Icmp6Error> {
+        let mut offset = self.ndp_options_offset()?;
+        let options = view::NdpOptionsRef::new(&self.packet.data[offset..self.packet.data_len])?;
+        let mut found = Vec::new();
+        for option in options {
+            let len = option.data.len() + 2;
+            if option.option_type == option_type {
+                found.push((offset, len));
+            }
+            offset += len;
+        }
+        Ok(found)
+    }
+
+    fn remove_ranges(&mut self, ranges: &[(usize, usize)]) {
+        for (offset, len) in ranges.iter().rev() {
+            self.packet.data.copy_within((offset + len)..self.packet.data_len, *offset);
+            self.packet.data_len -= len;
+        }
+    }
+
+    /// Removes every option of a type from the current NDP message in place
+    pub fn remove_ndp_options(&mut self, option_type: u8) -> Result<(), Icmp6Error> {
+        let found = self.find_ndp_options(option_type)?;
+        self.remove_ranges(&found);
+        if self.compute_checksum {
+            self.update_checksum()?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the options of the same type in the current NDP message, overwriting in place when the length matches
+    pub fn replace_ndp_option(&mut self, option: &NdpOption) -> Result<(), Icmp6Error> {
+        let option_data = if let Some(option_data) = Self::serialize_ndp_option(option) {
+            option_data
+        } else {
+            return Err(Icmp6Error::new("NDP option too long"));
+        };
+
+        let found = self.find_ndp_options(option.option_type())?;
+        if found.len() == 1 && found[0].1 == option_data.len() {
+            let offset = found[0].0;
+            self.packet.data[offset..(offset + option_data.len())].copy_from_slice(&option_data);
+        } else {
+            self.remove_ranges(&found);
+            let len = self.packet.data_len;
+            if len + option_data.len() > self.packet.data.len() {
+                return Err(Icmp6Error::new("ICMPv6 packet too long"));
+            }
+            self.packet.data[len..(len + option_data.len())].copy_from_slice(&option_data);
+            self.packet.data_len += option_data.len();
+        }
+        if self.compute_checksum {
+            self.update_checksum()?;
+        }
+        Ok(())
+    }
+
     pub fn set_packet(&mut self, packet: Icmp6Packet) -> Result<(), Icmp6Error> {
+        self.encode_packet(packet)?;
+        if self.compute_checksum {
+            self.update_checksum()?;
+        }
+        Ok(())
+    }
+
+    fn encode_packet(&mut self, packet: Icmp6Packet) -> Result<(), Icmp6Error> {
         // let mut data: Vec<u8> = Vec::new();
         match packet {
             Icmp6Packet::EchoRequest { identifier, sequence, data: payload } => {
@@ -817,10 +820,11 @@ impl Icmp6Writer {
                 data[7] = (count & 255) as u8;
 
                 for record in report.records {
-                    let sources_count = record.source_addresses.len() as u16;
-                    if sources_count > 80 {
+                    // the report is sized to the link MTU by the caller, see V2MulticastListenerReport::split
+                    if record.source_addresses.len() > u16::MAX as usize {
                         return Err(Icmp6Error::new("MLDv2 report source addresses too many"));
                     }
+                    let sources_count = record.source_addresses.len() as u16;
 
                     let mut record_data = vec![0u8; 20 + 16 * sources_count as usize];
                     record_data[0] = record.record_type;
@@ -867,7 +871,7 @@ impl Icmp6Writer {
                     }
 
                     data.extend_from_slice(&record_data);
-                    if data.len() > 1500 {
+                    if data.len() > self.packet.data.len() {
                         return Err(Icmp6Error::new("MLDv2 packet too long"));
                     }
                 }
@@ -1048,8 +1052,140 @@ impl Icmp6Writer {
                 Ok(())
             }
 
-            _ => {
-                return Err(Icmp6Error::new("ICMPv6 packet type is not supported"));
+            Icmp6Packet::DestinationUnreachable(error) => {
+                self.write_error_message(1, error.code.into(), [0; 4], &error.invoking_packet);
+                Ok(())
+            }
+
+            Icmp6Packet::PacketTooBig(error) => {
+                self.write_error_message(2, 0, error.mtu.to_be_bytes(), &error.invoking_packet);
+                Ok(())
+            }
+
+            Icmp6Packet::TimeExceeded(error) => {
+                self.write_error_message(3, error.code.into(), [0; 4], &error.invoking_packet);
+                Ok(())
+            }
+
+            Icmp6Packet::ParameterProblem(error) => {
+                self.write_error_message(4, error.code.into(), error.pointer.to_be_bytes(), &error.invoking_packet);
+                Ok(())
+            }
+
+            Icmp6Packet::V1MulticastListenerReport(report) => {
+                let mut data = vec![0u8; 24];
+                data[0] = 131;
+                data[8..24].copy_from_slice(&report.group_address.octets());
+                self.packet.data_len = data.len();
+                self.packet.data[..data.len()].copy_from_slice(&data);
+
+                self.setup_mld();
+                Ok(())
+            }
+
+            Icmp6Packet::V1MulticastListenerDone(done) => {
+                let mut data = vec![0u8; 24];
+                data[0] = 132;
+                data[8..24].copy_from_slice(&done.group_address.octets());
+                self.packet.data_len = data.len();
+                self.packet.data[..data.len()].copy_from_slice(&data);
+
+                self.setup_mld();
+                Ok(())
+            }
+
+            Icmp6Packet::NodeInformationQuery { code, qtype, flags, nonce, data } => {
+                self.write_node_information(139, code, qtype, flags, nonce, &data)
+            }
+
+            Icmp6Packet::NodeInformationResponse { code, qtype, flags, nonce, data } => {
+                self.write_node_information(140, code, qtype, flags, nonce, &data)
+            }
+
+            Icmp6Packet::ExtendedEchoRequest { identifier, sequence, local, data: payload } => {
+                if 8 + payload.len() > self.packet.data.len() {
+                    return Err(Icmp6Error::new("ICMPv6 packet too long"));
+                }
+                let mut data = vec![160, 0, 0, 0];
+                data.extend_from_slice(&identifier.to_be_bytes());
+                data.push(sequence);
+                data.push(if local { 0x01 } else { 0x00 });
+                data.extend_from_slice(&payload);
+                self.packet.data_len = data.len();
+                self.packet.data[..data.len()].copy_from_slice(&data);
+
+                Ok(())
+            }
+
+            Icmp6Packet::ExtendedEchoReply { code, identifier, sequence, state, active, ipv4, ipv6 } => {
+                let mut data = vec![161, code, 0, 0];
+                data.extend_from_slice(&identifier.to_be_bytes());
+                data.push(sequence);
+                let mut flags = (state & 0x07) << 5;
+                if active {
+                    flags |= 0x04;
+                }
+                if ipv4 {
+                    flags |= 0x02;
+                }
+                if ipv6 {
+                    flags |= 0x01;
+                }
+                data.push(flags);
+                self.packet.data_len = data.len();
+                self.packet.data[..data.len()].copy_from_slice(&data);
+
+                Ok(())
+            }
+
+            Icmp6Packet::RouterRenumbering(code) => {
+                self.write_header_only(138, code);
+                Ok(())
+            }
+
+            Icmp6Packet::InverseNeighborDiscoverySolicitation => {
+                self.write_header_only(141, 0);
+                Ok(())
+            }
+
+            Icmp6Packet::InverseNeighborDiscoveryAdvertisement => {
+                self.write_header_only(142, 0);
+                Ok(())
+            }
+
+            Icmp6Packet::HomeAgentAddressDiscoveryRequest => {
+                self.write_header_only(144, 0);
+                Ok(())
+            }
+
+            Icmp6Packet::HomeAgentAddressDiscoveryReply => {
+                self.write_header_only(145, 0);
+                Ok(())
+            }
+
+            Icmp6Packet::MobilePrefixSolicitation => {
+                self.write_header_only(146, 0);
+                Ok(())
+            }
+
+            Icmp6Packet::MobilePrefixAdvertisement => {
+                self.write_header_only(147, 0);
+                Ok(())
+            }
+
+            Icmp6Packet::DuplicateAddressRequest(code) => {
+                self.write_header_only(157, code);
+                Ok(())
+            }
+
+            Icmp6Packet::DuplicateAddressConfirmation(code) => {
+                self.write_header_only(158, code);
+                Ok(())
+            }
+
+            Icmp6Packet::Unknown(icmp6_type, code) => {
+                self.write_header_only(icmp6_type, code);
+                Ok(())
             }
         }
     }
@@ -0,0 +1,157 @@
+//! Forwarding counters of the kernel multicast routing table
+
+use super::socket::mifi_t;
+use super::AsyncIcmp6Socket;
+use crate::interface::InterfaceId;
+
+use std::net::Ipv6Addr;
+
+/// Counters of an MFC entry (`struct sioc_sg_req6`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SgCounters {
+    pub packets: u64,
+    pub bytes: u64,
+
+    /// packets received on a MIF other than the parent of the entry
+    pub wrong_if: u64,
+}
+
+impl std::ops::AddAssign for SgCounters {
+    fn add_assign(&mut self, other: Self) {
+        self.packets += other.packets;
+        self.bytes += other.bytes;
+        self.wrong_if += other.wrong_if;
+    }
+}
+
+/// Counters of a MIF (`struct sioc_mif_req6`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MifCounters {
+    pub in_packets: u64,
+    pub in_bytes: u64,
+    pub out_packets: u64,
+    pub out_bytes: u64,
+}
+
+/// MIFs and MFC entries of the kernel multicast routing table, as maintained by the MLD proxy
+pub trait MulticastRouter {
+    fn add_mif(&self, mif: mifi_t, if_index: InterfaceId) -> Result<(), std::io::Error>;
+    fn del_mif(&self, mif: mifi_t) -> Result<(), std::io::Error>;
+    fn add_mroute(&self, parent_mif: mifi_t, output_mifs: Vec<mifi_t>, group_addr: Ipv6Addr, source_addr: Ipv6Addr) -> Result<(), std::io::Error>;
+    fn del_mroute(&self, parent_mif: mifi_t, group_addr: Ipv6Addr, source_addr: Ipv6Addr) -> Result<(), std::io::Error>;
+    fn sg_counters(&self, group_addr: Ipv6Addr, source_addr: Ipv6Addr) -> Result<SgCounters, std::io::Error>;
+    fn mif_counters(&self, mif: mifi_t) -> Result<MifCounters, std::io::Error>;
+}
+
+impl MulticastRouter for AsyncIcmp6Socket {
+    fn add_mif(&self, mif: mifi_t, if_index: InterfaceId) -> Result<(), std::io::Error> {
+        self.multicast_add_vif(mif, if_index)
+    }
+
+    fn del_mif(&self, mif: mifi_t) -> Result<(), std::io::Error> {
+        self.multicast_del_vif(mif)
+    }
+
+    fn add_mroute(&self, parent_mif: mifi_t, output_mifs: Vec<mifi_t>, group_addr: Ipv6Addr, source_addr: Ipv6Addr) -> Result<(), std::io::Error> {
+        self.multicast_add_mroute(parent_mif, output_mifs, group_addr, source_addr)
+    }
+
+    fn del_mroute(&self, parent_mif: mifi_t, group_addr: Ipv6Addr, source_addr: Ipv6Addr) -> Result<(), std::io::Error> {
+        self.multicast_del_mroute(parent_mif, group_addr, source_addr)
+    }
+
+    fn sg_counters(&self, group_addr: Ipv6Addr, source_addr: Ipv6Addr) -> Result<SgCounters, std::io::Error> {
+        self.multicast_get_sg_count(group_addr, source_addr)
+    }
+
+    fn mif_counters(&self, mif: mifi_t) -> Result<MifCounters, std::io::Error> {
+        self.multicast_get_mif_count(mif)
+    }
+}
+
+/// Line of `/proc/net/ip6_mr_cache`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MfcCacheEntry {
+    pub group_addr: Ipv6Addr,
+    pub source_addr: Ipv6Addr,
+
+    /// `None` while the entry is unresolved
+    pub parent: Option<mifi_t>,
+
+    pub counters: SgCounters,
+
+    /// output MIFs and their TTL thresholds
+    pub output_mifs: Vec<(mifi_t, u8)>,
+}
+
+/// Line of `/proc/net/ip6_mr_vif`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MifEntry {
+    pub mif: mifi_t,
+    pub if_name: String,
+    pub counters: MifCounters,
+    pub flags: u32,
+}
+
+pub const MR_CACHE_PATH: &str = "/proc/net/ip6_mr_cache";
+pub const MR_VIF_PATH: &str = "/proc/net/ip6_mr_vif";
+
+fn parse_mr_cache_line(line: &str) -> Option<MfcCacheEntry> {
+    let mut fields = line.split_whitespace();
+    let group_addr = fields.next()?.parse().ok()?;
+    let source_addr = fields.next()?.parse().ok()?;
+    let parent = fields.next()?.parse::<i32>().ok()?;
+    let packets = fields.next()?.parse().ok()?;
+    let bytes = fields.next()?.parse().ok()?;
+    let wrong_if = fields.next()?.parse().ok()?;
+
+    let mut output_mifs = Vec::new();
+    for field in fields {
+        let (mif, ttl) = field.split_once(':')?;
+        output_mifs.push((mif.parse().ok()?, ttl.parse().ok()?));
+    }
+
+    Some(MfcCacheEntry {
+        group_addr,
+        source_addr,
+        parent: mifi_t::try_from(parent).ok(),
+        counters: SgCounters { packets, bytes, wrong_if },
+        output_mifs,
+    })
+}
+
+fn parse_mr_vif_line(line: &str) -> Option<MifEntry> {
+    let mut fields = line.split_whitespace();
+    let mif = fields.next()?.parse().ok()?;
+    let if_name = fields.next()?.to_string();
+    let in_bytes = fields.next()?.parse().ok()?;
+    let in_packets = fields.next()?.parse().ok()?;
+    let out_bytes = fields.next()?.parse().ok()?;
+    let out_packets = fields.next()?.parse().ok()?;
+    let flags = u32::from_str_radix(fields.next()?, 16).ok()?;
+
+    Some(MifEntry {
+        mif,
+        if_name,
+        counters: MifCounters { in_packets, in_bytes, out_packets, out_bytes },
+        flags,
+    })
+}
+
+/// Parses the contents of `/proc/net/ip6_mr_cache`, skipping the header and malformed lines
+pub fn parse_mr_cache(contents: &str) -> Vec<MfcCacheEntry> {
+    contents.lines().skip(1).filter_map(parse_mr_cache_line).collect()
+}
+
+/// Parses the contents of `/proc/net/ip6_mr_vif`, skipping the header and malformed lines
+pub fn parse_mr_vif(contents: &str) -> Vec<MifEntry> {
+    contents.lines().skip(1).filter_map(parse_mr_vif_line).collect()
+}
+
+pub async fn read_mr_cache() -> Result<Vec<MfcCacheEntry>, std::io::Error> {
+    Ok(parse_mr_cache(&tokio::fs::read_to_string(MR_CACHE_PATH).await?))
+}
+
+pub async fn read_mr_vif() -> Result<Vec<MifEntry>, std::io::Error> {
+    Ok(parse_mr_vif(&tokio::fs::read_to_string(MR_VIF_PATH).await?))
+}
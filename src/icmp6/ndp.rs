@@ -1,56 +1,395 @@
 
-#[derive(Clone)]
-pub struct NdpOption {
-    pub option_type: u8,
-    pub option_data: Vec<u8>,
+use std::net::Ipv6Addr;
+
+pub const ND_OPT_SOURCE_LINKADDR: u8 = 1;
+pub const ND_OPT_TARGET_LINKADDR: u8 = 2;
+pub const ND_OPT_PREFIX_INFORMATION: u8 = 3;
+pub const ND_OPT_REDIRECTED_HEADER: u8 = 4;
+pub const ND_OPT_MTU: u8 = 5;
+pub const ND_OPT_NONCE: u8 = 14;
+pub const ND_OPT_ROUTE_INFORMATION: u8 = 24;
+pub const ND_OPT_RDNSS: u8 = 25;
+pub const ND_OPT_DNSSL: u8 = 31;
+pub const ND_OPT_CAPTIVE_PORTAL: u8 = 37;
+pub const ND_OPT_PREF64: u8 = 38;
+
+/// Prefix Information option (RFC 4861 section 4.6.2)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PrefixInformation {
+    pub prefix_len: u8,
+
+    /// L flag
+    pub on_link: bool,
+
+    /// A flag
+    pub autonomous: bool,
+
+    /// R flag (RFC 6275 section 7.2)
+    pub router_address: bool,
+
+    pub valid_lifetime: u32,
+    pub preferred_lifetime: u32,
+    pub prefix: Ipv6Addr,
 }
 
-impl std::fmt::Debug for NdpOption {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self.option_type {
-            1 => {
-                // Source Link-layer Address
-                // MAC address format
-                let value = self.option_data.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":");
-                write!(f, "src LL: {}", value)
+/// Recursive DNS Server option (RFC 8106 section 5.1)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rdnss {
+    pub lifetime: u32,
+    pub servers: Vec<Ipv6Addr>,
+}
+
+/// DNS Search List option (RFC 8106 section 5.2)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Dnssl {
+    pub lifetime: u32,
+    pub domains: Vec<String>,
+}
+
+/// Route Information option (RFC 4191 section 2.3)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RouteInformation {
+    pub prefix_len: u8,
+
+    /// two-bit Prf value
+    pub preference: u8,
+
+    pub lifetime: u32,
+    pub prefix: Ipv6Addr,
+}
+
+/// PREF64 option (RFC 8781 section 4)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pref64 {
+    /// in units of 8 seconds, 13 bits
+    pub scaled_lifetime: u16,
+
+    /// one of 96, 64, 56, 48, 40 or 32
+    pub prefix_len: u8,
+
+    pub prefix: Ipv6Addr,
+}
+
+impl Pref64 {
+    const PREFIX_LENGTHS: [u8; 6] = [96, 64, 56, 48, 40, 32];
+
+    /// lifetime in seconds
+    pub fn lifetime(&self) -> u32 {
+        self.scaled_lifetime as u32 * 8
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+#[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NdpOption {
+    /// type 1
+    SourceLinkLayer(Vec<u8>),
+
+    /// type 2
+    TargetLinkLayer(Vec<u8>),
+
+    /// type 3
+    PrefixInformation(PrefixInformation),
+
+    /// type 4
+    /// original IP header and data
+    RedirectedHeader(Vec<u8>),
+
+    /// type 5
+    Mtu(u32),
+
+    /// type 25
+    Rdnss(Rdnss),
+
+    /// type 31
+    Dnssl(Dnssl),
+
+    /// type 24
+    RouteInformation(RouteInformation),
+
+    /// type 38
+    Pref64(Pref64),
+
+    /// type 14
+    Nonce(Vec<u8>),
+
+    /// type 37
+    /// URI of the captive portal API (RFC 8910)
+    CaptivePortal(String),
+
+    /// type, data (without the type and length octets)
+    Unknown(u8, Vec<u8>),
+}
+
+impl NdpOption {
+    pub fn option_type(&self) -> u8 {
+        match self {
+            NdpOption::SourceLinkLayer(_) => ND_OPT_SOURCE_LINKADDR,
+            NdpOption::TargetLinkLayer(_) => ND_OPT_TARGET_LINKADDR,
+            NdpOption::PrefixInformation(_) => ND_OPT_PREFIX_INFORMATION,
+            NdpOption::RedirectedHeader(_) => ND_OPT_REDIRECTED_HEADER,
+            NdpOption::Mtu(_) => ND_OPT_MTU,
+            NdpOption::Rdnss(_) => ND_OPT_RDNSS,
+            NdpOption::Dnssl(_) => ND_OPT_DNSSL,
+            NdpOption::RouteInformation(_) => ND_OPT_ROUTE_INFORMATION,
+            NdpOption::Pref64(_) => ND_OPT_PREF64,
+            NdpOption::Nonce(_) => ND_OPT_NONCE,
+            NdpOption::CaptivePortal(_) => ND_OPT_CAPTIVE_PORTAL,
+            NdpOption::Unknown(option_type, _) => *option_type,
+        }
+    }
+
+    /// Decodes the body of an option. Malformed options are kept as `Unknown`.
+    pub fn parse(option_type: u8, data: &[u8]) -> NdpOption {
+        Self::parse_typed(option_type, data).unwrap_or_else(|| NdpOption::Unknown(option_type, data.to_vec()))
+    }
+
+    fn parse_typed(option_type: u8, data: &[u8]) -> Option<NdpOption> {
+        let read_u32 = |offset: usize| -> Option<u32> {
+            Some(u32::from_be_bytes(data.get(offset..(offset + 4))?.try_into().ok()?))
+        };
+        let read_addr = |offset: usize| -> Option<Ipv6Addr> {
+            let octets: [u8; 16] = data.get(offset..(offset + 16))?.try_into().ok()?;
+            Some(Ipv6Addr::from(octets))
+        };
+
+        let option = match option_type {
+            ND_OPT_SOURCE_LINKADDR => NdpOption::SourceLinkLayer(data.to_vec()),
+
+            ND_OPT_TARGET_LINKADDR => NdpOption::TargetLinkLayer(data.to_vec()),
+
+            ND_OPT_PREFIX_INFORMATION => {
+                if data.len() != 30 {
+                    return None;
+                }
+                NdpOption::PrefixInformation(PrefixInformation {
+                    prefix_len: data[0],
+                    on_link: data[1] & 0x80 != 0,
+                    autonomous: data[1] & 0x40 != 0,
+                    router_address: data[1] & 0x20 != 0,
+                    valid_lifetime: read_u32(2)?,
+                    preferred_lifetime: read_u32(6)?,
+                    prefix: read_addr(14)?,
+                })
             }
 
-            2 => {
-                // Target Link-layer Address
-                // MAC address format
-                let value = self.option_data.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":");
-                write!(f, "tgt LL: {}", value)
+            ND_OPT_REDIRECTED_HEADER => NdpOption::RedirectedHeader(data.get(6..)?.to_vec()),
+
+            ND_OPT_MTU => {
+                if data.len() != 6 {
+                    return None;
+                }
+                NdpOption::Mtu(read_u32(2)?)
             }
 
-            3 => {
-                // prefix information
-                write!(f, "prefix [{}]", self.option_data.len())
+            ND_OPT_RDNSS => {
+                let addrs = data.get(6..)?;
+                if addrs.is_empty() || !addrs.len().is_multiple_of(16) {
+                    return None;
+                }
+                let servers = (6..data.len()).step_by(16).map(read_addr).collect::<Option<Vec<_>>>()?;
+                NdpOption::Rdnss(Rdnss {
+                    lifetime: read_u32(2)?,
+                    servers,
+                })
             }
 
-            4 => {
-                // Redirected Header
-                write!(f, "redirected header [{}]", self.option_data.len())
+            ND_OPT_DNSSL => {
+                let domains = crate::dhcp6::options::read_domain_list(data.get(6..)?).ok()?;
+                NdpOption::Dnssl(Dnssl {
+                    lifetime: read_u32(2)?,
+                    domains,
+                })
             }
 
-            5 => {
-                // MTU
-                write!(f, "MTU [{}]", self.option_data.len())
+            ND_OPT_ROUTE_INFORMATION => {
+                let prefix_len = *data.first()?;
+                let prefix_bytes = data.get(6..)?;
+                if prefix_len > 128 || prefix_bytes.len() > 16 || prefix_bytes.len() * 8 < prefix_len as usize {
+                    return None;
+                }
+                let mut octets = [0u8; 16];
+                octets[..prefix_bytes.len()].copy_from_slice(prefix_bytes);
+                NdpOption::RouteInformation(RouteInformation {
+                    prefix_len,
+                    preference: (data[1] >> 3) & 0b11,
+                    lifetime: read_u32(2)?,
+                    prefix: Ipv6Addr::from(octets),
+                })
             }
 
-            25 => {
-                // Recursive DNS Server
-                write!(f, "RDNSS [{}]", self.option_data.len())
+            ND_OPT_PREF64 => {
+                if data.len() != 14 {
+                    return None;
+                }
+                let value = u16::from_be_bytes([data[0], data[1]]);
+                let prefix_len = *Pref64::PREFIX_LENGTHS.get((value & 0b111) as usize)?;
+                let mut octets = [0u8; 16];
+                octets[..12].copy_from_slice(&data[2..14]);
+                NdpOption::Pref64(Pref64 {
+                    scaled_lifetime: value >> 3,
+                    prefix_len,
+                    prefix: Ipv6Addr::from(octets),
+                })
             }
 
-            31 => {
-                // DNSSL
-                write!(f, "DNSSL [{}]", self.option_data.len())
+            ND_OPT_NONCE => NdpOption::Nonce(data.to_vec()),
+
+            ND_OPT_CAPTIVE_PORTAL => {
+                let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+                if data[end..].iter().any(|b| *b != 0) {
+                    return None;
+                }
+                NdpOption::CaptivePortal(String::from_utf8(data[..end].to_vec()).ok()?)
+            }
+
+            _ => return None,
+        };
+        Some(option)
+    }
+
+    /// Encodes the body of an option; the writer adds type, length and padding.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        match self {
+            NdpOption::SourceLinkLayer(addr) | NdpOption::TargetLinkLayer(addr) => {
+                data.extend_from_slice(addr);
+            }
+
+            NdpOption::PrefixInformation(info) => {
+                data.push(info.prefix_len);
+                let mut flags = 0;
+                if info.on_link {
+                    flags |= 0x80;
+                }
+                if info.autonomous {
+                    flags |= 0x40;
+                }
+                if info.router_address {
+                    flags |= 0x20;
+                }
+                data.push(flags);
+                data.extend_from_slice(&info.valid_lifetime.to_be_bytes());
+                data.extend_from_slice(&info.preferred_lifetime.to_be_bytes());
+                data.extend_from_slice(&[0u8; 4]);
+                data.extend_from_slice(&info.prefix.octets());
+            }
+
+            NdpOption::RedirectedHeader(header) => {
+                data.extend_from_slice(&[0u8; 6]);
+                data.extend_from_slice(header);
+            }
+
+            NdpOption::Mtu(mtu) => {
+                data.extend_from_slice(&[0u8; 2]);
+                data.extend_from_slice(&mtu.to_be_bytes());
+            }
+
+            NdpOption::Rdnss(rdnss) => {
+                data.extend_from_slice(&[0u8; 2]);
+                data.extend_from_slice(&rdnss.lifetime.to_be_bytes());
+                for server in &rdnss.servers {
+                    data.extend_from_slice(&server.octets());
+                }
+            }
+
+            NdpOption::Dnssl(dnssl) => {
+                data.extend_from_slice(&[0u8; 2]);
+                data.extend_from_slice(&dnssl.lifetime.to_be_bytes());
+                crate::dhcp6::options::write_domain_list(&dnssl.domains, &mut data);
+            }
+
+            NdpOption::RouteInformation(route) => {
+                let prefix_bytes = match route.prefix_len {
+                    0 => 0,
+                    1..=64 => 8,
+                    _ => 16,
+                };
+                data.push(route.prefix_len);
+                data.push((route.preference & 0b11) << 3);
+                data.extend_from_slice(&route.lifetime.to_be_bytes());
+                data.extend_from_slice(&route.prefix.octets()[..prefix_bytes]);
+            }
+
+            NdpOption::Pref64(pref64) => {
+                let plc = Pref64::PREFIX_LENGTHS.iter().position(|len| *len == pref64.prefix_len).unwrap_or(0) as u16;
+                data.extend_from_slice(&((pref64.scaled_lifetime << 3) | plc).to_be_bytes());
+                data.extend_from_slice(&pref64.prefix.octets()[..12]);
+            }
+
+            NdpOption::Nonce(nonce) => {
+                data.extend_from_slice(nonce);
+            }
+
+            NdpOption::CaptivePortal(uri) => {
+                data.extend_from_slice(uri.as_bytes());
             }
 
-            _ => {
-                write!(f, "NdpOption({:#02x}) [{}]", self.option_type, self.option_data.len())
+            NdpOption::Unknown(_, value) => {
+                data.extend_from_slice(value);
             }
         }
+        data
+    }
+}
+
+fn fmt_link_layer_address(f: &mut std::fmt::Formatter, addr: &[u8]) -> std::fmt::Result {
+    // MAC address format
+    let value = addr.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":");
+    write!(f, "{}", value)
+}
+
+impl std::fmt::Debug for NdpOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NdpOption::SourceLinkLayer(addr) => {
+                write!(f, "src LL: ")?;
+                fmt_link_layer_address(f, addr)
+            }
+
+            NdpOption::TargetLinkLayer(addr) => {
+                write!(f, "tgt LL: ")?;
+                fmt_link_layer_address(f, addr)
+            }
+
+            NdpOption::PrefixInformation(info) => {
+                write!(f, "prefix {}/{} flags=", info.prefix, info.prefix_len)?;
+                if info.on_link {
+                    write!(f, "L")?;
+                }
+                if info.autonomous {
+                    write!(f, "A")?;
+                }
+                if info.router_address {
+                    write!(f, "R")?;
+                }
+                write!(f, " valid={} preferred={}", info.valid_lifetime, info.preferred_lifetime)
+            }
+
+            NdpOption::RedirectedHeader(header) => write!(f, "redirected header [{}]", header.len()),
+
+            NdpOption::Mtu(mtu) => write!(f, "MTU {}", mtu),
+
+            NdpOption::Rdnss(rdnss) => write!(f, "RDNSS {:?} lifetime={}", rdnss.servers, rdnss.lifetime),
+
+            NdpOption::Dnssl(dnssl) => write!(f, "DNSSL {:?} lifetime={}", dnssl.domains, dnssl.lifetime),
+
+            NdpOption::RouteInformation(route) => write!(f, "route {}/{} prf={} lifetime={}", route.prefix, route.prefix_len, route.preference, route.lifetime),
+
+            NdpOption::Pref64(pref64) => write!(f, "PREF64 {}/{} lifetime={}", pref64.prefix, pref64.prefix_len, pref64.lifetime()),
+
+            NdpOption::Nonce(nonce) => write!(f, "nonce [{}]", nonce.len()),
+
+            NdpOption::CaptivePortal(uri) => write!(f, "captive portal {}", uri),
+
+            NdpOption::Unknown(option_type, data) => write!(f, "NdpOption({:#02x}) [{}]", option_type, data.len()),
+        }
     }
 }
 
@@ -62,7 +401,8 @@ fn fmt_option_list(f: &mut std::fmt::Formatter, options: &[NdpOption]) -> std::f
     debug.finish()
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RouterSolicitation {
     pub options: Vec<NdpOption>,
 }
@@ -74,7 +414,8 @@ impl std::fmt::Debug for RouterSolicitation {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RouterAdvertisement {
     pub hop_limit: u8,
     pub managed_address_configuration: bool,
@@ -103,7 +444,8 @@ impl std::fmt::Debug for RouterAdvertisement {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NeighborSolicitation {
     pub target_address: std::net::Ipv6Addr,
     pub options: Vec<NdpOption>,
@@ -116,7 +458,8 @@ impl std::fmt::Debug for NeighborSolicitation {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NeighborAdvertisement {
     pub router: bool,
     pub solicited: bool,
@@ -142,7 +485,8 @@ impl std::fmt::Debug for NeighborAdvertisement {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Redirect {
     pub target_address: std::net::Ipv6Addr,
     pub destination_address: std::net::Ipv6Addr,
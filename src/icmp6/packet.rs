@@ -1,9 +1,11 @@
 
+use super::Icmp6Error;
 use crate::interface::InterfaceId;
 
 use std::fmt::Debug;
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PacketInfo {
     /// source address for sending, or destination address for receiving
     pub addr: std::net::Ipv6Addr,
@@ -13,6 +15,7 @@ pub struct PacketInfo {
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PacketHopLimit {
     pub hop_limit: u8,
 }
@@ -23,6 +26,157 @@ pub struct PacketHopByHop {
     pub hop_by_hop: Vec<u8>,
 }
 
+impl PacketHopByHop {
+    pub fn options(&self) -> Result<HopByHopOptions, Icmp6Error> {
+        HopByHopOptions::parse(&self.hop_by_hop)
+    }
+}
+
+impl From<&HopByHopOptions> for PacketHopByHop {
+    fn from(options: &HopByHopOptions) -> Self {
+        Self {
+            hop_by_hop: options.serialize(),
+        }
+    }
+}
+
+/// Router Alert value for MLD (RFC 2711)
+pub const ROUTER_ALERT_MLD: u16 = 0;
+
+/// Hop-by-Hop option TLV (RFC 8200 section 4.2)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HopByHopOption {
+    /// type 0
+    Pad1,
+
+    /// type 1, number of padding octets after the length
+    PadN(u8),
+
+    /// type 5 (RFC 2711)
+    RouterAlert(u16),
+
+    /// type 0xc2 (RFC 2675), payload length
+    Jumbo(u32),
+
+    /// type, data
+    Unknown(u8, Vec<u8>),
+}
+
+/// Hop-by-Hop Options header (RFC 8200 section 4.3)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HopByHopOptions {
+    pub next_header: u8,
+    pub options: Vec<HopByHopOption>,
+}
+
+impl HopByHopOptions {
+    /// Header sent with MLD messages (RFC 3810 section 5)
+    pub fn mld() -> Self {
+        Self {
+            next_header: 58,
+            options: vec![HopByHopOption::RouterAlert(ROUTER_ALERT_MLD)],
+        }
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self, Icmp6Error> {
+        if data.len() < 2 {
+            return Err(Icmp6Error::new("HBH: <bad length>"));
+        }
+        let next_header = data[0];
+        let len = (data[1] as usize + 1) * 8;
+        if len > data.len() {
+            return Err(Icmp6Error::new("HBH: <bad length>"));
+        }
+        let data = &data[2..len];
+
+        let mut options = Vec::new();
+        let mut i = 0;
+        while i < data.len() {
+            let option_type = data[i];
+            if option_type == 0 {
+                options.push(HopByHopOption::Pad1);
+                i += 1;
+                continue;
+            }
+            if i + 2 > data.len() {
+                return Err(Icmp6Error::new("HBH: option truncated"));
+            }
+            let option_len = data[i + 1] as usize;
+            if i + 2 + option_len > data.len() {
+                return Err(Icmp6Error::new("HBH: option truncated"));
+            }
+            let option_data = &data[(i + 2)..(i + 2 + option_len)];
+            let option = match option_type {
+                0x01 => HopByHopOption::PadN(option_len as u8),
+                0x05 => {
+                    if option_len != 2 {
+                        return Err(Icmp6Error::new("HBH: bad router alert length"));
+                    }
+                    HopByHopOption::RouterAlert(u16::from_be_bytes([option_data[0], option_data[1]]))
+                }
+                0xc2 => {
+                    if option_len != 4 {
+                        return Err(Icmp6Error::new("HBH: bad jumbo payload length"));
+                    }
+                    HopByHopOption::Jumbo(u32::from_be_bytes([option_data[0], option_data[1], option_data[2], option_data[3]]))
+                }
+                _ => HopByHopOption::Unknown(option_type, option_data.to_vec()),
+            };
+            options.push(option);
+            i += 2 + option_len;
+        }
+
+        Ok(Self { next_header, options })
+    }
+
+    /// Encodes the header, padding it to a multiple of 8 octets
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data = vec![self.next_header, 0];
+        for option in &self.options {
+            match option {
+                HopByHopOption::Pad1 => data.push(0),
+                HopByHopOption::PadN(len) => {
+                    data.extend_from_slice(&[0x01, *len]);
+                    data.resize(data.len() + *len as usize, 0);
+                }
+                HopByHopOption::RouterAlert(value) => {
+                    data.extend_from_slice(&[0x05, 2]);
+                    data.extend_from_slice(&value.to_be_bytes());
+                }
+                HopByHopOption::Jumbo(len) => {
+                    data.extend_from_slice(&[0xc2, 4]);
+                    data.extend_from_slice(&len.to_be_bytes());
+                }
+                HopByHopOption::Unknown(option_type, option_data) => {
+                    data.extend_from_slice(&[*option_type, option_data.len().min(255) as u8]);
+                    data.extend_from_slice(&option_data[..option_data.len().min(255)]);
+                }
+            }
+        }
+
+        match data.len() % 8 {
+            0 => {}
+            7 => data.push(0),
+            rem => {
+                let len = 8 - rem - 2;
+                data.extend_from_slice(&[0x01, len as u8]);
+                data.resize(data.len() + len, 0);
+            }
+        }
+        data[1] = (data.len() / 8 - 1).min(255) as u8;
+        data
+    }
+
+    pub fn router_alert(&self) -> Option<u16> {
+        self.options.iter().find_map(|option| match option {
+            HopByHopOption::RouterAlert(value) => Some(*value),
+            _ => None,
+        })
+    }
+}
+
 pub struct Packet {
     /// destination address for sending, or source address for receiving
     pub target_addr: std::net::Ipv6Addr,
@@ -1,4 +1,5 @@
 
+use super::mroute::{MifCounters, SgCounters};
 use super::packet;
 use crate::interface::InterfaceId;
 
@@ -70,6 +71,14 @@ impl RawIcmp6Socket {
         Ok(())
     }
 
+    unsafe fn ioctl<T: Sized>(&self, request: libc::c_ulong, arg: &mut T) -> Result<(), std::io::Error> {
+        let code = unsafe { libc::ioctl(self.socket, request as _, arg as *mut T as *mut libc::c_void) };
+        if code < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
     fn ipv6_mreq(&self, addr: std::net::Ipv6Addr, if_index: InterfaceId) -> libc::ipv6_mreq {
         libc::ipv6_mreq {
             ipv6mr_multiaddr: libc::in6_addr { s6_addr: addr.octets() },
@@ -186,6 +195,45 @@ impl RawIcmp6Socket {
         unsafe { self.setsockopt(Ipv6Opt::MRT6_DEL_MFC, &mfc) }
     }
 
+    // c_ulong counters are u32 on 32-bit targets
+    #[allow(clippy::useless_conversion)]
+    pub fn multicast_get_sg_count(&self, group_addr: Ipv6Addr, source_addr: Ipv6Addr) -> Result<SgCounters, std::io::Error> {
+        let mut req = sioc_sg_req6 {
+            src: libc::sockaddr_in6 { sin6_family: libc::AF_INET6 as libc::sa_family_t, sin6_port: 0, sin6_flowinfo: 0, sin6_addr: libc::in6_addr { s6_addr: source_addr.octets() }, sin6_scope_id: 0 },
+            grp: libc::sockaddr_in6 { sin6_family: libc::AF_INET6 as libc::sa_family_t, sin6_port: 0, sin6_flowinfo: 0, sin6_addr: libc::in6_addr { s6_addr: group_addr.octets() }, sin6_scope_id: 0 },
+            pktcnt: 0,
+            bytecnt: 0,
+            wrong_if: 0,
+        };
+
+        unsafe { self.ioctl(SIOCGETSGCNT_IN6, &mut req)? };
+        Ok(SgCounters {
+            packets: u64::from(req.pktcnt),
+            bytes: u64::from(req.bytecnt),
+            wrong_if: u64::from(req.wrong_if),
+        })
+    }
+
+    // c_ulong counters are u32 on 32-bit targets
+    #[allow(clippy::useless_conversion)]
+    pub fn multicast_get_mif_count(&self, vif: mifi_t) -> Result<MifCounters, std::io::Error> {
+        let mut req = sioc_mif_req6 {
+            mifi: vif,
+            icount: 0,
+            ocount: 0,
+            ibytes: 0,
+            obytes: 0,
+        };
+
+        unsafe { self.ioctl(SIOCGETMIFCNT_IN6, &mut req)? };
+        Ok(MifCounters {
+            in_packets: u64::from(req.icount),
+            in_bytes: u64::from(req.ibytes),
+            out_packets: u64::from(req.ocount),
+            out_bytes: u64::from(req.obytes),
+        })
+    }
+
     pub fn recv(&self, packet: &mut packet::Packet) -> Result<(), std::io::Error> {
         unsafe {
             let mut cmsg = [0u8; 1500];
@@ -254,9 +302,10 @@ impl RawIcmp6Socket {
         Ok(())
     }
 
-    pub fn recv_parser(&self, parser: &mut super::Icmp6Parser) -> Result<(), std::io::Error> {
+    /// Receives an ICMPv6 message into the parser, or returns the multicast routing upcall received instead
+    pub fn recv_parser(&self, parser: &mut super::Icmp6Parser) -> Result<Option<Mrt6Msg>, std::io::Error> {
         self.recv(&mut parser.packet)?;
-        Ok(())
+        Ok(Mrt6Msg::parse(parser.packet.data()))
     }
 
     pub fn send(&self, packet: &packet::Packet) -> Result<(), std::io::Error> {
@@ -413,9 +462,10 @@ impl AsyncIcmp6Socket {
         }
     }
 
-    pub async fn recv_parser(&self, parser: &mut super::Icmp6Parser) -> Result<(), std::io::Error> {
+    /// Receives an ICMPv6 message into the parser, or returns the multicast routing upcall received instead
+    pub async fn recv_parser(&self, parser: &mut super::Icmp6Parser) -> Result<Option<Mrt6Msg>, std::io::Error> {
         self.recv(&mut parser.packet).await?;
-        Ok(())
+        Ok(Mrt6Msg::parse(parser.packet.data()))
     }
 
     pub async fn send(&self, packet: &packet::Packet) -> Result<(), std::io::Error> {
@@ -463,6 +513,14 @@ impl AsyncIcmp6Socket {
     pub fn multicast_del_mroute(&self, parent_vif: mifi_t, group_addr: Ipv6Addr, source_addr: Ipv6Addr) -> Result<(), std::io::Error> {
         self.inner.get_ref().multicast_del_mroute(parent_vif, group_addr, source_addr)
     }
+
+    pub fn multicast_get_sg_count(&self, group_addr: Ipv6Addr, source_addr: Ipv6Addr) -> Result<SgCounters, std::io::Error> {
+        self.inner.get_ref().multicast_get_sg_count(group_addr, source_addr)
+    }
+
+    pub fn multicast_get_mif_count(&self, vif: mifi_t) -> Result<MifCounters, std::io::Error> {
+        self.inner.get_ref().multicast_get_mif_count(vif)
+    }
 }
 
 pub trait SocketOpt {
@@ -569,3 +627,96 @@ pub struct mf6cctl {
     pub mf6cc_parent: mifi_t,
     pub mf6cc_ifset: if_set,
 }
+
+pub const SIOCPROTOPRIVATE: libc::c_ulong = 0x89e0;
+pub const SIOCGETMIFCNT_IN6: libc::c_ulong = SIOCPROTOPRIVATE;
+pub const SIOCGETSGCNT_IN6: libc::c_ulong = SIOCPROTOPRIVATE + 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub struct sioc_sg_req6 {
+    pub src: libc::sockaddr_in6,
+    pub grp: libc::sockaddr_in6,
+    pub pktcnt: libc::c_ulong,
+    pub bytecnt: libc::c_ulong,
+    pub wrong_if: libc::c_ulong,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub struct sioc_mif_req6 {
+    pub mifi: mifi_t,
+    pub icount: libc::c_ulong,
+    pub ocount: libc::c_ulong,
+    pub ibytes: libc::c_ulong,
+    pub obytes: libc::c_ulong,
+}
+
+pub const MRT6MSG_NOCACHE: u8 = 1;
+pub const MRT6MSG_WRONGMIF: u8 = 2;
+pub const MRT6MSG_WHOLEPKT: u8 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub struct mrt6msg {
+    pub im6_mbz: u8,
+    pub im6_msgtype: u8,
+    pub im6_mif: mifi_t,
+    pub im6_pad: u32,
+    pub im6_src: libc::in6_addr,
+    pub im6_dst: libc::in6_addr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mrt6MsgType {
+    /// no MFC entry for a packet received on a MIF
+    NoCache,
+
+    /// packet received on a MIF other than the parent of its MFC entry
+    WrongMif,
+
+    /// whole packet for the PIM register MIF
+    WholePkt,
+
+    Unknown(u8),
+}
+
+impl From<u8> for Mrt6MsgType {
+    fn from(value: u8) -> Self {
+        match value {
+            MRT6MSG_NOCACHE => Self::NoCache,
+            MRT6MSG_WRONGMIF => Self::WrongMif,
+            MRT6MSG_WHOLEPKT => Self::WholePkt,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+/// Upcall from the kernel multicast routing code, delivered on the socket that enabled MRT6_INIT
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mrt6Msg {
+    pub msg_type: Mrt6MsgType,
+    pub mif: mifi_t,
+    pub source_addr: Ipv6Addr,
+    pub group_addr: Ipv6Addr,
+}
+
+impl Mrt6Msg {
+    /// Decodes an upcall; ICMPv6 messages never start with a zero type, which is `im6_mbz` here
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < std::mem::size_of::<mrt6msg>() || data[0] != 0 {
+            return None;
+        }
+
+        let msg = unsafe { std::ptr::read_unaligned(data.as_ptr() as *const mrt6msg) };
+        Some(Self {
+            msg_type: msg.im6_msgtype.into(),
+            mif: msg.im6_mif,
+            source_addr: msg.im6_src.s6_addr.into(),
+            group_addr: msg.im6_dst.s6_addr.into(),
+        })
+    }
+}
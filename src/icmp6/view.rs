@@ -0,0 +1,542 @@
+//! Borrowed views over received ICMPv6 messages.
+//! Constructors validate lengths once so accessors can't go out of bounds; nothing is copied until `into_owned`.
+
+use super::Icmp6Error;
+use super::mld::*;
+use super::ndp::*;
+
+use std::net::Ipv6Addr;
+
+fn read_addr(data: &[u8], offset: usize) -> Ipv6Addr {
+    let mut octets = [0u8; 16];
+    octets.copy_from_slice(&data[offset..(offset + 16)]);
+    Ipv6Addr::from(octets)
+}
+
+/// Option as found on the wire; `data` excludes the type and length octets but includes padding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NdpOptionRef<'a> {
+    pub option_type: u8,
+    pub data: &'a [u8],
+}
+
+impl NdpOptionRef<'_> {
+    pub fn into_owned(self) -> NdpOption {
+        NdpOption::parse(self.option_type, self.data)
+    }
+}
+
+/// NDP options area, decoded one option at a time
+#[derive(Debug, Clone, Copy)]
+pub struct NdpOptionsRef<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> NdpOptionsRef<'a> {
+    /// RFC 4861 section 4.6: options with zero length or running past the end invalidate the packet
+    pub fn new(data: &'a [u8]) -> Result<Self, Icmp6Error> {
+        let mut i = 0;
+        while i < data.len() {
+            if i + 2 > data.len() {
+                return Err(Icmp6Error::new("NDP option truncated"));
+            }
+            let option_length = data[i + 1];
+            if option_length == 0 {
+                return Err(Icmp6Error::new("NDP option with zero length"));
+            }
+            i += option_length as usize * 8;
+            if i > data.len() {
+                return Err(Icmp6Error::new("NDP option truncated"));
+            }
+        }
+        Ok(Self { data })
+    }
+
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn iter(&self) -> NdpOptionIter<'a> {
+        NdpOptionIter { data: self.data }
+    }
+
+    pub fn find(&self, option_type: u8) -> Option<NdpOptionRef<'a>> {
+        self.iter().find(|option| option.option_type == option_type)
+    }
+
+    pub fn into_owned(self) -> Vec<NdpOption> {
+        self.iter().map(NdpOptionRef::into_owned).collect()
+    }
+}
+
+impl<'a> IntoIterator for NdpOptionsRef<'a> {
+    type Item = NdpOptionRef<'a>;
+    type IntoIter = NdpOptionIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NdpOptionIter<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for NdpOptionIter<'a> {
+    type Item = NdpOptionRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // framing was validated by NdpOptionsRef::new
+        if self.data.len() < 2 {
+            return None;
+        }
+        let total_length = (self.data[1] as usize * 8).clamp(2, self.data.len());
+        let option = NdpOptionRef {
+            option_type: self.data[0],
+            data: &self.data[2..total_length],
+        };
+        self.data = &self.data[total_length..];
+        Some(option)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RouterSolicitationRef<'a> {
+    data: &'a [u8],
+    options: NdpOptionsRef<'a>,
+}
+
+impl<'a> RouterSolicitationRef<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, Icmp6Error> {
+        if data.len() < 8 {
+            return Err(Icmp6Error::new("NDP packet too short"));
+        }
+        let options = NdpOptionsRef::new(&data[8..])?;
+        Ok(Self { data, options })
+    }
+
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn options(&self) -> NdpOptionsRef<'a> {
+        self.options
+    }
+
+    pub fn into_owned(self) -> RouterSolicitation {
+        RouterSolicitation {
+            options: self.options.into_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RouterAdvertisementRef<'a> {
+    data: &'a [u8],
+    options: NdpOptionsRef<'a>,
+}
+
+impl<'a> RouterAdvertisementRef<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, Icmp6Error> {
+        if data.len() < 16 {
+            return Err(Icmp6Error::new("NDP packet too short"));
+        }
+        let options = NdpOptionsRef::new(&data[16..])?;
+        Ok(Self { data, options })
+    }
+
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn hop_limit(&self) -> u8 {
+        self.data[4]
+    }
+
+    pub fn managed_address_configuration(&self) -> bool {
+        self.data[5] & 0x80 != 0
+    }
+
+    pub fn other_configuration(&self) -> bool {
+        self.data[5] & 0x40 != 0
+    }
+
+    pub fn router_lifetime(&self) -> u16 {
+        u16::from_be_bytes([self.data[6], self.data[7]])
+    }
+
+    pub fn reachable_time(&self) -> u32 {
+        u32::from_be_bytes([self.data[8], self.data[9], self.data[10], self.data[11]])
+    }
+
+    pub fn retrans_timer(&self) -> u32 {
+        u32::from_be_bytes([self.data[12], self.data[13], self.data[14], self.data[15]])
+    }
+
+    pub fn options(&self) -> NdpOptionsRef<'a> {
+        self.options
+    }
+
+    pub fn into_owned(self) -> RouterAdvertisement {
+        RouterAdvertisement {
+            hop_limit: self.hop_limit(),
+            managed_address_configuration: self.managed_address_configuration(),
+            other_configuration: self.other_configuration(),
+            router_lifetime: self.router_lifetime(),
+            reachable_time: self.reachable_time(),
+            retrans_timer: self.retrans_timer(),
+            options: self.options.into_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct NeighborSolicitationRef<'a> {
+    data: &'a [u8],
+    options: NdpOptionsRef<'a>,
+}
+
+impl<'a> NeighborSolicitationRef<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, Icmp6Error> {
+        if data.len() < 24 {
+            return Err(Icmp6Error::new("NDP packet too short"));
+        }
+        let options = NdpOptionsRef::new(&data[24..])?;
+        Ok(Self { data, options })
+    }
+
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn target_address(&self) -> Ipv6Addr {
+        read_addr(self.data, 8)
+    }
+
+    pub fn options(&self) -> NdpOptionsRef<'a> {
+        self.options
+    }
+
+    pub fn into_owned(self) -> NeighborSolicitation {
+        NeighborSolicitation {
+            target_address: self.target_address(),
+            options: self.options.into_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct NeighborAdvertisementRef<'a> {
+    data: &'a [u8],
+    options: NdpOptionsRef<'a>,
+}
+
+impl<'a> NeighborAdvertisementRef<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, Icmp6Error> {
+        if data.len() < 24 {
+            return Err(Icmp6Error::new("NDP packet too short"));
+        }
+        let options = NdpOptionsRef::new(&data[24..])?;
+        Ok(Self { data, options })
+    }
+
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn router(&self) -> bool {
+        self.data[4] & 0x80 != 0
+    }
+
+    pub fn solicited(&self) -> bool {
+        self.data[4] & 0x40 != 0
+    }
+
+    pub fn override_(&self) -> bool {
+        self.data[4] & 0x20 != 0
+    }
+
+    pub fn target_address(&self) -> Ipv6Addr {
+        read_addr(self.data, 8)
+    }
+
+    pub fn options(&self) -> NdpOptionsRef<'a> {
+        self.options
+    }
+
+    pub fn into_owned(self) -> NeighborAdvertisement {
+        NeighborAdvertisement {
+            router: self.router(),
+            solicited: self.solicited(),
+            override_: self.override_(),
+            target_address: self.target_address(),
+            options: self.options.into_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RedirectRef<'a> {
+    data: &'a [u8],
+    options: NdpOptionsRef<'a>,
+}
+
+impl<'a> RedirectRef<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, Icmp6Error> {
+        if data.len() < 40 {
+            return Err(Icmp6Error::new("NDP packet too short"));
+        }
+        let options = NdpOptionsRef::new(&data[40..])?;
+        Ok(Self { data, options })
+    }
+
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn target_address(&self) -> Ipv6Addr {
+        read_addr(self.data, 8)
+    }
+
+    pub fn destination_address(&self) -> Ipv6Addr {
+        read_addr(self.data, 24)
+    }
+
+    pub fn options(&self) -> NdpOptionsRef<'a> {
+        self.options
+    }
+
+    pub fn into_owned(self) -> Redirect {
+        Redirect {
+            target_address: self.target_address(),
+            destination_address: self.destination_address(),
+            options: self.options.into_owned(),
+        }
+    }
+}
+
+/// Sequence of 16-byte addresses
+#[derive(Debug, Clone)]
+pub struct AddressIter<'a> {
+    chunks: std::slice::ChunksExact<'a, u8>,
+}
+
+impl<'a> AddressIter<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { chunks: data.chunks_exact(16) }
+    }
+}
+
+impl Iterator for AddressIter<'_> {
+    type Item = Ipv6Addr;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.chunks.next().map(|chunk| read_addr(chunk, 0))
+    }
+}
+
+impl ExactSizeIterator for AddressIter<'_> {
+    fn len(&self) -> usize {
+        self.chunks.len()
+    }
+}
+
+/// MLDv1 or MLDv2 query; the MLDv2 fields read as zero for an MLDv1 query
+#[derive(Debug, Clone, Copy)]
+pub struct MulticastListenerQueryRef<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> MulticastListenerQueryRef<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, Icmp6Error> {
+        if data.len() < 24 {
+            return Err(Icmp6Error::new("MLD packet too short"));
+        }
+        Ok(Self { data })
+    }
+
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn maximum_response_delay(&self) -> u16 {
+        u16::from_be_bytes([self.data[4], self.data[5]])
+    }
+
+    pub fn group_address(&self) -> Ipv6Addr {
+        read_addr(self.data, 8)
+    }
+
+    pub fn is_v2(&self) -> bool {
+        self.data.len() >= 28
+    }
+
+    pub fn supress_router_processing(&self) -> bool {
+        self.is_v2() && self.data[24] & 0x80 != 0
+    }
+
+    pub fn qrv(&self) -> u8 {
+        if self.is_v2() { self.data[24] & 0x07 } else { 0 }
+    }
+
+    pub fn qqic(&self) -> u8 {
+        if self.is_v2() { self.data[25] } else { 0 }
+    }
+
+    pub fn source_addresses(&self) -> AddressIter<'a> {
+        if self.is_v2() {
+            AddressIter::new(&self.data[28..])
+        } else {
+            AddressIter::new(&[])
+        }
+    }
+
+    pub fn into_owned(self) -> MulticastListenerQuery {
+        MulticastListenerQuery {
+            maximum_response_delay: self.maximum_response_delay(),
+            group_address: self.group_address(),
+            supress_router_processing: self.supress_router_processing(),
+            qrv: self.qrv(),
+            qqic: self.qqic(),
+            source_addresses: self.source_addresses().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MulticastReportRecordRef<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> MulticastReportRecordRef<'a> {
+    pub fn record_type(&self) -> u8 {
+        self.data[0]
+    }
+
+    pub fn multicast_address(&self) -> Ipv6Addr {
+        read_addr(self.data, 4)
+    }
+
+    pub fn source_addresses(&self) -> AddressIter<'a> {
+        let numsources = u16::from_be_bytes([self.data[2], self.data[3]]) as usize;
+        AddressIter::new(&self.data[20..(20 + 16 * numsources)])
+    }
+
+    pub fn into_owned(self) -> MulticastReportRecord {
+        MulticastReportRecord {
+            record_type: self.record_type(),
+            multicast_address: self.multicast_address(),
+            source_addresses: self.source_addresses().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MulticastReportRecordIter<'a> {
+    data: &'a [u8],
+    remaining: u16,
+}
+
+impl<'a> Iterator for MulticastReportRecordIter<'a> {
+    type Item = MulticastReportRecordRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // record lengths were validated by V2MulticastListenerReportRef::new
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let len = V2MulticastListenerReportRef::record_len(self.data)?;
+        let record = MulticastReportRecordRef { data: &self.data[..len] };
+        self.data = &self.data[len..];
+        Some(record)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct V2MulticastListenerReportRef<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> V2MulticastListenerReportRef<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, Icmp6Error> {
+        if data.len() < 8 {
+            return Err(Icmp6Error::new("MLDv2 packet too short"));
+        }
+
+        let numrecords = u16::from_be_bytes([data[6], data[7]]);
+        let mut offset = 8;
+        for _ in 0..numrecords {
+            offset += if let Some(len) = Self::record_len(&data[offset..]) {
+                len
+            } else {
+                return Err(Icmp6Error::new("MLDv2 packet too short"));
+            };
+        }
+        Ok(Self { data })
+    }
+
+    /// Length of the record at the start of `data`, if it fits
+    fn record_len(data: &[u8]) -> Option<usize> {
+        if data.len() < 20 {
+            return None;
+        }
+        let aux_data_len = data[1];
+        let numsources = u16::from_be_bytes([data[2], data[3]]);
+
+        // aux data length is in units of 32-bit words (RFC 3810 section 5.2.6)
+        let len = 4 + 16 + 16 * numsources as usize + 4 * aux_data_len as usize;
+        if len > data.len() {
+            None
+        } else {
+            Some(len)
+        }
+    }
+
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn records(&self) -> MulticastReportRecordIter<'a> {
+        MulticastReportRecordIter {
+            data: &self.data[8..],
+            remaining: u16::from_be_bytes([self.data[6], self.data[7]]),
+        }
+    }
+
+    pub fn into_owned(self) -> V2MulticastListenerReport {
+        V2MulticastListenerReport {
+            records: self.records().map(MulticastReportRecordRef::into_owned).collect(),
+        }
+    }
+}
+
+/// Borrowed counterpart of `Icmp6Packet` for the message types seen on the hot path
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub enum Icmp6PacketRef<'a> {
+    /// type 130
+    MulticastListenerQuery(MulticastListenerQueryRef<'a>),
+
+    /// type 133
+    RouterSolicitation(RouterSolicitationRef<'a>),
+
+    /// type 134
+    RouterAdvertisement(RouterAdvertisementRef<'a>),
+
+    /// type 135
+    NeighborSolicitation(NeighborSolicitationRef<'a>),
+
+    /// type 136
+    NeighborAdvertisement(NeighborAdvertisementRef<'a>),
+
+    /// type 137
+    Redirect(RedirectRef<'a>),
+
+    /// type 143
+    V2MulticastListenerReport(V2MulticastListenerReportRef<'a>),
+
+    /// any other type; use `Icmp6Parser::parse` to decode it
+    Other { icmp6_type: u8, code: u8, data: &'a [u8] },
+}
@@ -32,6 +32,8 @@ pub fn name_to_index(name: &str) -> Result<InterfaceId, std::io::Error> {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Hash, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct InterfaceId {
     if_index: libc::c_uint,
 }
@@ -61,10 +63,17 @@ impl InterfaceId {
     }
 }
 
+impl From<libc::c_uint> for InterfaceId {
+    fn from(if_index: libc::c_uint) -> Self {
+        Self::new(if_index)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Interface {
     pub if_id: InterfaceId,
     pub if_name: String,
+    pub mtu: Option<u32>,
 }
 
 #[derive(Debug)]
@@ -173,6 +182,10 @@ impl InterfaceStateManager {
         self.state.interfaces.read().get(&if_index).map(|v| v.if_name.clone())
     }
 
+    pub fn get_mtu(&self, if_index: InterfaceId) -> Option<u32> {
+        self.state.interfaces.read().get(&if_index).and_then(|v| v.mtu)
+    }
+
     pub fn get_link_local_addrs(&self, if_index: InterfaceId) -> Option<Vec<std::net::Ipv6Addr>> {
         self.state.link_local_addrs.read().get(&if_index).map(|v| v.clone())
     }
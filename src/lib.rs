@@ -3,6 +3,9 @@ pub mod icmp6;
 pub mod group;
 pub mod interface;
 pub mod config;
+pub mod dhcp6;
+pub mod ra;
+pub mod querier;
 
 pub mod rtnl;
 pub mod util;
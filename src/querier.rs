@@ -0,0 +1,359 @@
+use crate::config::MldQuerierConfig;
+use crate::icmp6::mld::{self, MulticastListenerQuery};
+use crate::icmp6::AsyncIcmp6Socket;
+use crate::interface::{InterfaceId, InterfaceStateManager};
+use crate::ra::ALL_NODES;
+
+use parking_lot::{Mutex, RwLock};
+use tokio::time::Instant;
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::Ipv6Addr;
+use std::time::Duration;
+
+/// How the querier sends its queries and which address it sends them from
+pub trait QueryTransport: Send + Sync {
+    /// Link-local address of the interface, which also decides the querier election
+    fn link_local_addr(&self, if_id: InterfaceId) -> Option<Ipv6Addr>;
+
+    fn send_query(&self, if_id: InterfaceId, source: Ipv6Addr, destination: Ipv6Addr, query: MulticastListenerQuery) -> impl Future<Output = Result<(), std::io::Error>> + Send;
+}
+
+/// Queries sent on the ICMPv6 socket from the link-local addresses the interface manager knows of
+#[derive(Debug)]
+pub struct SocketQueryTransport {
+    socket: AsyncIcmp6Socket,
+    if_manager: InterfaceStateManager,
+}
+
+impl QueryTransport for SocketQueryTransport {
+    fn link_local_addr(&self, if_id: InterfaceId) -> Option<Ipv6Addr> {
+        self.if_manager.get_link_local_addr(if_id)
+    }
+
+    async fn send_query(&self, if_id: InterfaceId, source: Ipv6Addr, destination: Ipv6Addr, query: MulticastListenerQuery) -> Result<(), std::io::Error> {
+        let mut writer = crate::icmp6::Icmp6Writer::new();
+        writer.set_destination(destination);
+        writer.set_packet_info(Some(crate::icmp6::packet::PacketInfo {
+            if_index: if_id,
+            addr: source,
+        }));
+        writer.set_packet(crate::icmp6::Icmp6Packet::MulticastListenerQuery(query)).map_err(std::io::Error::other)?;
+        self.socket.send_writer(&writer).await
+    }
+}
+
+/// Per-interface querier state (RFC 3810 section 7.6)
+#[derive(Debug, Clone)]
+struct QuerierState {
+    /// router that won the election, `None` while we are the querier
+    other_querier: Option<Ipv6Addr>,
+
+    /// Other Querier Present timer
+    other_querier_expires: Instant,
+
+    startup_sent: u32,
+    next_general_query: Instant,
+
+    /// Robustness Variable and Query Interval in use, adopted from the querier while we are not it
+    robustness: u8,
+    query_interval: Duration,
+}
+
+impl QuerierState {
+    fn new(config: &MldQuerierConfig, now: Instant) -> Self {
+        Self {
+            other_querier: None,
+            other_querier_expires: now,
+            startup_sent: 0,
+            next_general_query: now,
+            robustness: config.robustness,
+            query_interval: config.query_interval(),
+        }
+    }
+}
+
+/// Group-Specific or Group-and-Source-Specific query still to be retransmitted (RFC 3810 section 7.6.3)
+#[derive(Debug, Clone)]
+struct SpecificQuery {
+    if_id: InterfaceId,
+    group_addr: Ipv6Addr,
+    source_addrs: Vec<Ipv6Addr>,
+    remaining: u8,
+    next: Instant,
+}
+
+/// MLDv2 querier on downstream interfaces
+#[derive(Debug)]
+pub struct MldQuerier<T = SocketQueryTransport> {
+    transport: T,
+    config: RwLock<MldQuerierConfig>,
+    states: Mutex<HashMap<InterfaceId, QuerierState>>,
+    specific_queries: Mutex<Vec<SpecificQuery>>,
+    wakeup: tokio::sync::Notify,
+}
+
+impl MldQuerier {
+    pub fn new(socket: AsyncIcmp6Socket, if_manager: InterfaceStateManager, interfaces: &[InterfaceId], config: MldQuerierConfig) -> Self {
+        Self::with_transport(SocketQueryTransport { socket, if_manager }, interfaces, config)
+    }
+}
+
+impl<T: QueryTransport> MldQuerier<T> {
+    pub fn with_transport(transport: T, interfaces: &[InterfaceId], config: MldQuerierConfig) -> Self {
+        let now = Instant::now();
+        let states = interfaces.iter().map(|if_id| (*if_id, QuerierState::new(&config, now))).collect();
+
+        Self {
+            transport,
+            config: RwLock::new(config),
+            states: Mutex::new(states),
+            specific_queries: Mutex::new(Vec::new()),
+            wakeup: tokio::sync::Notify::new(),
+        }
+    }
+
+    /// Starts or stops querying on interfaces after a configuration change
+    pub fn set_interfaces(&self, interfaces: &[InterfaceId]) {
+        let now = Instant::now();
+        let config = self.config.read().clone();
+        let mut states = self.states.lock();
+        states.retain(|if_id, _| interfaces.contains(if_id));
+        for if_id in interfaces {
+            states.entry(*if_id).or_insert_with(|| QuerierState::new(&config, now));
+        }
+        drop(states);
+        self.specific_queries.lock().retain(|query| interfaces.contains(&query.if_id));
+        self.wakeup.notify_one();
+    }
+
+    pub fn set_config(&self, config: MldQuerierConfig) {
+        if *self.config.read() == config {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut states = self.states.lock();
+        for state in states.values_mut() {
+            if state.other_querier.is_none() {
+                *state = QuerierState::new(&config, now);
+            }
+        }
+        drop(states);
+        *self.config.write() = config;
+        self.wakeup.notify_one();
+    }
+
+    pub fn is_querier(&self, if_id: InterfaceId) -> bool {
+        self.states.lock().get(&if_id).map(|state| state.other_querier.is_none()).unwrap_or(false)
+    }
+
+    /// Querier election on a query heard on a downstream interface (RFC 3810 section 7.6.2)
+    pub fn receive_query(&self, if_id: InterfaceId, source: Ipv6Addr, query: &MulticastListenerQuery) {
+        if !source.is_unicast_link_local() {
+            log::debug!("Ignoring Multicast Listener Query from non-link-local address: {}", source);
+            return;
+        }
+
+        let own_addr = self.transport.link_local_addr(if_id);
+        if own_addr.map(|own_addr| source >= own_addr).unwrap_or(false) {
+            return;
+        }
+
+        let config = self.config.read().clone();
+        let mut states = self.states.lock();
+        let state = if let Some(state) = states.get_mut(&if_id) {
+            state
+        } else {
+            return;
+        };
+
+        if state.other_querier != Some(source) {
+            log::info!("Multicast Listener Querier on {:?} is now {}", if_id, source);
+        }
+        state.other_querier = Some(source);
+
+        // RFC 3810 section 9.1 and 9.2: non-queriers use the querier's values
+        if query.qrv != 0 {
+            state.robustness = query.qrv;
+        }
+        if query.qqic != 0 {
+            state.query_interval = Duration::from_secs(mld::decode_qqic(query.qqic) as u64);
+        }
+
+        let other_querier_present_interval = state.query_interval * state.robustness as u32 + config.query_response_interval() / 2;
+        state.other_querier_expires = Instant::now() + other_querier_present_interval;
+        drop(states);
+
+        self.specific_queries.lock().retain(|query| query.if_id != if_id);
+        self.wakeup.notify_one();
+    }
+
+    /// Sends Group-Specific queries after listeners reported leaving a group
+    pub fn query_group(&self, if_id: InterfaceId, group_addr: Ipv6Addr) {
+        self.schedule_specific_query(if_id, group_addr, Vec::new());
+    }
+
+    /// Sends Group-and-Source-Specific queries after listeners reported blocking sources
+    pub fn query_sources(&self, if_id: InterfaceId, group_addr: Ipv6Addr, source_addrs: Vec<Ipv6Addr>) {
+        if source_addrs.is_empty() {
+            return;
+        }
+        self.schedule_specific_query(if_id, group_addr, source_addrs);
+    }
+
+    fn schedule_specific_query(&self, if_id: InterfaceId, group_addr: Ipv6Addr, source_addrs: Vec<Ipv6Addr>) {
+        let robustness = if let Some(state) = self.states.lock().get(&if_id) {
+            if state.other_querier.is_some() {
+                return;
+            }
+            state.robustness
+        } else {
+            return;
+        };
+
+        let now = Instant::now();
+        let mut specific_queries = self.specific_queries.lock();
+        let pending = specific_queries.iter_mut().find(|query| {
+            query.if_id == if_id && query.group_addr == group_addr && query.source_addrs.is_empty() == source_addrs.is_empty()
+        });
+
+        // Last Listener Query Count defaults to the Robustness Variable
+        if let Some(pending) = pending {
+            for source_addr in source_addrs {
+                if !pending.source_addrs.contains(&source_addr) {
+                    pending.source_addrs.push(source_addr);
+                }
+            }
+            pending.remaining = robustness;
+            pending.next = pending.next.min(now);
+        } else {
+            specific_queries.push(SpecificQuery {
+                if_id,
+                group_addr,
+                source_addrs,
+                remaining: robustness,
+                next: now,
+            });
+        }
+        drop(specific_queries);
+        self.wakeup.notify_one();
+    }
+
+    async fn send(&self, if_id: InterfaceId, destination: Ipv6Addr, query: MulticastListenerQuery) -> Result<(), std::io::Error> {
+        let source = if let Some(source) = self.transport.link_local_addr(if_id) {
+            source
+        } else {
+            return Err(std::io::Error::new(std::io::ErrorKind::AddrNotAvailable, "no link-local address"));
+        };
+
+        self.transport.send_query(if_id, source, destination, query).await
+    }
+
+    fn general_query(&self, robustness: u8, query_interval: Duration) -> MulticastListenerQuery {
+        let config = self.config.read();
+        MulticastListenerQuery {
+            maximum_response_delay: mld::encode_max_response_code(config.query_response_interval),
+            group_address: Ipv6Addr::UNSPECIFIED,
+            supress_router_processing: false,
+            qrv: if robustness <= 7 { robustness } else { 0 },
+            qqic: mld::encode_qqic(query_interval.as_secs() as u32),
+            source_addresses: Vec::new(),
+        }
+    }
+
+    pub async fn run(&self) -> Result<(), std::io::Error> {
+        loop {
+            let now = Instant::now();
+            let config = self.config.read().clone();
+
+            let mut due = Vec::new();
+            for (if_id, state) in self.states.lock().iter_mut() {
+                if state.other_querier.is_some() && state.other_querier_expires <= now {
+                    log::info!("Other Multicast Listener Querier on {:?} timed out, resuming querier role", if_id);
+                    *state = QuerierState::new(&config, now);
+                    // a returning querier is not starting up
+                    state.startup_sent = state.robustness as u32;
+                }
+                if state.other_querier.is_none() && state.next_general_query <= now {
+                    due.push((*if_id, state.robustness, state.query_interval));
+                }
+            }
+
+            for (if_id, robustness, query_interval) in due {
+                let query = self.general_query(robustness, query_interval);
+                let result = self.send(if_id, ALL_NODES, query).await;
+                if let Err(e) = &result {
+                    log::warn!("Failed to send General Query on {:?}: {:?}", if_id, e);
+                }
+
+                let mut states = self.states.lock();
+                let state = if let Some(state) = states.get_mut(&if_id) {
+                    state
+                } else {
+                    continue;
+                };
+                if result.is_err() {
+                    state.next_general_query = now + Duration::from_secs(1);
+                    continue;
+                }
+
+                // Startup Query Count and Startup Query Interval (RFC 3810 section 9.6 and 9.7)
+                state.startup_sent = state.startup_sent.saturating_add(1);
+                state.next_general_query = if state.startup_sent < state.robustness as u32 {
+                    now + state.query_interval / 4
+                } else {
+                    now + state.query_interval
+                };
+            }
+
+            let due_specific = self.specific_queries.lock().iter()
+                .filter(|query| query.next <= now)
+                .cloned()
+                .collect::<Vec<_>>();
+
+            for specific in due_specific {
+                if !self.is_querier(specific.if_id) {
+                    continue;
+                }
+
+                let query = MulticastListenerQuery {
+                    maximum_response_delay: mld::encode_max_response_code(config.last_listener_query_interval),
+                    group_address: specific.group_addr,
+                    supress_router_processing: false,
+                    qrv: if config.robustness <= 7 { config.robustness } else { 0 },
+                    qqic: mld::encode_qqic(config.query_interval),
+                    source_addresses: specific.source_addrs.clone(),
+                };
+                if let Err(e) = self.send(specific.if_id, specific.group_addr, query).await {
+                    log::warn!("Failed to send Multicast Listener Query for {} on {:?}: {:?}", specific.group_addr, specific.if_id, e);
+                }
+            }
+
+            let next_specific = {
+                let mut specific_queries = self.specific_queries.lock();
+                for query in specific_queries.iter_mut() {
+                    if query.next <= now {
+                        query.remaining = query.remaining.saturating_sub(1);
+                        query.next = now + config.last_listener_query_interval();
+                    }
+                }
+                specific_queries.retain(|query| query.remaining > 0);
+                specific_queries.iter().map(|query| query.next).min()
+            };
+
+            let next_state = self.states.lock().values().map(|state| {
+                if state.other_querier.is_some() {
+                    state.other_querier_expires
+                } else {
+                    state.next_general_query
+                }
+            }).min();
+
+            let next = next_state.into_iter().chain(next_specific).min();
+            let next = next.unwrap_or(now + Duration::from_secs(60));
+            let _ = tokio::time::timeout_at(next, self.wakeup.notified()).await;
+        }
+    }
+}
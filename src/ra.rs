@@ -0,0 +1,372 @@
+
+use crate::config::{RaPrefixConfig, RaRewriteConfig, RaRouteConfig, RaServerConfig};
+use crate::icmp6::ndp::{Dnssl, NdpOption, PrefixInformation, Rdnss, RouteInformation, RouterAdvertisement};
+use crate::icmp6::AsyncIcmp6Socket;
+use crate::interface::{InterfaceId, InterfaceStateManager};
+
+use parking_lot::{Mutex, RwLock};
+use rand::Rng;
+use tokio::time::Instant;
+
+use std::collections::HashMap;
+use std::net::Ipv6Addr;
+use std::time::Duration;
+
+pub const ALL_NODES: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+
+/// router constants (RFC 4861 section 10)
+pub const MAX_INITIAL_RTR_ADVERT_INTERVAL: Duration = Duration::from_secs(16);
+pub const MAX_INITIAL_RTR_ADVERTISEMENTS: u32 = 3;
+pub const MIN_DELAY_BETWEEN_RAS: Duration = Duration::from_secs(3);
+pub const MAX_RA_DELAY_TIME: Duration = Duration::from_millis(500);
+
+/// Per-interface advertising state (RFC 4861 section 6.2.4)
+#[derive(Debug, Clone)]
+struct AdvertisingState {
+    initial_sent: u32,
+    last_sent: Option<Instant>,
+    next: Instant,
+
+    /// bumped whenever the advertised information changes
+    generation: u64,
+
+    /// generation of the last advertisement sent
+    advertised: u64,
+}
+
+/// Originates Router Advertisements on downstream interfaces
+#[derive(Debug)]
+pub struct RaServer {
+    socket: AsyncIcmp6Socket,
+    if_manager: InterfaceStateManager,
+    config: RwLock<RaServerConfig>,
+    dynamic_prefixes: RwLock<HashMap<InterfaceId, Vec<RaPrefixConfig>>>,
+    states: Mutex<HashMap<InterfaceId, AdvertisingState>>,
+    wakeup: tokio::sync::Notify,
+    sent: tokio::sync::Notify,
+}
+
+impl RaServer {
+    pub fn new(socket: AsyncIcmp6Socket, if_manager: InterfaceStateManager, interfaces: &[InterfaceId], config: RaServerConfig) -> Self {
+        let now = Instant::now();
+        let states = interfaces.iter().map(|if_id| {
+            (*if_id, AdvertisingState {
+                initial_sent: 0,
+                last_sent: None,
+                next: now,
+                generation: 0,
+                advertised: 0,
+            })
+        }).collect();
+
+        Self {
+            socket,
+            if_manager,
+            config: RwLock::new(config),
+            dynamic_prefixes: RwLock::new(HashMap::new()),
+            states: Mutex::new(states),
+            wakeup: tokio::sync::Notify::new(),
+            sent: tokio::sync::Notify::new(),
+        }
+    }
+
+    /// Starts or stops advertising on interfaces after a configuration change
+    pub fn set_interfaces(&self, interfaces: &[InterfaceId]) {
+        let now = Instant::now();
+        let mut states = self.states.lock();
+        states.retain(|if_id, _| interfaces.contains(if_id));
+        for if_id in interfaces {
+            states.entry(*if_id).or_insert(AdvertisingState {
+                initial_sent: 0,
+                last_sent: None,
+                next: now,
+                generation: 0,
+                advertised: 0,
+            });
+        }
+        drop(states);
+        self.dynamic_prefixes.write().retain(|if_id, _| interfaces.contains(if_id));
+        self.wakeup.notify_one();
+    }
+
+    pub fn set_config(&self, config: RaServerConfig) {
+        let changed = *self.config.read() != config;
+        if !changed {
+            return;
+        }
+        *self.config.write() = config;
+
+        let if_ids = self.states.lock().keys().cloned().collect::<Vec<_>>();
+        for if_id in if_ids {
+            self.reset(if_id);
+        }
+    }
+
+    /// Prefixes learned at runtime (e.g. via DHCPv6-PD) advertised in addition to the configured ones
+    pub fn set_prefixes(&self, if_id: InterfaceId, prefixes: Vec<RaPrefixConfig>) {
+        let prev = self.dynamic_prefixes.write().insert(if_id, prefixes.clone());
+        if prev.as_ref() != Some(&prefixes) {
+            self.reset(if_id);
+        }
+    }
+
+    /// Restarts the initial advertisements after the advertised information changed
+    fn reset(&self, if_id: InterfaceId) {
+        let mut states = self.states.lock();
+        if let Some(state) = states.get_mut(&if_id) {
+            state.initial_sent = 0;
+            state.generation += 1;
+            state.next = Self::earliest_multicast(state, Instant::now());
+        }
+        drop(states);
+        self.wakeup.notify_one();
+    }
+
+    /// Waits until an advertisement reflecting the information set so far has been sent on `if_id`.
+    /// Returns immediately if the interface is not advertised on.
+    pub async fn wait_advertised(&self, if_id: InterfaceId) {
+        let generation = if let Some(state) = self.states.lock().get(&if_id) {
+            state.generation
+        } else {
+            return;
+        };
+
+        loop {
+            let sent = self.sent.notified();
+            tokio::pin!(sent);
+            sent.as_mut().enable();
+
+            match self.states.lock().get(&if_id) {
+                Some(state) if state.advertised < generation => (),
+                _ => return,
+            }
+            sent.await;
+        }
+    }
+
+    /// Schedules a multicast answer to a Router Solicitation (RFC 4861 section 6.2.6)
+    pub fn solicit(&self, if_id: InterfaceId) {
+        let mut states = self.states.lock();
+        let state = if let Some(state) = states.get_mut(&if_id) {
+            state
+        } else {
+            return;
+        };
+
+        let delay = rand::thread_rng().gen_range(Duration::ZERO..=MAX_RA_DELAY_TIME);
+        let at = Self::earliest_multicast(state, Instant::now() + delay);
+        if at < state.next {
+            state.next = at;
+            drop(states);
+            self.wakeup.notify_one();
+        }
+    }
+
+    fn earliest_multicast(state: &AdvertisingState, at: Instant) -> Instant {
+        match state.last_sent {
+            Some(last_sent) => at.max(last_sent + MIN_DELAY_BETWEEN_RAS),
+            None => at,
+        }
+    }
+
+    /// Delay until the next unsolicited advertisement
+    fn interval(&self, initial_sent: u32) -> Duration {
+        unsolicited_interval(&self.config.read(), initial_sent)
+    }
+
+    pub fn advertisement(&self, if_id: InterfaceId) -> RouterAdvertisement {
+        let config = self.config.read();
+        let mut options = Vec::new();
+
+        let dynamic_prefixes = self.dynamic_prefixes.read().get(&if_id).cloned().unwrap_or_default();
+        for prefix in config.prefixes.iter().chain(dynamic_prefixes.iter()) {
+            options.push(prefix_information(prefix));
+        }
+
+        if let Some(mtu) = config.mtu {
+            options.push(NdpOption::Mtu(mtu));
+        }
+        if !config.rdnss.is_empty() {
+            options.push(rdnss_option(&config.rdnss, config.dns_lifetime()));
+        }
+        if !config.dnssl.is_empty() {
+            options.push(dnssl_option(&config.dnssl, config.dns_lifetime()));
+        }
+        for route in &config.routes {
+            options.push(route_information(route));
+        }
+
+        RouterAdvertisement {
+            hop_limit: config.hop_limit,
+            managed_address_configuration: config.managed,
+            other_configuration: config.other,
+            router_lifetime: config.router_lifetime,
+            reachable_time: config.reachable_time,
+            retrans_timer: config.retrans_timer,
+            options,
+        }
+    }
+
+    async fn send(&self, writer: &mut crate::icmp6::Icmp6Writer, rtnl_link: &mut crate::rtnl::link::LinkManager, if_id: InterfaceId) -> Result<(), std::io::Error> {
+        let source = if let Some(source) = self.if_manager.get_link_local_addr(if_id) {
+            source
+        } else {
+            return Err(std::io::Error::new(std::io::ErrorKind::AddrNotAvailable, "no link-local address"));
+        };
+
+        let mut ra = self.advertisement(if_id);
+        if let Ok(Some(link_layer_address)) = rtnl_link.get_link_layer_address(if_id).await {
+            ra.options.insert(0, NdpOption::SourceLinkLayer(link_layer_address));
+        }
+
+        writer.set_destination(ALL_NODES);
+        writer.set_packet_info(Some(crate::icmp6::packet::PacketInfo {
+            if_index: if_id,
+            addr: source,
+        }));
+        writer.set_hop_limit(Some(255));
+        writer.set_hop_by_hop(None);
+        writer.set_packet(crate::icmp6::Icmp6Packet::RouterAdvertisement(ra)).map_err(std::io::Error::other)?;
+        self.socket.send_writer(writer).await
+    }
+
+    pub async fn run(&self) -> Result<(), std::io::Error> {
+        let rtnl = crate::rtnl::RtnetlinkConnection::new().await?;
+        let mut rtnl_link = rtnl.link();
+        let mut writer = crate::icmp6::Icmp6Writer::new();
+
+        loop {
+            let now = Instant::now();
+            let due = self.states.lock().iter().filter(|(_, state)| state.next <= now).map(|(if_id, _)| *if_id).collect::<Vec<_>>();
+
+            for if_id in due {
+                // the advertisement is built after this, so it is at least as new as `generation`
+                let generation = self.states.lock().get(&if_id).map(|state| state.generation).unwrap_or(0);
+                let result = self.send(&mut writer, &mut rtnl_link, if_id).await;
+                if let Err(e) = &result {
+                    log::warn!("Failed to send Router Advertisement on {:?}: {:?}", if_id, e);
+                }
+
+                let mut states = self.states.lock();
+                let state = if let Some(state) = states.get_mut(&if_id) {
+                    state
+                } else {
+                    continue;
+                };
+                if result.is_ok() {
+                    state.last_sent = Some(now);
+                    state.initial_sent = state.initial_sent.saturating_add(1);
+                    state.advertised = generation;
+                    state.next = now + self.interval(state.initial_sent);
+                } else {
+                    state.next = now + Duration::from_secs(1);
+                }
+            }
+
+            self.sent.notify_waiters();
+
+            let next = self.states.lock().values().map(|state| state.next).min();
+            let next = next.unwrap_or(now + Duration::from_secs(60));
+            let _ = tokio::time::timeout_at(next, self.wakeup.notified()).await;
+        }
+    }
+}
+
+/// MinRtrAdvInterval and MaxRtrAdvInterval, clamped to the limits of RFC 4861 section 6.2.1
+pub fn advertising_interval_range(config: &RaServerConfig) -> (Duration, Duration) {
+    let max = config.max_interval.clamp(4, 1800);
+    let min = config.min_interval().clamp(3, max * 3 / 4);
+    (Duration::from_secs(min as u64), Duration::from_secs(max as u64))
+}
+
+/// Random delay until the next unsolicited advertisement after `initial_sent` advertisements (RFC 4861 section 6.2.4)
+pub fn unsolicited_interval(config: &RaServerConfig, initial_sent: u32) -> Duration {
+    let (min, max) = advertising_interval_range(config);
+    let interval = rand::thread_rng().gen_range(min..=max);
+    if initial_sent < MAX_INITIAL_RTR_ADVERTISEMENTS {
+        interval.min(MAX_INITIAL_RTR_ADVERT_INTERVAL)
+    } else {
+        interval
+    }
+}
+
+fn prefix_information(prefix: &RaPrefixConfig) -> NdpOption {
+    NdpOption::PrefixInformation(PrefixInformation {
+        prefix_len: prefix.prefix_len,
+        on_link: prefix.on_link,
+        autonomous: prefix.autonomous,
+        router_address: false,
+        valid_lifetime: prefix.valid_lifetime,
+        preferred_lifetime: prefix.preferred_lifetime,
+        prefix: prefix.prefix,
+    })
+}
+
+fn rdnss_option(servers: &[Ipv6Addr], lifetime: u32) -> NdpOption {
+    NdpOption::Rdnss(Rdnss {
+        lifetime,
+        servers: servers.to_vec(),
+    })
+}
+
+fn dnssl_option(domains: &[String], lifetime: u32) -> NdpOption {
+    NdpOption::Dnssl(Dnssl {
+        lifetime,
+        domains: domains.to_vec(),
+    })
+}
+
+fn route_information(route: &RaRouteConfig) -> NdpOption {
+    NdpOption::RouteInformation(RouteInformation {
+        prefix_len: route.prefix_len,
+        preference: route.preference.into(),
+        lifetime: route.lifetime,
+        prefix: route.prefix,
+    })
+}
+
+/// Applies a downstream's rewrite rules to a relayed Router Advertisement
+pub fn rewrite_router_advertisement(ra: &mut RouterAdvertisement, rule: &RaRewriteConfig) {
+    if let Some(router_lifetime) = rule.router_lifetime {
+        ra.router_lifetime = router_lifetime;
+    }
+    if let Some(hop_limit) = rule.hop_limit {
+        ra.hop_limit = hop_limit;
+    }
+    if let Some(managed) = rule.managed {
+        ra.managed_address_configuration = managed;
+    }
+    if let Some(other) = rule.other {
+        ra.other_configuration = other;
+    }
+
+    if rule.drop_rdnss {
+        ra.options.retain(|option| !matches!(option, NdpOption::Rdnss(_)));
+    }
+    if rule.drop_dnssl {
+        ra.options.retain(|option| !matches!(option, NdpOption::Dnssl(_)));
+    }
+
+    if let Some(max_mtu) = rule.max_mtu {
+        let mut found = false;
+        for option in ra.options.iter_mut() {
+            if let NdpOption::Mtu(mtu) = option {
+                found = true;
+                *mtu = (*mtu).min(max_mtu);
+            }
+        }
+        if !found {
+            ra.options.push(NdpOption::Mtu(max_mtu));
+        }
+    }
+
+    if !rule.rdnss.is_empty() {
+        ra.options.push(rdnss_option(&rule.rdnss, rule.dns_lifetime));
+    }
+    if !rule.dnssl.is_empty() {
+        ra.options.push(dnssl_option(&rule.dnssl, rule.dns_lifetime));
+    }
+    for route in &rule.routes {
+        ra.options.push(route_information(route));
+    }
+}
@@ -49,4 +49,20 @@ impl AddressManager {
         }
         Ok(addrs)
     }
+
+    pub async fn add_v6(&self, if_index: InterfaceId, addr: std::net::Ipv6Addr, prefix_len: u8) -> Result<(), std::io::Error> {
+        let if_index = if_index.inner_unchecked();
+        let req = self.handle.add(if_index, std::net::IpAddr::V6(addr), prefix_len).replace();
+        req.execute().await.map_err(std::io::Error::other)
+    }
+
+    pub async fn delete_v6(&self, if_index: InterfaceId, addr: std::net::Ipv6Addr, prefix_len: u8) -> Result<(), std::io::Error> {
+        let if_index = if_index.inner_unchecked();
+        let mut addr_msg = netlink_packet_route::address::AddressMessage::default();
+        addr_msg.header.family = netlink_packet_route::AddressFamily::Inet6;
+        addr_msg.header.prefix_len = prefix_len;
+        addr_msg.header.index = if_index;
+        addr_msg.attributes.push(netlink_packet_route::address::AddressAttribute::Address(std::net::IpAddr::V6(addr)));
+        self.handle.del(addr_msg).execute().await.map_err(std::io::Error::other)
+    }
 }
@@ -20,11 +20,15 @@ impl LinkManager {
         while let Some(response) = response.try_next().await.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))? {
             let if_index = response.header.index;
             let mut if_name = None;
+            let mut mtu = None;
             for link in response.attributes.iter() {
                 match link {
                     netlink_packet_route::link::LinkAttribute::IfName(name) => {
                         if_name = Some(name.clone());
                     }
+                    netlink_packet_route::link::LinkAttribute::Mtu(value) => {
+                        mtu = Some(*value);
+                    }
                     _ => {}
                 }
             }
@@ -33,7 +37,7 @@ impl LinkManager {
                 continue;
             }
 
-            interfaces.push(Interface { if_id: InterfaceId::new(if_index), if_name: if_name.unwrap() });
+            interfaces.push(Interface { if_id: InterfaceId::new(if_index), if_name: if_name.unwrap(), mtu });
         }
         Ok(interfaces)
     }
@@ -44,11 +48,15 @@ impl LinkManager {
         futures::pin_mut!(response);
         while let Some(response) = response.try_next().await.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))? {
             let mut if_name = None;
+            let mut mtu = None;
             for link in response.attributes.iter() {
                 match link {
                     netlink_packet_route::link::LinkAttribute::IfName(name) => {
                         if_name = Some(name.clone());
                     }
+                    netlink_packet_route::link::LinkAttribute::Mtu(value) => {
+                        mtu = Some(*value);
+                    }
                     _ => {}
                 }
             }
@@ -57,7 +65,7 @@ impl LinkManager {
                 continue;
             }
 
-            return Ok(Some(Interface { if_id: InterfaceId::new(if_index), if_name: if_name.unwrap() }));
+            return Ok(Some(Interface { if_id: InterfaceId::new(if_index), if_name: if_name.unwrap(), mtu }));
         }
         Ok(None)
     }
@@ -71,7 +79,12 @@ impl LinkManager {
                 continue;
             }
 
-            return Ok(Some(Interface { if_id: InterfaceId::new(if_index), if_name: if_name.to_owned() }));
+            let mtu = response.attributes.iter().find_map(|link| match link {
+                netlink_packet_route::link::LinkAttribute::Mtu(value) => Some(*value),
+                _ => None,
+            });
+
+            return Ok(Some(Interface { if_id: InterfaceId::new(if_index), if_name: if_name.to_owned(), mtu }));
         }
         Ok(None)
     }
@@ -0,0 +1,53 @@
+//! Multicast routing table stand-in shared by the MLD proxy tests.
+
+#![allow(dead_code)]
+
+use ftthd::config::MldQuerierConfig;
+use ftthd::group::MldSubscriptionManager;
+use ftthd::icmp6::mroute::{MifCounters, MulticastRouter, SgCounters};
+use ftthd::icmp6::socket::mifi_t;
+use ftthd::interface::InterfaceId;
+
+use std::net::Ipv6Addr;
+
+/// Kernel multicast routing table that accepts everything
+#[derive(Debug)]
+pub struct NullRouter;
+
+impl MulticastRouter for NullRouter {
+    fn add_mif(&self, _mif: mifi_t, _if_index: InterfaceId) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+
+    fn del_mif(&self, _mif: mifi_t) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+
+    fn add_mroute(&self, _parent_mif: mifi_t, _output_mifs: Vec<mifi_t>, _group_addr: Ipv6Addr, _source_addr: Ipv6Addr) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+
+    fn del_mroute(&self, _parent_mif: mifi_t, _group_addr: Ipv6Addr, _source_addr: Ipv6Addr) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+
+    fn sg_counters(&self, _group_addr: Ipv6Addr, _source_addr: Ipv6Addr) -> Result<SgCounters, std::io::Error> {
+        Ok(SgCounters::default())
+    }
+
+    fn mif_counters(&self, _mif: mifi_t) -> Result<MifCounters, std::io::Error> {
+        Ok(MifCounters::default())
+    }
+}
+
+pub fn upstream() -> InterfaceId {
+    InterfaceId::from(1)
+}
+
+pub fn downstream(n: u32) -> InterfaceId {
+    InterfaceId::from(1 + n)
+}
+
+pub fn manager() -> MldSubscriptionManager<NullRouter> {
+    MldSubscriptionManager::with_router(NullRouter, upstream(), (1..=3).map(downstream), MldQuerierConfig::default()).unwrap()
+}
@@ -0,0 +1,164 @@
+//! Diagnostics of the configuration checker, with the line each one points at.
+
+use ftthd::config::{Config, ConfigDiagnostic};
+
+use std::path::Path;
+
+const PATH: &str = "/etc/ftthd.toml";
+
+/// An interface name no host running the tests has
+const MISSING_IF: &str = "ftthtest0";
+
+/// Line numbers and messages of the diagnostics for `content`, in the order they were reported
+fn diagnostics(content: &str) -> Vec<(Option<usize>, String)> {
+    let diagnostics = Config::check_str(PATH, content).expect_err("configuration accepted");
+    for diagnostic in &diagnostics {
+        assert_eq!(diagnostic.path, Path::new(PATH));
+    }
+    diagnostics.into_iter().map(|ConfigDiagnostic { line, message, .. }| (line, message)).collect()
+}
+
+fn at(line: usize, message: &str) -> (Option<usize>, String) {
+    (Some(line), message.to_owned())
+}
+
+#[test]
+fn valid_configuration_is_accepted() {
+    let content = r#"
+[global]
+proxy_mode = "dhcpv6_pd"
+
+[interfaces]
+upstream = "lo"
+downstreams = []
+
+[dhcp6]
+request_address = true
+prefix_length_hint = 56
+
+[mld_querier]
+enabled = true
+"#;
+    let config = Config::check_str(PATH, content).unwrap();
+    assert!(config.dhcp6.request_address);
+    assert!(config.mld_querier.enabled);
+}
+
+#[test]
+fn upstream_listed_as_downstream() {
+    let content = r#"[global]
+
+[interfaces]
+upstream = "lo"
+downstreams = ["lo"]
+"#;
+    assert_eq!(diagnostics(content), vec![
+        at(5, "upstream interface lo is also listed as a downstream"),
+    ]);
+}
+
+#[test]
+fn duplicate_and_unknown_downstreams() {
+    let content = format!(r#"[global]
+
+[interfaces]
+upstream = "lo"
+downstreams = [
+    "{0}",
+    "{0}",
+]
+"#, MISSING_IF);
+    assert_eq!(diagnostics(&content), vec![
+        at(6, &format!("unknown interface: {}", MISSING_IF)),
+        at(7, &format!("duplicate downstream interface: {}", MISSING_IF)),
+    ]);
+}
+
+#[test]
+fn unknown_upstream() {
+    let content = format!(r#"[global]
+
+[interfaces]
+upstream = "{}"
+downstreams = []
+"#, MISSING_IF);
+    assert_eq!(diagnostics(&content), vec![
+        at(4, &format!("unknown interface: {}", MISSING_IF)),
+    ]);
+}
+
+#[test]
+fn dhcp6_options_need_dhcpv6_pd_mode() {
+    let content = r#"[global]
+proxy_mode = "ndp_proxy"
+
+[interfaces]
+upstream = "lo"
+downstreams = []
+
+[dhcp6]
+request_address = true
+prefix_length_hint = 56
+"#;
+    assert_eq!(diagnostics(content), vec![
+        at(9, "dhcp6.request_address is only used with proxy_mode = \"dhcpv6_pd\""),
+        at(10, "dhcp6.prefix_length_hint is only used with proxy_mode = \"dhcpv6_pd\""),
+    ]);
+}
+
+#[test]
+fn ra_rewrite_needs_relayed_ras() {
+    let content = r#"[global]
+proxy_mode = "dhcpv6_pd"
+
+[interfaces]
+upstream = "lo"
+downstreams = []
+
+[ra_rewrite.lo]
+hop_limit = 64
+"#;
+    assert_eq!(diagnostics(content), vec![
+        at(8, "ra_rewrite has no effect with proxy_mode = \"dhcpv6_pd\" because upstream RAs are not relayed"),
+        at(8, "ra_rewrite.lo is not a downstream interface"),
+    ]);
+}
+
+#[test]
+fn unknown_keys() {
+    let content = r#"[global]
+proxy_mod = "dhcpv6_pd"
+
+[interfaces]
+upstream = "lo"
+downstreams = []
+
+[ra_server]
+max_intervl = 600
+
+[mld_querier]
+enable = true
+
+[ra_sever]
+enabled = true
+"#;
+    assert_eq!(diagnostics(content), vec![
+        at(2, "unknown key: global.proxy_mod"),
+        at(9, "unknown key: ra_server.max_intervl"),
+        at(12, "unknown key: mld_querier.enable"),
+        at(14, "unknown key: ra_sever"),
+    ]);
+}
+
+#[test]
+fn syntax_errors_point_at_their_line() {
+    let content = r#"[global]
+
+[interfaces]
+upstream = "lo"
+downstreams = ["lo"
+"#;
+    let diagnostics = diagnostics(content);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].0, Some(6));
+}
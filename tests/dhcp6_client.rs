@@ -0,0 +1,323 @@
+//! DHCPv6 client state machine on a paused clock against a scripted server.
+
+use ftthd::dhcp6::client::{Dhcp6Client, Dhcp6ClientConfig, Dhcp6Event};
+use ftthd::dhcp6::*;
+
+use parking_lot::Mutex;
+use tokio::time::Instant;
+
+use std::future::Future;
+use std::net::{Ipv6Addr, SocketAddrV6};
+use std::sync::Arc;
+use std::time::Duration;
+
+const IAID: u32 = 7;
+const PREFIX: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0x100, 0, 0, 0, 0);
+
+type ServerFn = Box<dyn Fn(&Dhcp6Message) -> Option<Dhcp6Message> + Send + Sync>;
+
+/// Answers each message sent by the client with whatever the test's server function returns
+struct FakeServer {
+    server: ServerFn,
+    sent: Mutex<Vec<(Instant, Dhcp6Message)>>,
+    replies_tx: tokio::sync::mpsc::UnboundedSender<Vec<u8>>,
+    replies_rx: tokio::sync::Mutex<tokio::sync::mpsc::UnboundedReceiver<Vec<u8>>>,
+}
+
+/// Handle to the fake server shared by the client and the test
+#[derive(Clone)]
+struct FakeTransport(Arc<FakeServer>);
+
+impl FakeTransport {
+    fn new<F>(server: F) -> Self
+    where
+        F: Fn(&Dhcp6Message) -> Option<Dhcp6Message> + Send + Sync + 'static,
+    {
+        let (replies_tx, replies_rx) = tokio::sync::mpsc::unbounded_channel();
+        Self(Arc::new(FakeServer {
+            server: Box::new(server),
+            sent: Mutex::new(Vec::new()),
+            replies_tx,
+            replies_rx: tokio::sync::Mutex::new(replies_rx),
+        }))
+    }
+
+    fn sent(&self, msg_type: MessageType) -> Vec<Instant> {
+        self.0.sent.lock().iter().filter(|(_, message)| message.msg_type == msg_type).map(|(at, _)| *at).collect()
+    }
+}
+
+impl Dhcp6Transport for FakeTransport {
+    fn send_to(&self, buf: &[u8], _addr: Ipv6Addr, _port: u16) -> impl Future<Output = Result<(), std::io::Error>> + Send {
+        let message = Dhcp6Message::parse(buf).unwrap();
+        if let Some(reply) = (self.0.server)(&message) {
+            self.0.replies_tx.send(reply.serialize()).unwrap();
+        }
+        self.0.sent.lock().push((Instant::now(), message));
+        async { Ok(()) }
+    }
+
+    async fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddrV6), std::io::Error> {
+        let reply = self.0.replies_rx.lock().await.recv().await.unwrap();
+        buf[..reply.len()].copy_from_slice(&reply);
+        Ok((reply.len(), SocketAddrV6::new("fe80::1".parse().unwrap(), SERVER_PORT, 0, 0)))
+    }
+}
+
+fn client_duid() -> Duid {
+    Duid::new_ll(&[0x02, 0, 0, 0, 0, 1])
+}
+
+fn server_duid() -> Duid {
+    Duid::new_ll(&[0x02, 0, 0, 0, 0, 2])
+}
+
+fn pd_config() -> Dhcp6ClientConfig {
+    Dhcp6ClientConfig {
+        request_address: false,
+        request_prefix: true,
+        prefix_length_hint: Some(56),
+    }
+}
+
+fn ia_pd(options: Vec<Dhcp6Option>) -> Dhcp6Option {
+    Dhcp6Option::IaPd(IaPd {
+        iaid: IAID,
+        t1: 100,
+        t2: 160,
+        options,
+    })
+}
+
+fn delegated_prefix() -> Dhcp6Option {
+    Dhcp6Option::IaPrefix(IaPrefix {
+        preferred_lifetime: 180,
+        valid_lifetime: 200,
+        prefix_len: 56,
+        prefix: PREFIX,
+        options: Vec::new(),
+    })
+}
+
+fn status(code: StatusCodeValue) -> Dhcp6Option {
+    Dhcp6Option::StatusCode(StatusCode {
+        code,
+        message: String::new(),
+    })
+}
+
+/// Reply of type `msg_type` to `message`, carrying `ia`
+fn answer(message: &Dhcp6Message, msg_type: MessageType, ia: Dhcp6Option) -> Option<Dhcp6Message> {
+    let mut reply = Dhcp6Message::new(msg_type, message.transaction_id);
+    reply.options.push(Dhcp6Option::ClientId(message.client_id().unwrap().clone()));
+    reply.options.push(Dhcp6Option::ServerId(server_duid()));
+    reply.options.push(ia);
+    Some(reply)
+}
+
+/// Grants the prefix to Solicit and Request and ignores everything else
+fn granting_server(message: &Dhcp6Message) -> Option<Dhcp6Message> {
+    match message.msg_type {
+        MessageType::Solicit => answer(message, MessageType::Advertise, ia_pd(vec![delegated_prefix()])),
+        MessageType::Request => answer(message, MessageType::Reply, ia_pd(vec![delegated_prefix()])),
+        _ => None,
+    }
+}
+
+fn client(transport: &FakeTransport) -> Arc<Dhcp6Client<FakeTransport>> {
+    Arc::new(Dhcp6Client::with_transport(transport.clone(), client_duid(), IAID, pd_config(), None))
+}
+
+fn assert_between(value: Duration, min: Duration, max: Duration) {
+    assert!(value >= min && value <= max, "{:?} not within {:?}..={:?}", value, min, max);
+}
+
+/// Each RT is twice the previous one or MRT, both with RAND between -0.1 and 0.1 (RFC 8415 section 15)
+fn assert_backoff(intervals: &[Duration], mrt: Duration) {
+    for w in intervals.windows(2) {
+        let (prev, rt) = (w[0], w[1]);
+        let doubled = rt >= prev.mul_f64(1.8) && rt <= prev.mul_f64(2.2);
+        let capped = rt >= mrt.mul_f64(0.9) && rt <= mrt.mul_f64(1.1);
+        assert!(doubled || capped, "RT {:?} after {:?} is neither doubled nor MRT", rt, prev);
+        assert!(rt <= mrt.mul_f64(1.1));
+    }
+}
+
+fn intervals(times: &[Instant]) -> Vec<Duration> {
+    times.windows(2).map(|w| w[1] - w[0]).collect()
+}
+
+#[tokio::test(start_paused = true)]
+async fn solicit_retransmissions_double_up_to_mrt() {
+    let transport = FakeTransport::new(|_| None);
+    let client = client(&transport);
+
+    let started = Instant::now();
+    let res = tokio::time::timeout(Duration::from_secs(6 * 3600), client.solicit()).await;
+    assert!(res.is_err(), "Solicit has no MRC or MRD and must keep going");
+
+    let times = transport.sent(MessageType::Solicit);
+    assert!(times.len() > 14);
+
+    // SOL_MAX_DELAY
+    assert_between(times[0] - started, Duration::ZERO, Duration::from_secs(1));
+
+    // the first RT of a Solicit has a strictly positive RAND (RFC 8415 section 18.2.1)
+    let intervals = intervals(&times);
+    assert_between(intervals[0], Duration::from_secs(1), Duration::from_millis(1100));
+
+    // SOL_MAX_RT
+    let mrt = Duration::from_secs(3600);
+    assert_backoff(&intervals, mrt);
+    assert_between(*intervals.last().unwrap(), mrt.mul_f64(0.9), mrt.mul_f64(1.1));
+}
+
+#[tokio::test(start_paused = true)]
+async fn request_gives_up_after_mrc() {
+    let transport = FakeTransport::new(|message| match message.msg_type {
+        MessageType::Solicit => answer(message, MessageType::Advertise, ia_pd(vec![delegated_prefix()])),
+        _ => None,
+    });
+    let client = client(&transport);
+
+    let err = client.solicit().await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+
+    // REQ_MAX_RC
+    let times = transport.sent(MessageType::Request);
+    assert_eq!(times.len(), 10);
+    assert_between(times[1] - times[0], Duration::from_millis(900), Duration::from_millis(1100));
+
+    // REQ_MAX_RT
+    let intervals = intervals(&times);
+    assert_backoff(&intervals, Duration::from_secs(30));
+    assert_between(*intervals.last().unwrap(), Duration::from_secs(27), Duration::from_secs(33));
+}
+
+#[tokio::test(start_paused = true)]
+async fn confirm_gives_up_after_mrd() {
+    let transport = FakeTransport::new(granting_server);
+    let client = client(&transport);
+    let lease = client.solicit().await.unwrap();
+
+    let err = client.confirm(&lease).await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+
+    // CNF_MAX_RD, counted from the first transmission
+    let times = transport.sent(MessageType::Confirm);
+    assert_eq!(Instant::now() - times[0], Duration::from_secs(10));
+
+    // CNF_MAX_RT
+    assert_backoff(&intervals(&times), Duration::from_secs(4));
+}
+
+#[tokio::test(start_paused = true)]
+async fn lease_goes_through_renew_and_rebind_before_it_is_lost() {
+    let transport = FakeTransport::new(granting_server);
+    let client = client(&transport);
+    let mut events = client.subscribe();
+
+    let runner = client.clone();
+    let task = tokio::spawn(async move { runner.run(None).await });
+
+    let acquired = match events.recv().await.unwrap() {
+        Dhcp6Event::Acquired(lease) => lease,
+        event => panic!("unexpected event: {:?}", event),
+    };
+    let bound = Instant::now();
+    assert_eq!(acquired.prefixes[0].prefix, PREFIX);
+
+    let lost = match events.recv().await.unwrap() {
+        Dhcp6Event::Lost(lease) => lease,
+        event => panic!("unexpected event: {:?}", event),
+    };
+    let lost_at = Instant::now();
+    task.abort();
+    assert_eq!(lost, acquired);
+
+    // valid lifetime
+    assert_between(lost_at - bound, Duration::from_secs(199), Duration::from_secs(200));
+
+    // T1 and T2
+    let renews = transport.sent(MessageType::Renew);
+    let rebinds = transport.sent(MessageType::Rebind);
+    assert!(!renews.is_empty() && !rebinds.is_empty());
+    assert_between(renews[0] - bound, Duration::from_secs(99), Duration::from_secs(100));
+    assert!(renews.iter().all(|at| *at - bound < Duration::from_secs(160)));
+    assert_between(rebinds[0] - bound, Duration::from_secs(159), Duration::from_secs(160));
+    assert!(rebinds.iter().all(|at| *at - bound < Duration::from_secs(200)));
+
+    // REN_TIMEOUT, then doubling
+    let intervals = intervals(&renews);
+    assert_between(intervals[0], Duration::from_secs(9), Duration::from_secs(11));
+    assert_backoff(&intervals, Duration::from_secs(600));
+}
+
+#[tokio::test(start_paused = true)]
+async fn advertise_without_prefix_is_ignored() {
+    let transport = FakeTransport::new(|message| match message.msg_type {
+        MessageType::Solicit => answer(message, MessageType::Advertise, ia_pd(vec![status(StatusCodeValue::NoPrefixAvail)])),
+        MessageType::Request => answer(message, MessageType::Reply, ia_pd(vec![delegated_prefix()])),
+        _ => None,
+    });
+    let client = client(&transport);
+
+    let res = tokio::time::timeout(Duration::from_secs(60), client.solicit()).await;
+    assert!(res.is_err());
+    assert!(transport.sent(MessageType::Solicit).len() > 1);
+    assert!(transport.sent(MessageType::Request).is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn no_binding_on_renew_requests_the_lease_again() {
+    let transport = FakeTransport::new(|message| match message.msg_type {
+        MessageType::Renew => answer(message, MessageType::Reply, ia_pd(vec![status(StatusCodeValue::NoBinding)])),
+        _ => granting_server(message),
+    });
+    let client = client(&transport);
+    let mut events = client.subscribe();
+
+    let runner = client.clone();
+    let task = tokio::spawn(async move { runner.run(None).await });
+
+    assert!(matches!(events.recv().await.unwrap(), Dhcp6Event::Acquired(_)));
+    let renewed = match events.recv().await.unwrap() {
+        Dhcp6Event::Renewed(lease) => lease,
+        event => panic!("unexpected event: {:?}", event),
+    };
+    task.abort();
+    assert_eq!(renewed.prefixes[0].prefix, PREFIX);
+
+    let sent = transport.0.sent.lock().iter().map(|(_, message)| message.clone()).collect::<Vec<_>>();
+    let types = sent.iter().map(|message| message.msg_type).collect::<Vec<_>>();
+    assert_eq!(types, vec![MessageType::Solicit, MessageType::Request, MessageType::Renew, MessageType::Request]);
+
+    // the Request reinstating the binding carries the prefix and the server's ID
+    let request = &sent[3];
+    assert_eq!(request.server_id(), Some(&server_duid()));
+    assert_eq!(request.ia_pd(IAID).unwrap().prefixes()[0].prefix, PREFIX);
+}
+
+#[tokio::test(start_paused = true)]
+async fn no_prefix_on_renew_falls_back_to_rebind() {
+    let transport = FakeTransport::new(|message| match message.msg_type {
+        MessageType::Renew => answer(message, MessageType::Reply, ia_pd(vec![status(StatusCodeValue::NoPrefixAvail)])),
+        MessageType::Rebind => answer(message, MessageType::Reply, ia_pd(vec![delegated_prefix()])),
+        _ => granting_server(message),
+    });
+    let client = client(&transport);
+    let mut events = client.subscribe();
+
+    let runner = client.clone();
+    let task = tokio::spawn(async move { runner.run(None).await });
+
+    assert!(matches!(events.recv().await.unwrap(), Dhcp6Event::Acquired(_)));
+    let bound = Instant::now();
+    assert!(matches!(events.recv().await.unwrap(), Dhcp6Event::Renewed(_)));
+    task.abort();
+
+    assert_eq!(transport.sent(MessageType::Renew).len(), 1);
+    let rebinds = transport.sent(MessageType::Rebind);
+    assert_eq!(rebinds.len(), 1);
+    assert_between(rebinds[0] - bound, Duration::from_secs(159), Duration::from_secs(160));
+}
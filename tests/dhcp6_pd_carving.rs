@@ -0,0 +1,86 @@
+//! Carving per-downstream /64s out of a delegated prefix.
+
+use ftthd::dhcp6::client::subnet_prefix;
+use ftthd::dhcp6::IaPrefix;
+
+use std::collections::HashSet;
+use std::net::Ipv6Addr;
+
+fn delegated(prefix: &str, prefix_len: u8) -> IaPrefix {
+    IaPrefix {
+        preferred_lifetime: 3600,
+        valid_lifetime: 7200,
+        prefix_len,
+        prefix: prefix.parse().unwrap(),
+        options: Vec::new(),
+    }
+}
+
+fn contains(prefix: &IaPrefix, addr: Ipv6Addr) -> bool {
+    let mask = !0u128 << (128 - prefix.prefix_len as u32);
+    u128::from(addr) & mask == u128::from(prefix.prefix) & mask
+}
+
+/// Every /64 that fits is inside the delegated prefix, aligned and distinct; the next one doesn't fit
+fn assert_carves(prefix: &IaPrefix, capacity: u32) {
+    let mut subnets = HashSet::new();
+    for index in 0..capacity {
+        let subnet = subnet_prefix(prefix, index).unwrap();
+        assert!(contains(prefix, subnet), "{} outside {}/{}", subnet, prefix.prefix, prefix.prefix_len);
+        assert_eq!(u128::from(subnet) & 0xffff_ffff_ffff_ffff, 0, "{} is not a /64", subnet);
+        assert!(subnets.insert(subnet), "{} handed out twice", subnet);
+    }
+    assert_eq!(subnet_prefix(prefix, capacity), None);
+    assert_eq!(subnet_prefix(prefix, u32::MAX), None);
+}
+
+#[test]
+fn slash_56_holds_256_downstreams() {
+    let prefix = delegated("2001:db8:1200::", 56);
+    assert_carves(&prefix, 256);
+    assert_eq!(subnet_prefix(&prefix, 0), Some("2001:db8:1200::".parse().unwrap()));
+    assert_eq!(subnet_prefix(&prefix, 1), Some("2001:db8:1200:1::".parse().unwrap()));
+    assert_eq!(subnet_prefix(&prefix, 255), Some("2001:db8:1200:ff::".parse().unwrap()));
+}
+
+#[test]
+fn slash_60_holds_16_downstreams() {
+    let prefix = delegated("2001:db8:1200:30::", 60);
+    assert_carves(&prefix, 16);
+    assert_eq!(subnet_prefix(&prefix, 15), Some("2001:db8:1200:3f::".parse().unwrap()));
+}
+
+#[test]
+fn slash_64_holds_one_downstream() {
+    let prefix = delegated("2001:db8:1200:34::", 64);
+    assert_carves(&prefix, 1);
+    assert_eq!(subnet_prefix(&delegated("2001:db8:1200:34::", 72), 0), None);
+}
+
+#[test]
+fn subnets_are_stable() {
+    // host bits set by the server and changed lifetimes on renewal don't move the /64s
+    let prefix = delegated("2001:db8:1200::", 56);
+    let sloppy = delegated("2001:db8:1200:ab::1", 56);
+    let renewed = IaPrefix {
+        preferred_lifetime: 0,
+        valid_lifetime: 60,
+        ..prefix.clone()
+    };
+
+    for index in 0..256 {
+        let subnet = subnet_prefix(&prefix, index);
+        assert_eq!(subnet_prefix(&prefix, index), subnet);
+        assert_eq!(subnet_prefix(&sloppy, index), subnet);
+        assert_eq!(subnet_prefix(&renewed, index), subnet);
+    }
+}
+
+#[test]
+fn more_downstreams_than_the_prefix_holds() {
+    // downstreams past the capacity get nothing, the ones before keep their /64
+    let prefix = delegated("2001:db8:1200:30::", 60);
+    let subnets = (0..20).map(|index| subnet_prefix(&prefix, index)).collect::<Vec<_>>();
+    assert!(subnets[..16].iter().all(Option::is_some));
+    assert!(subnets[16..].iter().all(Option::is_none));
+}
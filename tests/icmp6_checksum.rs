@@ -0,0 +1,90 @@
+//! Checksums computed by `Icmp6Writer` must be accepted by `Icmp6Parser` and corruption detected.
+
+use ftthd::icmp6::checksum::icmp6_checksum;
+use ftthd::icmp6::packet::{Packet, PacketInfo};
+use ftthd::icmp6::{Icmp6Packet, Icmp6Parser, Icmp6Writer};
+use ftthd::interface::InterfaceId;
+
+use proptest::prelude::*;
+
+use std::net::Ipv6Addr;
+
+/// Turns a packet built for sending into the same packet as seen by the receiver
+fn received(writer: &Icmp6Writer) -> Packet {
+    let sent = writer.packet();
+    let info = sent.info.expect("packet info");
+    let data = sent.data();
+
+    let mut packet = Packet::new();
+    packet.data[..data.len()].copy_from_slice(data);
+    packet.data_len = data.len();
+    packet.target_addr = info.addr;
+    packet.info = Some(PacketInfo {
+        addr: sent.target_addr,
+        if_index: info.if_index,
+    });
+    packet
+}
+
+fn echo_writer(source: Ipv6Addr, destination: Ipv6Addr, identifier: u16, sequence: u16, data: Vec<u8>) -> Icmp6Writer {
+    let mut writer = Icmp6Writer::new();
+    writer.set_compute_checksum(true);
+    writer.set_destination(destination);
+    writer.set_packet_info(Some(PacketInfo {
+        addr: source,
+        if_index: InterfaceId::UNSPECIFIED,
+    }));
+    writer.set_packet(Icmp6Packet::EchoRequest { identifier, sequence, data }).unwrap();
+    writer
+}
+
+#[test]
+fn known_echo_request() {
+    let source = "fe80::1".parse().unwrap();
+    let destination = "fe80::2".parse().unwrap();
+    let writer = echo_writer(source, destination, 0x1234, 1, b"ping".to_vec());
+    let data = writer.packet().data();
+    assert_eq!(&data[2..4], &[0x91, 0xae]);
+    assert_eq!(icmp6_checksum(source, destination, data), 0);
+}
+
+#[test]
+fn missing_source_address() {
+    let mut writer = Icmp6Writer::new();
+    writer.set_compute_checksum(true);
+    writer.set_destination(Ipv6Addr::LOCALHOST);
+    let result = writer.set_packet(Icmp6Packet::EchoReply { identifier: 1, sequence: 1, data: Vec::new() });
+    assert!(result.is_err());
+}
+
+proptest! {
+    #[test]
+    fn round_trip(
+        source in any::<u128>(),
+        destination in any::<u128>(),
+        identifier in any::<u16>(),
+        sequence in any::<u16>(),
+        data in prop::collection::vec(any::<u8>(), 0..256),
+    ) {
+        let writer = echo_writer(Ipv6Addr::from(source), Ipv6Addr::from(destination), identifier, sequence, data);
+        let mut parser = Icmp6Parser::new_from_packet(received(&writer));
+        parser.set_verify_checksum(true);
+        prop_assert!(parser.parse().is_ok());
+    }
+
+    #[test]
+    fn corruption_detected(
+        data in prop::collection::vec(any::<u8>(), 1..256),
+        index in any::<prop::sample::Index>(),
+        flip in 1u8..=255,
+    ) {
+        let writer = echo_writer("2001:db8::1".parse().unwrap(), "2001:db8::2".parse().unwrap(), 7, 7, data);
+        let mut packet = received(&writer);
+        let index = index.index(packet.data_len);
+        packet.data[index] ^= flip;
+
+        let mut parser = Icmp6Parser::new_from_packet(packet);
+        parser.set_verify_checksum(true);
+        prop_assert!(parser.parse().is_err());
+    }
+}
@@ -0,0 +1,150 @@
+//! Property tests: `Icmp6Parser::parse` must return `Icmp6Error` on malformed input, never panic or hang.
+
+use ftthd::icmp6::packet::{Packet, PacketHopByHop, PacketHopLimit};
+use ftthd::icmp6::Icmp6Parser;
+
+use proptest::prelude::*;
+
+/// Hop-by-Hop header carrying a Router Alert for MLD (RFC 2711)
+const ROUTER_ALERT_MLD: [u8; 8] = [58, 0, 0x05, 0x02, 0x00, 0x00, 0x01, 0x00];
+
+fn packet(data: &[u8], data_len: usize, hop_limit: Option<u8>, hop_by_hop: Option<Vec<u8>>) -> Packet {
+    let mut packet = Packet::new();
+    packet.data[..data.len()].copy_from_slice(data);
+    packet.data_len = data_len;
+    packet.hop_limit = hop_limit.map(|hop_limit| PacketHopLimit { hop_limit });
+    packet.hop_by_hop = hop_by_hop.map(|hop_by_hop| PacketHopByHop { hop_by_hop });
+    packet
+}
+
+fn parse(packet: Packet) {
+    let _ = Icmp6Parser::new_from_packet(packet).parse();
+}
+
+/// Known message types followed by arbitrary bytes, so every decoder arm gets exercised
+fn icmp6_message() -> impl Strategy<Value = Vec<u8>> {
+    let known = prop::sample::select(vec![1u8, 2, 3, 4, 128, 129, 130, 131, 132, 133, 134, 135, 136, 137, 138, 143]);
+    (prop_oneof![known, any::<u8>()], prop::collection::vec(any::<u8>(), 0..512)).prop_map(|(icmp6_type, mut rest)| {
+        rest.insert(0, icmp6_type);
+        rest
+    })
+}
+
+/// NDP option area built from options with arbitrary (possibly zero or overlong) lengths
+fn ndp_options() -> impl Strategy<Value = Vec<u8>> {
+    prop::collection::vec((any::<u8>(), 0u8..8, prop::collection::vec(any::<u8>(), 0..64)), 0..8).prop_map(|options| {
+        let mut buf = Vec::new();
+        for (option_type, option_length, body) in options {
+            buf.push(option_type);
+            buf.push(option_length);
+            buf.extend_from_slice(&body);
+        }
+        buf
+    })
+}
+
+proptest! {
+    #[test]
+    fn arbitrary_bytes(
+        data in prop::collection::vec(any::<u8>(), 0..2048),
+        hop_limit in prop::option::of(any::<u8>()),
+        hop_by_hop in prop::option::of(prop::collection::vec(any::<u8>(), 0..64)),
+    ) {
+        parse(packet(&data, data.len(), hop_limit, hop_by_hop));
+    }
+
+    #[test]
+    fn data_len_out_of_range(data in prop::collection::vec(any::<u8>(), 0..64), data_len in any::<usize>()) {
+        parse(packet(&data, data_len, None, None));
+    }
+
+    #[test]
+    fn known_types(data in icmp6_message()) {
+        parse(packet(&data, data.len(), None, None));
+        parse(packet(&data, data.len(), Some(1), Some(ROUTER_ALERT_MLD.to_vec())));
+    }
+
+    #[test]
+    fn hop_by_hop_options(hop_by_hop in prop::collection::vec(prop_oneof![Just(0u8), Just(1), Just(2), Just(5), any::<u8>()], 0..32)) {
+        let query = [130u8, 0, 0, 0, 0, 0, 0, 0, 0xff, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01];
+        parse(packet(&query, query.len(), Some(1), Some(hop_by_hop)));
+    }
+
+    #[test]
+    fn ndp_option_lengths(icmp6_type in 133u8..=137, options in ndp_options()) {
+        let header_len = match icmp6_type {
+            133 => 8,
+            134 => 16,
+            137 => 40,
+            _ => 24,
+        };
+        let mut data = vec![0u8; header_len];
+        data[0] = icmp6_type;
+        data.extend_from_slice(&options);
+        parse(packet(&data, data.len(), Some(255), None));
+    }
+
+    #[test]
+    fn mldv2_record_counts(
+        numrecords in any::<u16>(),
+        records in prop::collection::vec((any::<u8>(), any::<u8>(), 0u16..4, prop::collection::vec(any::<u8>(), 0..96)), 0..4),
+    ) {
+        let mut data = vec![143u8, 0, 0, 0, 0, 0];
+        data.extend_from_slice(&numrecords.to_be_bytes());
+        for (record_type, aux_data_len, numsources, body) in records {
+            data.push(record_type);
+            data.push(aux_data_len);
+            data.extend_from_slice(&numsources.to_be_bytes());
+            data.extend_from_slice(&body);
+        }
+        parse(packet(&data, data.len(), Some(1), Some(ROUTER_ALERT_MLD.to_vec())));
+    }
+}
+
+#[test]
+fn ndp_option_zero_length() {
+    let data = [133u8, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0];
+    assert!(Icmp6Parser::new_from_packet(packet(&data, data.len(), Some(255), None)).parse().is_err());
+}
+
+#[test]
+fn ndp_option_at_end() {
+    let data = [133u8, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0x02, 0, 0, 0, 0, 0x01];
+    let parsed = Icmp6Parser::new_from_packet(packet(&data, data.len(), Some(255), None)).parse();
+    match parsed {
+        Ok(ftthd::icmp6::Icmp6Packet::RouterSolicitation(rs)) => assert_eq!(rs.options.len(), 1),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn hop_by_hop_truncated() {
+    let query = [130u8, 0, 0, 0, 0, 0, 0, 0, 0xff, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01];
+    let hop_by_hop = vec![58, 0, 0x05, 0x02, 0x00];
+    assert!(Icmp6Parser::new_from_packet(packet(&query, query.len(), Some(1), Some(hop_by_hop))).parse().is_err());
+}
+
+#[test]
+fn mld_requirement_reported() {
+    use ftthd::icmp6::packet::{HopByHopOption, HopByHopOptions};
+
+    let query = [130u8, 0, 0, 0, 0, 0, 0, 0, 0xff, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01];
+    let rsvp = HopByHopOptions {
+        next_header: 58,
+        options: vec![HopByHopOption::RouterAlert(1)],
+    };
+    let padding = HopByHopOptions {
+        next_header: 58,
+        options: vec![HopByHopOption::PadN(4)],
+    };
+    let cases = [
+        (255, Some(HopByHopOptions::mld().serialize()), "MLD packet with hop limit other than 1"),
+        (1, None, "MLD packet without hop-by-hop options"),
+        (1, Some(padding.serialize()), "MLD packet without router alert"),
+        (1, Some(rsvp.serialize()), "MLD packet with non-MLD router alert"),
+    ];
+    for (hop_limit, hop_by_hop, message) in cases {
+        let parsed = Icmp6Parser::new_from_packet(packet(&query, query.len(), Some(hop_limit), hop_by_hop)).parse();
+        assert_eq!(parsed.unwrap_err().to_string(), message);
+    }
+}
@@ -0,0 +1,239 @@
+//! Every `Icmp6Packet` variant encoded by `Icmp6Writer` must parse back to an equal value.
+
+use ftthd::icmp6::error_message::*;
+use ftthd::icmp6::mld::*;
+use ftthd::icmp6::ndp::*;
+use ftthd::icmp6::packet::{Packet, PacketHopByHop, PacketHopLimit};
+use ftthd::icmp6::{Icmp6Packet, Icmp6Parser, Icmp6Writer};
+
+use proptest::prelude::*;
+
+use std::net::Ipv6Addr;
+
+fn round_trip(packet: Icmp6Packet) -> Result<Icmp6Packet, String> {
+    let mut writer = Icmp6Writer::new();
+    writer.set_packet(packet).map_err(|e| e.to_string())?;
+
+    let sent = writer.packet();
+    let mut received = Packet::new();
+    received.data[..sent.data().len()].copy_from_slice(sent.data());
+    received.data_len = sent.data().len();
+    received.hop_limit = Some(PacketHopLimit {
+        hop_limit: sent.hop_limit.map(|hop_limit| hop_limit.hop_limit).unwrap_or(255),
+    });
+    received.hop_by_hop = sent.hop_by_hop.as_ref().map(|hop_by_hop| PacketHopByHop {
+        hop_by_hop: hop_by_hop.hop_by_hop.clone(),
+    });
+
+    Icmp6Parser::new_from_packet(received).parse().map_err(|e| e.to_string())
+}
+
+fn addr() -> impl Strategy<Value = Ipv6Addr> {
+    any::<u128>().prop_map(Ipv6Addr::from)
+}
+
+fn invoking_packet() -> impl Strategy<Value = InvokingPacket> {
+    let header = (any::<u8>(), 0u32..(1 << 20), any::<u16>(), any::<u8>(), any::<u8>(), addr(), addr()).prop_map(
+        |(traffic_class, flow_label, payload_length, next_header, hop_limit, source, destination)| Ipv6Header {
+            traffic_class,
+            flow_label,
+            payload_length,
+            next_header,
+            hop_limit,
+            source,
+            destination,
+        },
+    );
+    (header, prop::collection::vec(any::<u8>(), 0..256)).prop_map(|(header, payload)| InvokingPacket {
+        header: Some(header),
+        payload,
+    })
+}
+
+fn ndp_option() -> impl Strategy<Value = NdpOption> {
+    prop_oneof![
+        prop::collection::vec(any::<u8>(), 6).prop_map(NdpOption::SourceLinkLayer),
+        prop::collection::vec(any::<u8>(), 6).prop_map(NdpOption::TargetLinkLayer),
+        any::<u32>().prop_map(NdpOption::Mtu),
+        prop::collection::vec(any::<u8>(), 6).prop_map(NdpOption::Nonce),
+        (0u8..=128, any::<bool>(), any::<bool>(), any::<bool>(), any::<u32>(), any::<u32>(), addr()).prop_map(
+            |(prefix_len, on_link, autonomous, router_address, valid_lifetime, preferred_lifetime, prefix)| {
+                NdpOption::PrefixInformation(PrefixInformation {
+                    prefix_len,
+                    on_link,
+                    autonomous,
+                    router_address,
+                    valid_lifetime,
+                    preferred_lifetime,
+                    prefix,
+                })
+            }
+        ),
+        (any::<u32>(), prop::collection::vec(addr(), 1..4)).prop_map(|(lifetime, servers)| NdpOption::Rdnss(Rdnss { lifetime, servers })),
+    ]
+}
+
+fn ndp_options() -> impl Strategy<Value = Vec<NdpOption>> {
+    prop::collection::vec(ndp_option(), 0..6)
+}
+
+fn error_message() -> impl Strategy<Value = Icmp6Packet> {
+    prop_oneof![
+        (any::<u8>(), invoking_packet()).prop_map(|(code, invoking_packet)| {
+            Icmp6Packet::DestinationUnreachable(DestinationUnreachable { code: code.into(), invoking_packet })
+        }),
+        (any::<u32>(), invoking_packet()).prop_map(|(mtu, invoking_packet)| {
+            Icmp6Packet::PacketTooBig(PacketTooBig { mtu, invoking_packet })
+        }),
+        (any::<u8>(), invoking_packet()).prop_map(|(code, invoking_packet)| {
+            Icmp6Packet::TimeExceeded(TimeExceeded { code: code.into(), invoking_packet })
+        }),
+        (any::<u8>(), any::<u32>(), invoking_packet()).prop_map(|(code, pointer, invoking_packet)| {
+            Icmp6Packet::ParameterProblem(ParameterProblem { code: code.into(), pointer, invoking_packet })
+        }),
+    ]
+}
+
+fn echo() -> impl Strategy<Value = Icmp6Packet> {
+    prop_oneof![
+        (any::<u16>(), any::<u16>(), prop::collection::vec(any::<u8>(), 0..256)).prop_map(|(identifier, sequence, data)| {
+            Icmp6Packet::EchoRequest { identifier, sequence, data }
+        }),
+        (any::<u16>(), any::<u16>(), prop::collection::vec(any::<u8>(), 0..256)).prop_map(|(identifier, sequence, data)| {
+            Icmp6Packet::EchoReply { identifier, sequence, data }
+        }),
+        (any::<u16>(), any::<u8>(), any::<bool>(), prop::collection::vec(any::<u8>(), 0..64)).prop_map(|(identifier, sequence, local, data)| {
+            Icmp6Packet::ExtendedEchoRequest { identifier, sequence, local, data }
+        }),
+        (any::<u8>(), any::<u16>(), any::<u8>(), 0u8..8, any::<bool>(), any::<bool>(), any::<bool>()).prop_map(
+            |(code, identifier, sequence, state, active, ipv4, ipv6)| {
+                Icmp6Packet::ExtendedEchoReply { code, identifier, sequence, state, active, ipv4, ipv6 }
+            }
+        ),
+        (any::<u8>(), any::<u16>(), any::<u16>(), any::<u64>(), prop::collection::vec(any::<u8>(), 0..64)).prop_map(
+            |(code, qtype, flags, nonce, data)| Icmp6Packet::NodeInformationQuery { code, qtype, flags, nonce, data }
+        ),
+        (any::<u8>(), any::<u16>(), any::<u16>(), any::<u64>(), prop::collection::vec(any::<u8>(), 0..64)).prop_map(
+            |(code, qtype, flags, nonce, data)| Icmp6Packet::NodeInformationResponse { code, qtype, flags, nonce, data }
+        ),
+    ]
+}
+
+fn mld() -> impl Strategy<Value = Icmp6Packet> {
+    let record = (any::<u8>(), addr(), prop::collection::vec(addr(), 0..4)).prop_map(|(record_type, multicast_address, source_addresses)| {
+        MulticastReportRecord { record_type, multicast_address, source_addresses }
+    });
+    prop_oneof![
+        (any::<u16>(), addr(), any::<bool>(), 0u8..8, any::<u8>(), prop::collection::vec(addr(), 0..8)).prop_map(
+            |(maximum_response_delay, group_address, supress_router_processing, qrv, qqic, source_addresses)| {
+                Icmp6Packet::MulticastListenerQuery(MulticastListenerQuery {
+                    maximum_response_delay,
+                    group_address,
+                    supress_router_processing,
+                    qrv,
+                    qqic,
+                    source_addresses,
+                })
+            }
+        ),
+        addr().prop_map(|group_address| Icmp6Packet::V1MulticastListenerReport(V1MulticastListenerReport { group_address })),
+        addr().prop_map(|group_address| Icmp6Packet::V1MulticastListenerDone(V1MulticastListenerDone { group_address })),
+        prop::collection::vec(record, 0..4).prop_map(|records| Icmp6Packet::V2MulticastListenerReport(V2MulticastListenerReport { records })),
+    ]
+}
+
+fn ndp() -> impl Strategy<Value = Icmp6Packet> {
+    prop_oneof![
+        ndp_options().prop_map(|options| Icmp6Packet::RouterSolicitation(RouterSolicitation { options })),
+        (any::<u8>(), any::<bool>(), any::<bool>(), any::<u16>(), any::<u32>(), any::<u32>(), ndp_options()).prop_map(
+            |(hop_limit, managed_address_configuration, other_configuration, router_lifetime, reachable_time, retrans_timer, options)| {
+                Icmp6Packet::RouterAdvertisement(RouterAdvertisement {
+                    hop_limit,
+                    managed_address_configuration,
+                    other_configuration,
+                    router_lifetime,
+                    reachable_time,
+                    retrans_timer,
+                    options,
+                })
+            }
+        ),
+        (addr(), ndp_options()).prop_map(|(target_address, options)| {
+            Icmp6Packet::NeighborSolicitation(NeighborSolicitation { target_address, options })
+        }),
+        (any::<bool>(), any::<bool>(), any::<bool>(), addr(), ndp_options()).prop_map(|(router, solicited, override_, target_address, options)| {
+            Icmp6Packet::NeighborAdvertisement(NeighborAdvertisement { router, solicited, override_, target_address, options })
+        }),
+        (addr(), addr(), ndp_options()).prop_map(|(target_address, destination_address, options)| {
+            Icmp6Packet::Redirect(Redirect { target_address, destination_address, options })
+        }),
+    ]
+}
+
+fn header_only() -> impl Strategy<Value = Icmp6Packet> {
+    prop_oneof![
+        any::<u8>().prop_map(Icmp6Packet::RouterRenumbering),
+        Just(Icmp6Packet::InverseNeighborDiscoverySolicitation),
+        Just(Icmp6Packet::InverseNeighborDiscoveryAdvertisement),
+        Just(Icmp6Packet::HomeAgentAddressDiscoveryRequest),
+        Just(Icmp6Packet::HomeAgentAddressDiscoveryReply),
+        Just(Icmp6Packet::MobilePrefixSolicitation),
+        Just(Icmp6Packet::MobilePrefixAdvertisement),
+        any::<u8>().prop_map(Icmp6Packet::DuplicateAddressRequest),
+        any::<u8>().prop_map(Icmp6Packet::DuplicateAddressConfirmation),
+        (prop::sample::select(vec![5u8, 100, 150, 200, 255]), any::<u8>()).prop_map(|(icmp6_type, code)| Icmp6Packet::Unknown(icmp6_type, code)),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn every_variant(packet in prop_oneof![error_message(), echo(), mld(), ndp(), header_only()]) {
+        prop_assert_eq!(round_trip(packet.clone()), Ok(packet));
+    }
+}
+
+#[test]
+fn error_message_truncated_to_minimum_mtu() {
+    let packet = Icmp6Packet::PacketTooBig(PacketTooBig {
+        mtu: 1280,
+        invoking_packet: InvokingPacket {
+            header: Ipv6Header::parse(&[0x60; 40]),
+            payload: vec![0xaa; 2000],
+        },
+    });
+    let mut writer = Icmp6Writer::new();
+    writer.set_packet(packet).unwrap();
+    assert_eq!(writer.packet().data().len(), ftthd::icmp6::MAX_ERROR_MESSAGE_LEN);
+}
+
+fn hop_by_hop_option() -> impl Strategy<Value = ftthd::icmp6::packet::HopByHopOption> {
+    use ftthd::icmp6::packet::HopByHopOption;
+
+    prop_oneof![
+        Just(HopByHopOption::Pad1),
+        (0u8..8).prop_map(HopByHopOption::PadN),
+        any::<u16>().prop_map(HopByHopOption::RouterAlert),
+        any::<u32>().prop_map(HopByHopOption::Jumbo),
+        (prop::sample::select(vec![0x1eu8, 0x3e, 0x5e, 0x63, 0xff]), prop::collection::vec(any::<u8>(), 0..16))
+            .prop_map(|(option_type, data)| HopByHopOption::Unknown(option_type, data)),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn hop_by_hop_options(next_header in any::<u8>(), options in prop::collection::vec(hop_by_hop_option(), 0..6)) {
+        use ftthd::icmp6::packet::{HopByHopOption, HopByHopOptions};
+
+        let hop_by_hop = HopByHopOptions { next_header, options };
+        let data = hop_by_hop.serialize();
+        prop_assert_eq!(data.len() % 8, 0);
+
+        // trailing padding is the only difference after a round trip
+        let mut parsed = HopByHopOptions::parse(&data).unwrap();
+        while parsed.options.len() > hop_by_hop.options.len() {
+            let padding = parsed.options.pop();
+            prop_assert!(matches!(padding, Some(HopByHopOption::Pad1 | HopByHopOption::PadN(_))));
+        }
+        prop_assert_eq!(parsed, hop_by_hop);
+    }
+}
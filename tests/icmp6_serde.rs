@@ -0,0 +1,50 @@
+//! JSON representation of parsed packets, enabled by the `serde` feature.
+#![cfg(feature = "serde")]
+
+use ftthd::icmp6::mld::*;
+use ftthd::icmp6::ndp::*;
+use ftthd::icmp6::Icmp6Packet;
+
+#[test]
+fn json_round_trip() {
+    let packets = vec![
+        Icmp6Packet::RouterAdvertisement(RouterAdvertisement {
+            hop_limit: 64,
+            managed_address_configuration: false,
+            other_configuration: true,
+            router_lifetime: 1800,
+            reachable_time: 0,
+            retrans_timer: 0,
+            options: vec![
+                NdpOption::SourceLinkLayer(vec![2, 0, 0, 0, 0, 1]),
+                NdpOption::Rdnss(Rdnss {
+                    lifetime: 1800,
+                    servers: vec!["2001:db8::53".parse().unwrap()],
+                }),
+            ],
+        }),
+        Icmp6Packet::V2MulticastListenerReport(V2MulticastListenerReport {
+            records: vec![MulticastReportRecord {
+                record_type: 4,
+                multicast_address: "ff05::1:3".parse().unwrap(),
+                source_addresses: Vec::new(),
+            }],
+        }),
+        Icmp6Packet::EchoRequest { identifier: 1, sequence: 2, data: vec![3] },
+    ];
+
+    for packet in packets {
+        let json = serde_json::to_string(&packet).unwrap();
+        let decoded: Icmp6Packet = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, packet);
+    }
+}
+
+#[test]
+fn addresses_as_strings() {
+    let packet = Icmp6Packet::V1MulticastListenerReport(V1MulticastListenerReport {
+        group_address: "ff02::fb".parse().unwrap(),
+    });
+    let json = serde_json::to_value(&packet).unwrap();
+    assert_eq!(json["V1MulticastListenerReport"]["group_address"], "ff02::fb");
+}
@@ -0,0 +1,133 @@
+//! Borrowed views must decode exactly what `Icmp6Parser::parse` does, and option patching must keep messages well-formed.
+
+use ftthd::icmp6::ndp::*;
+use ftthd::icmp6::packet::{Packet, PacketHopByHop, PacketHopLimit};
+use ftthd::icmp6::view::Icmp6PacketRef;
+use ftthd::icmp6::{Icmp6Packet, Icmp6Parser, Icmp6Writer};
+
+use proptest::prelude::*;
+
+use std::net::Ipv6Addr;
+
+/// Hop-by-Hop header carrying a Router Alert for MLD (RFC 2711)
+const ROUTER_ALERT_MLD: [u8; 8] = [58, 0, 0x05, 0x02, 0x00, 0x00, 0x01, 0x00];
+
+fn parser(data: &[u8]) -> Icmp6Parser {
+    let mut packet = Packet::new();
+    packet.data[..data.len()].copy_from_slice(data);
+    packet.data_len = data.len();
+    packet.hop_limit = Some(PacketHopLimit { hop_limit: 1 });
+    packet.hop_by_hop = Some(PacketHopByHop { hop_by_hop: ROUTER_ALERT_MLD.to_vec() });
+    Icmp6Parser::new_from_packet(packet)
+}
+
+fn owned(packet: Icmp6PacketRef) -> Option<Icmp6Packet> {
+    match packet {
+        Icmp6PacketRef::MulticastListenerQuery(query) => Some(Icmp6Packet::MulticastListenerQuery(query.into_owned())),
+        Icmp6PacketRef::RouterSolicitation(rs) => Some(Icmp6Packet::RouterSolicitation(rs.into_owned())),
+        Icmp6PacketRef::RouterAdvertisement(ra) => Some(Icmp6Packet::RouterAdvertisement(ra.into_owned())),
+        Icmp6PacketRef::NeighborSolicitation(ns) => Some(Icmp6Packet::NeighborSolicitation(ns.into_owned())),
+        Icmp6PacketRef::NeighborAdvertisement(na) => Some(Icmp6Packet::NeighborAdvertisement(na.into_owned())),
+        Icmp6PacketRef::Redirect(redirect) => Some(Icmp6Packet::Redirect(redirect.into_owned())),
+        Icmp6PacketRef::V2MulticastListenerReport(report) => Some(Icmp6Packet::V2MulticastListenerReport(report.into_owned())),
+        _ => None,
+    }
+}
+
+fn neighbor_solicitation(options: Vec<NdpOption>) -> Icmp6Writer {
+    let mut writer = Icmp6Writer::new();
+    writer.set_packet(Icmp6Packet::NeighborSolicitation(NeighborSolicitation {
+        target_address: "2001:db8::1".parse().unwrap(),
+        options,
+    })).unwrap();
+    writer
+}
+
+fn options_of(writer: &Icmp6Writer) -> Vec<NdpOption> {
+    match parser(writer.packet().data()).parse() {
+        Ok(Icmp6Packet::NeighborSolicitation(ns)) => ns.options,
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+proptest! {
+    #[test]
+    fn view_matches_parse(
+        icmp6_type in prop::sample::select(vec![130u8, 133, 134, 135, 136, 137, 143]),
+        rest in prop::collection::vec(any::<u8>(), 0..256),
+    ) {
+        let mut data = vec![icmp6_type];
+        data.extend_from_slice(&rest);
+        let parser = parser(&data);
+
+        match (parser.parse(), parser.parse_ref()) {
+            (Ok(packet), Ok(view)) => prop_assert_eq!(Some(packet), owned(view)),
+            (Err(e1), Err(e2)) => prop_assert_eq!(e1, e2),
+            (packet, view) => prop_assert!(false, "parse: {:?}, parse_ref: {:?}", packet, view),
+        }
+    }
+}
+
+#[test]
+fn lazy_options() {
+    let writer = neighbor_solicitation(vec![
+        NdpOption::Nonce(vec![1, 2, 3, 4, 5, 6]),
+        NdpOption::SourceLinkLayer(vec![2, 0, 0, 0, 0, 1]),
+    ]);
+    let parser = parser(writer.packet().data());
+    let ns = match parser.parse_ref() {
+        Ok(Icmp6PacketRef::NeighborSolicitation(ns)) => ns,
+        other => panic!("unexpected {:?}", other),
+    };
+
+    assert_eq!(ns.target_address(), "2001:db8::1".parse::<Ipv6Addr>().unwrap());
+    assert_eq!(ns.options().iter().count(), 2);
+    assert_eq!(ns.options().find(ND_OPT_SOURCE_LINKADDR).map(|option| option.data), Some(&[2u8, 0, 0, 0, 0, 1][..]));
+}
+
+#[test]
+fn replace_in_place() {
+    let mut writer = neighbor_solicitation(vec![
+        NdpOption::SourceLinkLayer(vec![2, 0, 0, 0, 0, 1]),
+        NdpOption::Nonce(vec![1, 2, 3, 4, 5, 6]),
+    ]);
+    let len = writer.packet().data().len();
+
+    writer.replace_ndp_option(&NdpOption::SourceLinkLayer(vec![2, 0, 0, 0, 0, 2])).unwrap();
+    assert_eq!(writer.packet().data().len(), len);
+    assert_eq!(options_of(&writer), vec![
+        NdpOption::SourceLinkLayer(vec![2, 0, 0, 0, 0, 2]),
+        NdpOption::Nonce(vec![1, 2, 3, 4, 5, 6]),
+    ]);
+}
+
+#[test]
+fn replace_with_different_length() {
+    let mut writer = neighbor_solicitation(vec![
+        NdpOption::SourceLinkLayer(vec![2, 0, 0, 0, 0, 1]),
+        NdpOption::Nonce(vec![1, 2, 3, 4, 5, 6]),
+        NdpOption::SourceLinkLayer(vec![2, 0, 0, 0, 0, 3]),
+    ]);
+
+    let address = vec![0xaa; 14];
+    writer.replace_ndp_option(&NdpOption::SourceLinkLayer(address.clone())).unwrap();
+    assert_eq!(options_of(&writer), vec![
+        NdpOption::Nonce(vec![1, 2, 3, 4, 5, 6]),
+        NdpOption::SourceLinkLayer(address),
+    ]);
+}
+
+#[test]
+fn remove_options() {
+    let mut raw = neighbor_solicitation(vec![
+        NdpOption::SourceLinkLayer(vec![2, 0, 0, 0, 0, 1]),
+        NdpOption::Nonce(vec![1, 2, 3, 4, 5, 6]),
+    ]);
+    let mut writer = Icmp6Writer::new();
+    writer.set_raw_packet(raw.packet().data()).unwrap();
+    writer.remove_ndp_options(ND_OPT_SOURCE_LINKADDR).unwrap();
+    assert_eq!(options_of(&writer), vec![NdpOption::Nonce(vec![1, 2, 3, 4, 5, 6])]);
+
+    raw.set_packet(Icmp6Packet::EchoRequest { identifier: 1, sequence: 1, data: Vec::new() }).unwrap();
+    assert!(raw.remove_ndp_options(ND_OPT_SOURCE_LINKADDR).is_err());
+}
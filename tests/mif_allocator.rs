@@ -0,0 +1,41 @@
+//! Allocation of MIF numbers across interface reconfigurations.
+
+use ftthd::group::MifAllocator;
+use ftthd::icmp6::socket::MAXMIFS;
+
+#[test]
+fn freed_mifs_are_reused() {
+    let mut allocator = MifAllocator::default();
+    let upstream = allocator.allocate().unwrap();
+
+    // one downstream removed and added back on every reload
+    for _ in 0..(MAXMIFS * 3) {
+        let vifd = allocator.allocate().expect("MIFs ran out");
+        assert_ne!(vifd, upstream);
+        allocator.free(vifd);
+    }
+}
+
+#[test]
+fn lowest_unused_mif_is_allocated() {
+    let mut allocator = MifAllocator::default();
+    assert_eq!(allocator.allocate(), Some(0));
+    assert_eq!(allocator.allocate(), Some(1));
+    assert_eq!(allocator.allocate(), Some(2));
+
+    allocator.free(1);
+    assert_eq!(allocator.allocate(), Some(1));
+    assert_eq!(allocator.allocate(), Some(3));
+}
+
+#[test]
+fn allocation_fails_beyond_maxmifs() {
+    let mut allocator = MifAllocator::default();
+    for _ in 0..MAXMIFS {
+        assert!(allocator.allocate().is_some());
+    }
+    assert_eq!(allocator.allocate(), None);
+
+    allocator.free(5);
+    assert_eq!(allocator.allocate(), Some(5));
+}
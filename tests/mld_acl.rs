@@ -0,0 +1,157 @@
+//! Matching of downstream multicast access control lists and their enforcement on reports.
+
+mod common;
+
+use common::{downstream, manager};
+use ftthd::config::{MldAclConfig, MldAclPrefix};
+use ftthd::group::FilterMode;
+use ftthd::icmp6::mld::*;
+
+use std::collections::{HashMap, HashSet};
+use std::net::Ipv6Addr;
+
+fn prefix(prefix: &str, prefix_len: u8) -> MldAclPrefix {
+    MldAclPrefix {
+        prefix: prefix.parse().unwrap(),
+        prefix_len,
+    }
+}
+
+fn addr(addr: &str) -> Ipv6Addr {
+    addr.parse().unwrap()
+}
+
+#[test]
+fn empty_acl_permits_everything() {
+    let acl = MldAclConfig::default();
+    assert!(acl.permits_group(&addr("ff3e::8000:1")));
+    assert!(acl.permits_source(&addr("2001:db8::1")));
+    assert!(acl.permits_any_source());
+}
+
+#[test]
+fn deny_overrides_allow() {
+    let acl = MldAclConfig {
+        allow_groups: vec![prefix("ff3e::", 16)],
+        deny_groups: vec![prefix("ff3e::8000:0", 112)],
+        allow_sources: vec![prefix("2001:db8::", 32)],
+        deny_sources: vec![prefix("2001:db8:bad::", 48)],
+        max_groups: None,
+    };
+
+    assert!(acl.permits_group(&addr("ff3e::1")));
+    assert!(!acl.permits_group(&addr("ff3e::8000:1")));
+    assert!(!acl.permits_group(&addr("ff05::1")));
+
+    assert!(acl.permits_source(&addr("2001:db8::1")));
+    assert!(!acl.permits_source(&addr("2001:db8:bad::1")));
+    assert!(!acl.permits_source(&addr("2001:db9::1")));
+    assert!(!acl.permits_any_source());
+}
+
+#[test]
+fn zero_length_prefix_matches_all() {
+    assert!(prefix("::", 0).contains(&addr("ff3e::1")));
+    assert!(prefix("ff3e::1", 128).contains(&addr("ff3e::1")));
+    assert!(!prefix("ff3e::1", 128).contains(&addr("ff3e::2")));
+}
+
+fn record(record_type: u8, group_addr: &str, source_addresses: &[&str]) -> MulticastReportRecord {
+    MulticastReportRecord {
+        record_type,
+        multicast_address: addr(group_addr),
+        source_addresses: source_addresses.iter().map(|source_addr| addr(source_addr)).collect(),
+    }
+}
+
+fn sources(source_addrs: &[&str]) -> HashSet<Ipv6Addr> {
+    source_addrs.iter().map(|source_addr| addr(source_addr)).collect()
+}
+
+fn filtering_acl() -> MldAclConfig {
+    MldAclConfig {
+        deny_groups: vec![prefix("ff3e::8000:0", 112)],
+        deny_sources: vec![prefix("2001:db8:bad::", 48)],
+        ..Default::default()
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn max_groups_caps_new_joins() {
+    let mut manager = manager();
+    manager.set_interface_acls(HashMap::from([(downstream(1), MldAclConfig {
+        max_groups: Some(2),
+        ..Default::default()
+    })]));
+
+    for group_addr in ["ff3e::1", "ff3e::2", "ff3e::3"] {
+        manager.process_record(downstream(1), &record(MODE_IS_EXCLUDE, group_addr, &[]));
+    }
+    assert!(manager.get_subscription(downstream(1), addr("ff3e::1")).is_some());
+    assert!(manager.get_subscription(downstream(1), addr("ff3e::2")).is_some());
+    assert!(manager.get_subscription(downstream(1), addr("ff3e::3")).is_none());
+    assert!(manager.get_membership(addr("ff3e::3")).is_none());
+
+    // groups already joined are still refreshed, and other interfaces are not limited
+    manager.process_record(downstream(1), &record(MODE_IS_INCLUDE, "ff3e::2", &["2001:db8::1"]));
+    assert!(manager.get_subscription(downstream(1), addr("ff3e::2")).unwrap().sources.contains_key(&addr("2001:db8::1")));
+    manager.process_record(downstream(2), &record(MODE_IS_EXCLUDE, "ff3e::3", &[]));
+    assert!(manager.get_membership(addr("ff3e::3")).is_some());
+}
+
+#[tokio::test(start_paused = true)]
+async fn source_filters_deny_exclude_joins() {
+    let mut manager = manager();
+    manager.set_interface_acls(HashMap::from([(downstream(1), filtering_acl())]));
+
+    manager.process_record(downstream(1), &record(MODE_IS_EXCLUDE, "ff3e::1", &[]));
+    manager.process_record(downstream(1), &record(CHANGE_TO_EXCLUDE_MODE, "ff3e::2", &["2001:db8:bad::1"]));
+    assert!(manager.get_subscription(downstream(1), addr("ff3e::1")).is_none());
+    assert!(manager.get_subscription(downstream(1), addr("ff3e::2")).is_none());
+
+    manager.process_record(downstream(1), &record(MODE_IS_INCLUDE, "ff3e::1", &["2001:db8::1"]));
+    assert_eq!(manager.get_subscription(downstream(1), addr("ff3e::1")).unwrap().filter_mode, FilterMode::Include);
+}
+
+#[tokio::test(start_paused = true)]
+async fn denied_groups_and_sources_are_stripped() {
+    let mut manager = manager();
+    manager.set_interface_acls(HashMap::from([(downstream(1), filtering_acl())]));
+
+    manager.process_record(downstream(1), &record(MODE_IS_INCLUDE, "ff3e::8000:1", &["2001:db8::1"]));
+    assert!(manager.get_subscription(downstream(1), addr("ff3e::8000:1")).is_none());
+
+    manager.process_record(downstream(1), &record(MODE_IS_INCLUDE, "ff3e::1", &["2001:db8::1", "2001:db8:bad::1"]));
+    manager.process_record(downstream(1), &record(ALLOW_NEW_SOURCES, "ff3e::1", &["2001:db8:bad::2"]));
+    let subscription = manager.get_subscription(downstream(1), addr("ff3e::1")).unwrap();
+    assert_eq!(subscription.requested_sources(), sources(&["2001:db8::1"]));
+    assert_eq!(manager.get_membership(addr("ff3e::1")).unwrap().source_addrs, sources(&["2001:db8::1"]));
+
+    // leaving is always permitted
+    manager.process_record(downstream(1), &record(CHANGE_TO_INCLUDE_MODE, "ff3e::1", &[]));
+    assert!(manager.get_subscription(downstream(1), addr("ff3e::1")).is_some());
+    manager.process_record(downstream(1), &record(BLOCK_OLD_SOURCES, "ff3e::1", &["2001:db8::1"]));
+    assert!(manager.get_subscription(downstream(1), addr("ff3e::1")).is_some());
+}
+
+#[tokio::test(start_paused = true)]
+async fn reload_prunes_existing_state() {
+    let mut manager = manager();
+    manager.process_record(downstream(1), &record(MODE_IS_EXCLUDE, "ff3e::1", &[]));
+    manager.process_record(downstream(1), &record(MODE_IS_INCLUDE, "ff3e::2", &["2001:db8::1", "2001:db8:bad::1"]));
+    manager.process_record(downstream(1), &record(MODE_IS_INCLUDE, "ff3e::8000:1", &["2001:db8::1"]));
+    manager.process_record(downstream(2), &record(MODE_IS_EXCLUDE, "ff3e::1", &[]));
+
+    manager.set_interface_acls(HashMap::from([(downstream(1), filtering_acl())]));
+
+    // the any-source join is dropped on the filtered interface only
+    assert!(manager.get_subscription(downstream(1), addr("ff3e::1")).is_none());
+    assert!(manager.get_subscription(downstream(2), addr("ff3e::1")).is_some());
+    assert_eq!(manager.get_membership(addr("ff3e::1")).unwrap().filter_mode, FilterMode::Exclude);
+
+    assert_eq!(manager.get_subscription(downstream(1), addr("ff3e::2")).unwrap().requested_sources(), sources(&["2001:db8::1"]));
+    assert_eq!(manager.get_membership(addr("ff3e::2")).unwrap().source_addrs, sources(&["2001:db8::1"]));
+
+    assert!(manager.get_subscription(downstream(1), addr("ff3e::8000:1")).is_none());
+    assert!(manager.get_membership(addr("ff3e::8000:1")).is_none());
+}
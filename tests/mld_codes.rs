@@ -0,0 +1,35 @@
+//! Maximum Response Code and QQIC encodings (RFC 3810 sections 5.1.3 and 5.1.9).
+
+use ftthd::icmp6::mld::*;
+
+use proptest::prelude::*;
+
+proptest! {
+    #[test]
+    fn max_response_code_covers_delay(delay in 0u32..=8387584) {
+        let decoded = decode_max_response_code(encode_max_response_code(delay));
+        prop_assert!(decoded <= delay);
+        // the mantissa keeps 12 bits, so the error is below 1/4096 of the delay
+        prop_assert!(delay - decoded <= delay >> 12 << 3 || delay < 0x8000 && decoded == delay);
+    }
+
+    #[test]
+    fn max_response_code_round_trip(code in any::<u16>()) {
+        prop_assert_eq!(encode_max_response_code(decode_max_response_code(code)), code);
+    }
+
+    #[test]
+    fn qqic_round_trip(code in any::<u8>()) {
+        prop_assert_eq!(encode_qqic(decode_qqic(code)), code);
+    }
+}
+
+#[test]
+fn known_values() {
+    assert_eq!(decode_max_response_code(10000), 10000);
+    assert_eq!(decode_max_response_code(0x8000), 0x1000 << 3);
+    assert_eq!(decode_qqic(125), 125);
+    assert_eq!(decode_qqic(0x80), 128);
+    assert_eq!(encode_qqic(31744), 0xff);
+    assert_eq!(encode_qqic(100000), 0xff);
+}
@@ -0,0 +1,110 @@
+//! MFC entries wanted for a group's downstream subscriptions and the changes that install them.
+
+use ftthd::group::{mroute_changes, wanted_mroutes, FilterMode, MldSubscription, MrouteChanges};
+use ftthd::icmp6::socket::mifi_t;
+
+use tokio::time::Instant;
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::net::Ipv6Addr;
+use std::time::Duration;
+
+const GROUP: Ipv6Addr = Ipv6Addr::new(0xff3e, 0, 0, 0, 0, 0, 0x8000, 1);
+const S1: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+const S2: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2);
+const WILDCARD: Ipv6Addr = Ipv6Addr::UNSPECIFIED;
+
+/// INCLUDE mode listening to `sources`
+fn include(sources: &[Ipv6Addr]) -> MldSubscription {
+    let timer = Instant::now() + Duration::from_secs(260);
+    MldSubscription {
+        group_addr: GROUP,
+        filter_mode: FilterMode::Include,
+        group_timer: Instant::now(),
+        sources: sources.iter().map(|addr| (*addr, Some(timer))).collect(),
+        older_host_present: None,
+    }
+}
+
+/// EXCLUDE mode blocking `excluded`
+fn exclude(excluded: &[Ipv6Addr]) -> MldSubscription {
+    MldSubscription {
+        group_addr: GROUP,
+        filter_mode: FilterMode::Exclude,
+        group_timer: Instant::now() + Duration::from_secs(260),
+        sources: excluded.iter().map(|addr| (*addr, None)).collect(),
+        older_host_present: None,
+    }
+}
+
+fn mifs(mifs: &[mifi_t]) -> BTreeSet<mifi_t> {
+    mifs.iter().cloned().collect()
+}
+
+fn mroutes(entries: &[(Ipv6Addr, &[mifi_t])]) -> HashMap<Ipv6Addr, BTreeSet<mifi_t>> {
+    entries.iter().map(|(src, output)| (*src, mifs(output))).collect()
+}
+
+#[test]
+fn include_source_joins_exclude_wildcard() {
+    let (include, exclude) = (include(&[S1]), exclude(&[]));
+    let wanted = wanted_mroutes([(1, &include), (2, &exclude)], &HashSet::new());
+    assert_eq!(wanted, mroutes(&[(S1, &[1, 2]), (WILDCARD, &[2])]));
+
+    assert_eq!(mroute_changes(&HashMap::new(), &wanted), MrouteChanges {
+        delete: Vec::new(),
+        add: vec![(WILDCARD, mifs(&[2])), (S1, mifs(&[1, 2]))],
+    });
+}
+
+#[test]
+fn upcall_sources_follow_the_wildcard() {
+    let (include, exclude) = (include(&[S1]), exclude(&[]));
+    let wanted = wanted_mroutes([(1, &include), (2, &exclude)], &HashSet::from([S2]));
+    assert_eq!(wanted, mroutes(&[(S1, &[1, 2]), (S2, &[2]), (WILDCARD, &[2])]));
+}
+
+#[test]
+fn source_blocked_everywhere_gets_an_entry_without_outputs() {
+    let (first, second) = (exclude(&[S1]), exclude(&[S1]));
+    let wanted = wanted_mroutes([(1, &first), (2, &second)], &HashSet::new());
+    assert_eq!(wanted, mroutes(&[(S1, &[]), (WILDCARD, &[1, 2])]));
+
+    // blocked on one interface only
+    let (first, second) = (exclude(&[S1]), exclude(&[]));
+    let wanted = wanted_mroutes([(1, &first), (2, &second)], &HashSet::new());
+    assert_eq!(wanted, mroutes(&[(S1, &[2]), (WILDCARD, &[1, 2])]));
+}
+
+#[test]
+fn excluded_source_without_wildcard_needs_no_entry() {
+    let exclude = exclude(&[S1]);
+    let include = include(&[S2]);
+    let wanted = wanted_mroutes([(1, &include)], &HashSet::from([S1]));
+    assert_eq!(wanted, mroutes(&[(S2, &[1])]));
+
+    let wanted = wanted_mroutes([(1, &include), (2, &exclude)], &HashSet::new());
+    assert_eq!(wanted, mroutes(&[(S1, &[]), (S2, &[1, 2]), (WILDCARD, &[2])]));
+}
+
+#[test]
+fn last_listener_leaving_removes_all_entries() {
+    let installed = mroutes(&[(S1, &[1, 2]), (WILDCARD, &[2])]);
+    let wanted = wanted_mroutes([], &HashSet::from([S2]));
+    assert!(wanted.is_empty());
+
+    assert_eq!(mroute_changes(&installed, &wanted), MrouteChanges {
+        delete: vec![WILDCARD, S1],
+        add: Vec::new(),
+    });
+}
+
+#[test]
+fn only_changed_entries_are_reinstalled() {
+    let installed = mroutes(&[(S1, &[1, 2]), (S2, &[2]), (WILDCARD, &[2])]);
+    let wanted = mroutes(&[(S1, &[1, 2]), (S2, &[1, 2])]);
+    assert_eq!(mroute_changes(&installed, &wanted), MrouteChanges {
+        delete: vec![WILDCARD],
+        add: vec![(S2, mifs(&[1, 2]))],
+    });
+}
@@ -0,0 +1,208 @@
+//! MLDv2 querier election and query timing on a paused clock (RFC 3810 sections 7.6 and 9).
+
+use ftthd::config::MldQuerierConfig;
+use ftthd::icmp6::mld::{self, MulticastListenerQuery};
+use ftthd::interface::InterfaceId;
+use ftthd::querier::{MldQuerier, QueryTransport};
+
+use parking_lot::Mutex;
+use tokio::time::Instant;
+
+use std::net::Ipv6Addr;
+use std::sync::Arc;
+use std::time::Duration;
+
+const OWN_ADDR: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 2);
+const LOWER_ADDR: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+const HIGHER_ADDR: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 3);
+const ALL_NODES: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+const GROUP: Ipv6Addr = Ipv6Addr::new(0xff3e, 0, 0, 0, 0, 0, 0x8000, 1);
+const S1: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+const S2: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2);
+
+#[derive(Debug, Clone)]
+struct Sent {
+    at: Instant,
+    if_id: InterfaceId,
+    destination: Ipv6Addr,
+    query: MulticastListenerQuery,
+}
+
+/// Records the queries sent; every interface has `OWN_ADDR` as its link-local address
+#[derive(Debug, Clone, Default)]
+struct FakeTransport(Arc<Mutex<Vec<Sent>>>);
+
+impl FakeTransport {
+    fn general_queries(&self, if_id: InterfaceId) -> Vec<Instant> {
+        self.0.lock().iter()
+            .filter(|sent| sent.if_id == if_id && sent.destination == ALL_NODES)
+            .map(|sent| sent.at)
+            .collect()
+    }
+
+    fn specific_queries(&self, if_id: InterfaceId) -> Vec<Sent> {
+        self.0.lock().iter().filter(|sent| sent.if_id == if_id && sent.destination != ALL_NODES).cloned().collect()
+    }
+}
+
+impl QueryTransport for FakeTransport {
+    fn link_local_addr(&self, _if_id: InterfaceId) -> Option<Ipv6Addr> {
+        Some(OWN_ADDR)
+    }
+
+    async fn send_query(&self, if_id: InterfaceId, source: Ipv6Addr, destination: Ipv6Addr, query: MulticastListenerQuery) -> Result<(), std::io::Error> {
+        assert_eq!(source, OWN_ADDR);
+        self.0.lock().push(Sent {
+            at: Instant::now(),
+            if_id,
+            destination,
+            query,
+        });
+        Ok(())
+    }
+}
+
+fn downstream(n: u32) -> InterfaceId {
+    InterfaceId::from(1 + n)
+}
+
+/// Runs a querier on two downstream interfaces
+fn run_querier(config: MldQuerierConfig) -> (Arc<MldQuerier<FakeTransport>>, FakeTransport) {
+    let transport = FakeTransport::default();
+    let querier = Arc::new(MldQuerier::with_transport(transport.clone(), &[downstream(1), downstream(2)], config));
+    let runner = querier.clone();
+    tokio::spawn(async move { runner.run().await });
+    (querier, transport)
+}
+
+fn other_query() -> MulticastListenerQuery {
+    MulticastListenerQuery {
+        maximum_response_delay: mld::encode_max_response_code(10000),
+        group_address: Ipv6Addr::UNSPECIFIED,
+        supress_router_processing: false,
+        qrv: 2,
+        qqic: mld::encode_qqic(125),
+        source_addresses: Vec::new(),
+    }
+}
+
+fn offsets(start: Instant, times: &[Instant]) -> Vec<Duration> {
+    times.iter().map(|at| *at - start).collect()
+}
+
+#[tokio::test(start_paused = true)]
+async fn startup_queries_are_sent_at_a_quarter_query_interval() {
+    for robustness in [2, 3] {
+        let config = MldQuerierConfig {
+            robustness,
+            ..Default::default()
+        };
+        let query_interval = config.query_interval();
+        let start = Instant::now();
+        let (_querier, transport) = run_querier(config);
+        tokio::time::sleep(Duration::from_secs(300)).await;
+
+        // Startup Query Count equals the Robustness Variable, followed by queries every Query Interval
+        let startup_interval = query_interval / 4;
+        let mut expected = (0..robustness as u32).map(|n| startup_interval * n).collect::<Vec<_>>();
+        let last = *expected.last().unwrap();
+        expected.extend((1..).map(|n| last + query_interval * n).take_while(|at| *at <= Duration::from_secs(300)));
+        for n in [1, 2] {
+            let queries = transport.general_queries(downstream(n));
+            assert_eq!(offsets(start, &queries), expected, "robustness {}", robustness);
+        }
+
+        let sent = transport.0.lock()[0].query.clone();
+        assert_eq!(sent.group_address, Ipv6Addr::UNSPECIFIED);
+        assert_eq!(sent.qrv, robustness);
+        assert_eq!(mld::decode_qqic(sent.qqic), 125);
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn lowest_link_local_address_wins_election() {
+    let (querier, transport) = run_querier(MldQuerierConfig::default());
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert!(querier.is_querier(downstream(1)));
+    assert!(querier.is_querier(downstream(2)));
+
+    querier.receive_query(downstream(1), LOWER_ADDR, &other_query());
+    querier.receive_query(downstream(2), HIGHER_ADDR, &other_query());
+    assert!(!querier.is_querier(downstream(1)));
+    assert!(querier.is_querier(downstream(2)));
+
+    // queries from global addresses take no part in the election
+    querier.receive_query(downstream(2), S1, &other_query());
+    assert!(querier.is_querier(downstream(2)));
+
+    // the startup queries stop on the interface we lost
+    tokio::time::sleep(Duration::from_secs(60)).await;
+    assert_eq!(transport.general_queries(downstream(1)).len(), 1);
+    assert_eq!(transport.general_queries(downstream(2)).len(), 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn querier_role_resumes_after_other_querier_present_timeout() {
+    let config = MldQuerierConfig::default();
+    let (querier, transport) = run_querier(config.clone());
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let heard = Instant::now();
+    querier.receive_query(downstream(1), LOWER_ADDR, &other_query());
+
+    // Other Querier Present Interval (RFC 3810 section 9.5)
+    let other_querier_present_interval = config.query_interval() * config.robustness as u32 + config.query_response_interval() / 2;
+    tokio::time::sleep_until(heard + other_querier_present_interval - Duration::from_millis(1)).await;
+    assert!(!querier.is_querier(downstream(1)));
+    assert_eq!(transport.general_queries(downstream(1)).len(), 1);
+
+    tokio::time::sleep(Duration::from_millis(2)).await;
+    assert!(querier.is_querier(downstream(1)));
+    let queries = transport.general_queries(downstream(1));
+    assert_eq!(offsets(heard, &queries[1..]), vec![other_querier_present_interval]);
+
+    // a returning querier skips the startup queries
+    tokio::time::sleep(config.query_interval()).await;
+    let queries = transport.general_queries(downstream(1));
+    assert_eq!(offsets(heard, &queries[1..]), vec![other_querier_present_interval, other_querier_present_interval + config.query_interval()]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn specific_queries_are_retransmitted() {
+    let config = MldQuerierConfig::default();
+    let (querier, transport) = run_querier(config.clone());
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let start = Instant::now();
+    querier.query_group(downstream(1), GROUP);
+    querier.query_sources(downstream(2), GROUP, vec![S1]);
+    querier.query_sources(downstream(2), GROUP, vec![S2, S1]);
+    tokio::time::sleep(Duration::from_secs(10)).await;
+
+    // Last Listener Query Count equals the Robustness Variable, one Last Listener Query Interval apart
+    let expected = (0..config.robustness as u32).map(|n| config.last_listener_query_interval() * n).collect::<Vec<_>>();
+    for n in [1, 2] {
+        let queries = transport.specific_queries(downstream(n));
+        let times = queries.iter().map(|sent| sent.at).collect::<Vec<_>>();
+        assert_eq!(offsets(start, &times), expected);
+        for sent in queries {
+            assert_eq!(sent.destination, GROUP);
+            assert_eq!(sent.query.group_address, GROUP);
+            assert_eq!(mld::decode_max_response_code(sent.query.maximum_response_delay), config.last_listener_query_interval);
+            let source_addrs = if n == 1 { vec![] } else { vec![S1, S2] };
+            assert_eq!(sent.query.source_addresses, source_addrs);
+        }
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn non_querier_sends_no_specific_queries() {
+    let (querier, transport) = run_querier(MldQuerierConfig::default());
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    querier.receive_query(downstream(1), LOWER_ADDR, &other_query());
+
+    querier.query_group(downstream(1), GROUP);
+    querier.query_sources(downstream(1), GROUP, vec![S1]);
+    tokio::time::sleep(Duration::from_secs(10)).await;
+    assert!(transport.specific_queries(downstream(1)).is_empty());
+}
@@ -0,0 +1,77 @@
+//! `V2MulticastListenerReport::split` must keep every report within the size limit (RFC 3810 section 5.2.15).
+
+use ftthd::icmp6::mld::*;
+use ftthd::icmp6::{Icmp6Packet, Icmp6Writer};
+
+use proptest::prelude::*;
+
+use std::net::Ipv6Addr;
+
+fn record() -> impl Strategy<Value = MulticastReportRecord> {
+    (1u8..=6, any::<u128>(), prop::collection::vec(any::<u128>(), 0..200)).prop_map(|(record_type, group, sources)| {
+        MulticastReportRecord {
+            record_type,
+            multicast_address: Ipv6Addr::from(group),
+            source_addresses: sources.into_iter().map(Ipv6Addr::from).collect(),
+        }
+    })
+}
+
+proptest! {
+    #[test]
+    fn reports_fit(records in prop::collection::vec(record(), 0..16), mtu in prop::option::of(1280u32..9000)) {
+        let max_len = max_report_len(mtu);
+        let reports = V2MulticastListenerReport::split(records.clone(), max_len);
+
+        let mut writer = Icmp6Writer::new();
+        for report in &reports {
+            writer.set_packet(Icmp6Packet::V2MulticastListenerReport(report.clone())).unwrap();
+            prop_assert!(writer.packet().data().len() <= max_len);
+        }
+
+        // only exclude-mode records lose sources
+        let split = reports.into_iter().flat_map(|report| report.records).collect::<Vec<_>>();
+        for record in records {
+            let parts = split.iter().filter(|part| part.multicast_address == record.multicast_address && part.record_type == record.record_type);
+            let sources = parts.flat_map(|part| part.source_addresses.iter().cloned()).collect::<Vec<_>>();
+            if record.record_type == MODE_IS_EXCLUDE || record.record_type == CHANGE_TO_EXCLUDE_MODE {
+                prop_assert!(record.source_addresses.starts_with(&sources[..sources.len().min(record.source_addresses.len())]));
+            } else {
+                prop_assert!(sources.len() >= record.source_addresses.len());
+            }
+        }
+    }
+}
+
+#[test]
+fn aggregates_small_records() {
+    let records = (0..10u16).map(|i| MulticastReportRecord {
+        record_type: MODE_IS_EXCLUDE,
+        multicast_address: Ipv6Addr::new(0xff3e, 0, 0, 0, 0, 0, 0, i),
+        source_addresses: Vec::new(),
+    }).collect::<Vec<_>>();
+    let reports = V2MulticastListenerReport::split(records.clone(), MAX_REPORT_LEN);
+    assert_eq!(reports, vec![V2MulticastListenerReport { records }]);
+}
+
+#[test]
+fn report_len_follows_link_mtu() {
+    assert_eq!(max_report_len(None), MAX_REPORT_LEN);
+    assert_eq!(max_report_len(Some(1280)), MAX_REPORT_LEN);
+    assert_eq!(max_report_len(Some(1500)), 1452);
+    assert_eq!(max_report_len(Some(9000)), 8952);
+
+    // a link can't carry IPv6 below the minimum MTU, so that is what we assume
+    assert_eq!(max_report_len(Some(576)), MAX_REPORT_LEN);
+}
+
+#[test]
+fn ethernet_mtu_needs_fewer_reports() {
+    let records = vec![MulticastReportRecord {
+        record_type: ALLOW_NEW_SOURCES,
+        multicast_address: Ipv6Addr::new(0xff3e, 0, 0, 0, 0, 0, 0, 1),
+        source_addresses: (0..80u16).map(|i| Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, i)).collect(),
+    }];
+    assert_eq!(V2MulticastListenerReport::split(records.clone(), max_report_len(None)).len(), 2);
+    assert_eq!(V2MulticastListenerReport::split(records, max_report_len(Some(1500))).len(), 1);
+}
@@ -0,0 +1,290 @@
+//! MLDv2 router state per downstream interface (RFC 3810 section 7.4) and the merged proxy state (RFC 4605 section 4.1).
+
+mod common;
+
+use common::{downstream, manager};
+use ftthd::config::MldQuerierConfig;
+use ftthd::group::{FilterMode, MldMembership};
+use ftthd::icmp6::mld::*;
+
+use tokio::time::Instant;
+
+use std::collections::HashSet;
+use std::net::Ipv6Addr;
+use std::time::Duration;
+
+const GROUP: Ipv6Addr = Ipv6Addr::new(0xff3e, 0, 0, 0, 0, 0, 0x8000, 1);
+const S1: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+const S2: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2);
+const S3: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 3);
+
+fn record(record_type: u8, source_addresses: &[Ipv6Addr]) -> MulticastReportRecord {
+    MulticastReportRecord {
+        record_type,
+        multicast_address: GROUP,
+        source_addresses: source_addresses.to_vec(),
+    }
+}
+
+fn set(addrs: &[Ipv6Addr]) -> HashSet<Ipv6Addr> {
+    addrs.iter().cloned().collect()
+}
+
+/// Expected value of a source or group timer
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Timer {
+    /// set before the record under test arrived
+    Old,
+
+    /// set to the Multicast Address Listening Interval by the record under test
+    Mali,
+
+    /// excluded source in EXCLUDE mode
+    Excluded,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Initial {
+    /// INCLUDE({S1, S2})
+    Include,
+
+    /// EXCLUDE({S1}, {S2})
+    Exclude,
+}
+
+struct Case {
+    initial: Initial,
+    record_type: u8,
+    filter_mode: FilterMode,
+    sources: &'static [(Ipv6Addr, Timer)],
+
+    /// group timer, only checked in EXCLUDE mode
+    group_timer: Timer,
+
+    /// sources of each Group-and-Source-Specific query, an empty list for a Group-Specific query
+    queries: &'static [&'static [Ipv6Addr]],
+}
+
+/// RFC 3810 sections 7.4.1 and 7.4.2 for a record with sources B = {S2, S3}
+const CASES: &[Case] = &[
+    Case {
+        initial: Initial::Include,
+        record_type: MODE_IS_INCLUDE,
+        filter_mode: FilterMode::Include,
+        sources: &[(S1, Timer::Old), (S2, Timer::Mali), (S3, Timer::Mali)],
+        group_timer: Timer::Old,
+        queries: &[],
+    },
+    Case {
+        initial: Initial::Include,
+        record_type: ALLOW_NEW_SOURCES,
+        filter_mode: FilterMode::Include,
+        sources: &[(S1, Timer::Old), (S2, Timer::Mali), (S3, Timer::Mali)],
+        group_timer: Timer::Old,
+        queries: &[],
+    },
+    Case {
+        initial: Initial::Include,
+        record_type: CHANGE_TO_INCLUDE_MODE,
+        filter_mode: FilterMode::Include,
+        sources: &[(S1, Timer::Old), (S2, Timer::Mali), (S3, Timer::Mali)],
+        group_timer: Timer::Old,
+        queries: &[&[S1]],
+    },
+    Case {
+        initial: Initial::Include,
+        record_type: BLOCK_OLD_SOURCES,
+        filter_mode: FilterMode::Include,
+        sources: &[(S1, Timer::Old), (S2, Timer::Old)],
+        group_timer: Timer::Old,
+        queries: &[&[S2]],
+    },
+    Case {
+        initial: Initial::Include,
+        record_type: MODE_IS_EXCLUDE,
+        filter_mode: FilterMode::Exclude,
+        sources: &[(S2, Timer::Old), (S3, Timer::Excluded)],
+        group_timer: Timer::Mali,
+        queries: &[],
+    },
+    Case {
+        initial: Initial::Include,
+        record_type: CHANGE_TO_EXCLUDE_MODE,
+        filter_mode: FilterMode::Exclude,
+        sources: &[(S2, Timer::Old), (S3, Timer::Excluded)],
+        group_timer: Timer::Mali,
+        queries: &[&[S2]],
+    },
+    Case {
+        initial: Initial::Exclude,
+        record_type: MODE_IS_INCLUDE,
+        filter_mode: FilterMode::Exclude,
+        sources: &[(S1, Timer::Old), (S2, Timer::Mali), (S3, Timer::Mali)],
+        group_timer: Timer::Old,
+        queries: &[],
+    },
+    Case {
+        initial: Initial::Exclude,
+        record_type: ALLOW_NEW_SOURCES,
+        filter_mode: FilterMode::Exclude,
+        sources: &[(S1, Timer::Old), (S2, Timer::Mali), (S3, Timer::Mali)],
+        group_timer: Timer::Old,
+        queries: &[],
+    },
+    Case {
+        initial: Initial::Exclude,
+        record_type: CHANGE_TO_INCLUDE_MODE,
+        filter_mode: FilterMode::Exclude,
+        sources: &[(S1, Timer::Old), (S2, Timer::Mali), (S3, Timer::Mali)],
+        group_timer: Timer::Old,
+        queries: &[&[S1], &[]],
+    },
+    Case {
+        initial: Initial::Exclude,
+        record_type: BLOCK_OLD_SOURCES,
+        filter_mode: FilterMode::Exclude,
+        sources: &[(S1, Timer::Old), (S2, Timer::Excluded), (S3, Timer::Old)],
+        group_timer: Timer::Old,
+        queries: &[&[S3]],
+    },
+    Case {
+        initial: Initial::Exclude,
+        record_type: MODE_IS_EXCLUDE,
+        filter_mode: FilterMode::Exclude,
+        sources: &[(S2, Timer::Excluded), (S3, Timer::Mali)],
+        group_timer: Timer::Mali,
+        queries: &[],
+    },
+    Case {
+        initial: Initial::Exclude,
+        record_type: CHANGE_TO_EXCLUDE_MODE,
+        filter_mode: FilterMode::Exclude,
+        sources: &[(S2, Timer::Excluded), (S3, Timer::Old)],
+        group_timer: Timer::Mali,
+        queries: &[&[S3]],
+    },
+];
+
+#[tokio::test(start_paused = true)]
+async fn process_record_follows_rfc3810_tables() {
+    let mali = MldQuerierConfig::default().multicast_address_listening_interval();
+    let if_index = downstream(1);
+
+    for case in CASES {
+        let mut manager = manager();
+        let start = Instant::now();
+        match case.initial {
+            Initial::Include => {
+                manager.process_record(if_index, &record(MODE_IS_INCLUDE, &[S1, S2]));
+            }
+            Initial::Exclude => {
+                manager.process_record(if_index, &record(MODE_IS_EXCLUDE, &[S2]));
+                manager.process_record(if_index, &record(ALLOW_NEW_SOURCES, &[S1]));
+            }
+        }
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        let queries = manager.process_record(if_index, &record(case.record_type, &[S2, S3]));
+
+        let context = format!("{:?} state, record type {}", case.initial, case.record_type);
+        let timer = |timer: Timer| match timer {
+            Timer::Old => Some(start + mali),
+            Timer::Mali => Some(Instant::now() + mali),
+            Timer::Excluded => None,
+        };
+        let subscription = manager.get_subscription(if_index, GROUP).unwrap();
+        assert_eq!(subscription.filter_mode, case.filter_mode, "{}", context);
+        let expected = case.sources.iter().map(|(addr, expected)| (*addr, timer(*expected))).collect();
+        assert_eq!(subscription.sources, expected, "{}", context);
+        if case.filter_mode == FilterMode::Exclude {
+            assert_eq!(Some(subscription.group_timer), timer(case.group_timer), "{}", context);
+        }
+
+        let queries = queries.into_iter()
+            .inspect(|query| assert_eq!(query.group_addr, GROUP, "{}", context))
+            .map(|query| set(&query.source_addrs))
+            .collect::<Vec<_>>();
+        let expected = case.queries.iter().map(|sources| set(sources)).collect::<Vec<_>>();
+        assert_eq!(queries, expected, "{}", context);
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn include_downstreams_merge_to_union() {
+    let mut manager = manager();
+    manager.process_record(downstream(1), &record(MODE_IS_INCLUDE, &[S1, S2]));
+    manager.process_record(downstream(2), &record(MODE_IS_INCLUDE, &[S2, S3]));
+
+    assert_eq!(manager.get_membership(GROUP), Some(&MldMembership {
+        filter_mode: FilterMode::Include,
+        source_addrs: set(&[S1, S2, S3]),
+    }));
+    let report = manager.current_state_record(GROUP).unwrap();
+    assert_eq!(report.record_type, MODE_IS_INCLUDE);
+    assert_eq!(set(&report.source_addresses), set(&[S1, S2, S3]));
+}
+
+#[tokio::test(start_paused = true)]
+async fn include_downstream_removes_sources_from_exclude_list() {
+    let mut manager = manager();
+    manager.process_record(downstream(1), &record(MODE_IS_EXCLUDE, &[S1, S2]));
+    manager.process_record(downstream(2), &record(MODE_IS_INCLUDE, &[S1]));
+
+    assert_eq!(manager.get_membership(GROUP), Some(&MldMembership {
+        filter_mode: FilterMode::Exclude,
+        source_addrs: set(&[S2]),
+    }));
+    let report = manager.current_state_record(GROUP).unwrap();
+    assert_eq!(report.record_type, MODE_IS_EXCLUDE);
+    assert_eq!(set(&report.source_addresses), set(&[S2]));
+}
+
+#[tokio::test(start_paused = true)]
+async fn exclude_downstreams_merge_to_intersection() {
+    let mut manager = manager();
+    manager.process_record(downstream(1), &record(MODE_IS_EXCLUDE, &[S1, S2]));
+    manager.process_record(downstream(2), &record(MODE_IS_EXCLUDE, &[S2, S3]));
+    assert_eq!(manager.get_membership(GROUP), Some(&MldMembership {
+        filter_mode: FilterMode::Exclude,
+        source_addrs: set(&[S2]),
+    }));
+
+    // an INCLUDE listener for the only source every EXCLUDE listener blocks
+    manager.process_record(downstream(3), &record(MODE_IS_INCLUDE, &[S2]));
+    assert_eq!(manager.get_membership(GROUP), Some(&MldMembership {
+        filter_mode: FilterMode::Exclude,
+        source_addrs: HashSet::new(),
+    }));
+}
+
+#[tokio::test(start_paused = true)]
+async fn exclude_downstream_changes_upstream_filter_mode() {
+    let mut manager = manager();
+    manager.process_record(downstream(1), &record(MODE_IS_INCLUDE, &[S1]));
+    let reports = manager.take_due_reports();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].record_type, ALLOW_NEW_SOURCES);
+    assert_eq!(set(&reports[0].source_addresses), set(&[S1]));
+
+    // once the first report went out, the retransmission is pending
+    tokio::time::advance(Duration::from_secs(2)).await;
+    manager.take_due_reports();
+
+    manager.process_record(downstream(2), &record(CHANGE_TO_EXCLUDE_MODE, &[S1, S2]));
+    let reports = manager.take_due_reports();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].record_type, CHANGE_TO_EXCLUDE_MODE);
+    assert_eq!(set(&reports[0].source_addresses), set(&[S2]));
+
+    // the last EXCLUDE listener leaving reverts the upstream to INCLUDE mode
+    manager.process_record(downstream(2), &record(CHANGE_TO_INCLUDE_MODE, &[]));
+    tokio::time::advance(Duration::from_secs(1)).await;
+    manager.process_record(downstream(1), &record(MODE_IS_INCLUDE, &[S1]));
+    let group_timer = manager.get_subscription(downstream(2), GROUP).unwrap().group_timer;
+    tokio::time::advance(group_timer - Instant::now()).await;
+    manager.handle_timers();
+    let reports = manager.take_due_reports();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].record_type, CHANGE_TO_INCLUDE_MODE);
+    assert_eq!(set(&reports[0].source_addresses), set(&[S1]));
+}
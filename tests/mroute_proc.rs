@@ -0,0 +1,56 @@
+//! Parsing of the kernel multicast routing tables under /proc/net.
+
+use ftthd::icmp6::mroute::*;
+
+#[test]
+fn mr_cache_parsed() {
+    let contents = "\
+Group                            Origin                           Iif      Pkts  Bytes     Wrong  Oifs
+ff3e:0000:0000:0000:0000:0000:8000:0001 2001:0db8:0000:0000:0000:0000:0000:0001 1         120    157920        0  2:1    3:1  
+ff3e:0000:0000:0000:0000:0000:8000:0002 2001:0db8:0000:0000:0000:0000:0000:0002 -1          0        0        0
+malformed
+";
+
+    let entries = parse_mr_cache(contents);
+    assert_eq!(entries, vec![
+        MfcCacheEntry {
+            group_addr: "ff3e::8000:1".parse().unwrap(),
+            source_addr: "2001:db8::1".parse().unwrap(),
+            parent: Some(1),
+            counters: SgCounters { packets: 120, bytes: 157920, wrong_if: 0 },
+            output_mifs: vec![(2, 1), (3, 1)],
+        },
+        MfcCacheEntry {
+            group_addr: "ff3e::8000:2".parse().unwrap(),
+            source_addr: "2001:db8::2".parse().unwrap(),
+            parent: None,
+            counters: SgCounters::default(),
+            output_mifs: Vec::new(),
+        },
+    ]);
+}
+
+#[test]
+fn mr_vif_parsed() {
+    let contents = "\
+Interface      BytesIn  PktsIn  BytesOut PktsOut Flags
+ 1 eth0         157920     120         0       0 00000
+ 2 eth1              0       0    157920     120 00001
+";
+
+    let entries = parse_mr_vif(contents);
+    assert_eq!(entries, vec![
+        MifEntry {
+            mif: 1,
+            if_name: "eth0".to_string(),
+            counters: MifCounters { in_packets: 120, in_bytes: 157920, out_packets: 0, out_bytes: 0 },
+            flags: 0,
+        },
+        MifEntry {
+            mif: 2,
+            if_name: "eth1".to_string(),
+            counters: MifCounters { in_packets: 0, in_bytes: 0, out_packets: 120, out_bytes: 157920 },
+            flags: 1,
+        },
+    ]);
+}
@@ -0,0 +1,36 @@
+//! Multicast routing upcalls share the raw ICMPv6 socket and must be told apart from ICMPv6 messages.
+
+use ftthd::icmp6::socket::{Mrt6Msg, Mrt6MsgType};
+
+use proptest::prelude::*;
+
+use std::net::Ipv6Addr;
+
+#[test]
+fn nocache_decoded() {
+    let source: Ipv6Addr = "2001:db8::1".parse().unwrap();
+    let group: Ipv6Addr = "ff3e::8000:1".parse().unwrap();
+
+    let mut data = vec![0, 1];
+    data.extend_from_slice(&3u16.to_ne_bytes());
+    data.extend_from_slice(&[0; 4]);
+    data.extend_from_slice(&source.octets());
+    data.extend_from_slice(&group.octets());
+
+    assert_eq!(Mrt6Msg::parse(&data), Some(Mrt6Msg {
+        msg_type: Mrt6MsgType::NoCache,
+        mif: 3,
+        source_addr: source,
+        group_addr: group,
+    }));
+    assert_eq!(Mrt6Msg::parse(&data[..39]), None);
+}
+
+proptest! {
+    #[test]
+    fn icmp6_is_not_upcall(icmp6_type in 1u8..=255, rest in prop::collection::vec(any::<u8>(), 0..128)) {
+        let mut data = vec![icmp6_type];
+        data.extend(rest);
+        prop_assert_eq!(Mrt6Msg::parse(&data), None);
+    }
+}
@@ -0,0 +1,67 @@
+//! Router Advertisement intervals (RFC 4861 sections 6.2.1 and 6.2.4).
+
+use ftthd::config::RaServerConfig;
+use ftthd::ra::{advertising_interval_range, unsolicited_interval, MAX_INITIAL_RTR_ADVERT_INTERVAL, MAX_INITIAL_RTR_ADVERTISEMENTS};
+
+use std::time::Duration;
+
+fn config(max_interval: u32, min_interval: Option<u32>) -> RaServerConfig {
+    RaServerConfig {
+        max_interval,
+        min_interval,
+        ..Default::default()
+    }
+}
+
+fn secs(secs: u64) -> Duration {
+    Duration::from_secs(secs)
+}
+
+#[test]
+fn default_min_is_a_third_of_max() {
+    assert_eq!(advertising_interval_range(&RaServerConfig::default()), (secs(198), secs(600)));
+    assert_eq!(advertising_interval_range(&config(9, None)), (secs(3), secs(9)));
+    assert_eq!(advertising_interval_range(&config(1800, None)), (secs(594), secs(1800)));
+}
+
+#[test]
+fn default_min_is_three_quarters_of_small_max() {
+    assert_eq!(advertising_interval_range(&config(4, None)), (secs(3), secs(4)));
+    assert_eq!(advertising_interval_range(&config(8, None)), (secs(6), secs(8)));
+}
+
+#[test]
+fn configured_min_is_clamped() {
+    assert_eq!(advertising_interval_range(&config(600, Some(100))), (secs(100), secs(600)));
+    assert_eq!(advertising_interval_range(&config(600, Some(600))), (secs(450), secs(600)));
+    assert_eq!(advertising_interval_range(&config(600, Some(1))), (secs(3), secs(600)));
+}
+
+#[test]
+fn max_is_clamped() {
+    assert_eq!(advertising_interval_range(&config(1, None)), (secs(3), secs(4)));
+    assert_eq!(advertising_interval_range(&config(3600, None)), (secs(1188), secs(1800)));
+}
+
+#[test]
+fn intervals_stay_within_range() {
+    for (max_interval, min_interval) in [(4, None), (8, None), (600, None), (600, Some(600)), (30, Some(10))] {
+        let config = config(max_interval, min_interval);
+        let (min, max) = advertising_interval_range(&config);
+        for _ in 0..100 {
+            let interval = unsolicited_interval(&config, MAX_INITIAL_RTR_ADVERTISEMENTS);
+            assert!(interval >= min && interval <= max, "{:?} not within {:?}..={:?}", interval, min, max);
+        }
+    }
+}
+
+#[test]
+fn initial_intervals_are_capped() {
+    let config = RaServerConfig::default();
+    for initial_sent in 0..MAX_INITIAL_RTR_ADVERTISEMENTS {
+        for _ in 0..100 {
+            assert!(unsolicited_interval(&config, initial_sent) <= MAX_INITIAL_RTR_ADVERT_INTERVAL);
+        }
+    }
+    assert!((0..100).any(|_| unsolicited_interval(&config, MAX_INITIAL_RTR_ADVERTISEMENTS) > MAX_INITIAL_RTR_ADVERT_INTERVAL));
+}
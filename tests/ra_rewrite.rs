@@ -0,0 +1,190 @@
+//! Rewrite rules applied to relayed Router Advertisements, checked on the encoded and re-parsed packet.
+
+use ftthd::config::{RaRewriteConfig, RaRouteConfig, RoutePreference};
+use ftthd::icmp6::ndp::*;
+use ftthd::icmp6::packet::{Packet, PacketHopLimit};
+use ftthd::icmp6::{Icmp6Packet, Icmp6Parser, Icmp6Writer};
+use ftthd::ra::rewrite_router_advertisement;
+
+use std::net::Ipv6Addr;
+
+const UPSTREAM_DNS: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x53);
+const LOCAL_DNS: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 0x53);
+
+/// Encodes a Router Advertisement and parses it back as if received
+fn round_trip(ra: RouterAdvertisement) -> RouterAdvertisement {
+    let mut writer = Icmp6Writer::new();
+    writer.set_packet(Icmp6Packet::RouterAdvertisement(ra)).unwrap();
+
+    let sent = writer.packet();
+    let mut received = Packet::new();
+    received.data[..sent.data().len()].copy_from_slice(sent.data());
+    received.data_len = sent.data().len();
+    received.hop_limit = Some(PacketHopLimit { hop_limit: 255 });
+
+    match Icmp6Parser::new_from_packet(received).parse().unwrap() {
+        Icmp6Packet::RouterAdvertisement(ra) => ra,
+        packet => panic!("parsed as {:?}", packet),
+    }
+}
+
+fn prefix_information() -> NdpOption {
+    NdpOption::PrefixInformation(PrefixInformation {
+        prefix_len: 64,
+        on_link: true,
+        autonomous: true,
+        router_address: false,
+        valid_lifetime: 86400,
+        preferred_lifetime: 14400,
+        prefix: Ipv6Addr::new(0x2001, 0xdb8, 0, 1, 0, 0, 0, 0),
+    })
+}
+
+/// RA as heard from the upstream router
+fn upstream_ra() -> RouterAdvertisement {
+    round_trip(RouterAdvertisement {
+        hop_limit: 64,
+        managed_address_configuration: false,
+        other_configuration: false,
+        router_lifetime: 1800,
+        reachable_time: 0,
+        retrans_timer: 0,
+        options: vec![
+            prefix_information(),
+            NdpOption::Mtu(1500),
+            NdpOption::Rdnss(Rdnss {
+                lifetime: 600,
+                servers: vec![UPSTREAM_DNS],
+            }),
+            NdpOption::Dnssl(Dnssl {
+                lifetime: 600,
+                domains: vec!["isp.example".to_owned()],
+            }),
+        ],
+    })
+}
+
+fn rewritten(rule: &RaRewriteConfig) -> RouterAdvertisement {
+    let mut ra = upstream_ra();
+    rewrite_router_advertisement(&mut ra, rule);
+    round_trip(ra)
+}
+
+#[test]
+fn empty_rule_changes_nothing() {
+    assert_eq!(rewritten(&RaRewriteConfig::default()), upstream_ra());
+}
+
+#[test]
+fn header_overrides() {
+    let ra = rewritten(&RaRewriteConfig {
+        router_lifetime: Some(0),
+        hop_limit: Some(255),
+        managed: Some(true),
+        other: Some(true),
+        ..Default::default()
+    });
+
+    assert_eq!(ra.router_lifetime, 0);
+    assert_eq!(ra.hop_limit, 255);
+    assert!(ra.managed_address_configuration);
+    assert!(ra.other_configuration);
+    assert_eq!(ra.options, upstream_ra().options);
+}
+
+#[test]
+fn dns_options_are_replaced() {
+    let ra = rewritten(&RaRewriteConfig {
+        drop_rdnss: true,
+        drop_dnssl: true,
+        rdnss: vec![LOCAL_DNS],
+        dnssl: vec!["home.example".to_owned()],
+        dns_lifetime: 1200,
+        ..Default::default()
+    });
+
+    assert_eq!(ra.options, vec![
+        prefix_information(),
+        NdpOption::Mtu(1500),
+        NdpOption::Rdnss(Rdnss {
+            lifetime: 1200,
+            servers: vec![LOCAL_DNS],
+        }),
+        NdpOption::Dnssl(Dnssl {
+            lifetime: 1200,
+            domains: vec!["home.example".to_owned()],
+        }),
+    ]);
+}
+
+#[test]
+fn dns_options_are_removed() {
+    let ra = rewritten(&RaRewriteConfig {
+        drop_rdnss: true,
+        drop_dnssl: true,
+        ..Default::default()
+    });
+
+    assert_eq!(ra.options, vec![prefix_information(), NdpOption::Mtu(1500)]);
+}
+
+#[test]
+fn mtu_is_clamped_or_added() {
+    let ra = rewritten(&RaRewriteConfig {
+        max_mtu: Some(1454),
+        ..Default::default()
+    });
+    assert!(ra.options.contains(&NdpOption::Mtu(1454)));
+    assert!(!ra.options.contains(&NdpOption::Mtu(1500)));
+
+    // a larger bound leaves the upstream MTU alone
+    let ra = rewritten(&RaRewriteConfig {
+        max_mtu: Some(9000),
+        ..Default::default()
+    });
+    assert_eq!(ra.options, upstream_ra().options);
+
+    let mut ra = upstream_ra();
+    ra.options.retain(|option| !matches!(option, NdpOption::Mtu(_)));
+    rewrite_router_advertisement(&mut ra, &RaRewriteConfig {
+        max_mtu: Some(1454),
+        ..Default::default()
+    });
+    assert_eq!(round_trip(ra).options.last(), Some(&NdpOption::Mtu(1454)));
+}
+
+#[test]
+fn route_information_is_added() {
+    let ra = rewritten(&RaRewriteConfig {
+        routes: vec![
+            RaRouteConfig {
+                prefix: Ipv6Addr::new(0x2001, 0xdb8, 0x100, 0, 0, 0, 0, 0),
+                prefix_len: 40,
+                preference: RoutePreference::High,
+                lifetime: 3600,
+            },
+            RaRouteConfig {
+                prefix: Ipv6Addr::UNSPECIFIED,
+                prefix_len: 0,
+                preference: RoutePreference::Low,
+                lifetime: 0,
+            },
+        ],
+        ..Default::default()
+    });
+
+    let mut expected = upstream_ra().options;
+    expected.push(NdpOption::RouteInformation(RouteInformation {
+        prefix_len: 40,
+        preference: 0b01,
+        lifetime: 3600,
+        prefix: Ipv6Addr::new(0x2001, 0xdb8, 0x100, 0, 0, 0, 0, 0),
+    }));
+    expected.push(NdpOption::RouteInformation(RouteInformation {
+        prefix_len: 0,
+        preference: 0b11,
+        lifetime: 0,
+        prefix: Ipv6Addr::UNSPECIFIED,
+    }));
+    assert_eq!(ra.options, expected);
+}